@@ -0,0 +1,179 @@
+//! Room access control: passwords and invites
+//!
+//! A room can be protected by a password, stored only as an Argon2 hash,
+//! and/or made invite-only. The host hands out invite tokens that are
+//! valid for a limited time and optionally for a single join. A valid
+//! invite also lets its holder skip the password.
+//!
+//! Hashing and verifying are deliberately slow, so the ChatServer actor
+//! runs them on the blocking thread pool instead of inline, a few at a
+//! time.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+/// Longest accepted room password, in bytes
+pub const MAX_PASSWORD_LEN: usize = 128;
+
+/// Invites a room keeps at once; the oldest is dropped to make room
+pub const MAX_INVITES: usize = 32;
+
+/// Wait suggested to a client when every password slot is busy
+pub const PASSWORD_BUSY_RETRY: Duration = Duration::from_millis(500);
+
+/// Check a requested room password before hashing it
+pub fn check_password(password: &str) -> Result<(), AppError> {
+    if password.is_empty() || password.len() > MAX_PASSWORD_LEN {
+        return Err(AppError::InvalidMessage {
+            field: Some("password".to_string()),
+            reason: format!("password must be 1 to {} bytes", MAX_PASSWORD_LEN),
+        });
+    }
+    Ok(())
+}
+
+/// Hash a room password (PHC string format)
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::PasswordHash(e.to_string()))
+}
+
+/// Check a password against a hash from `hash_password`
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// An invite handed out by a room's host
+#[derive(Debug, Clone, Copy)]
+struct Invite {
+    expires_at: Instant,
+    single_use: bool,
+}
+
+/// Who may join a room
+#[derive(Debug, Default)]
+pub struct RoomAccess {
+    /// Argon2 hash of the room password, if any
+    pub password_hash: Option<String>,
+    /// Joining requires an invite
+    pub invite_only: bool,
+    /// Outstanding invites by token
+    invites: HashMap<String, Invite>,
+}
+
+impl RoomAccess {
+    /// Create access rules with no invites yet
+    pub fn new(password_hash: Option<String>, invite_only: bool) -> Self {
+        Self {
+            password_hash,
+            invite_only,
+            invites: HashMap::new(),
+        }
+    }
+
+    /// Check if joining needs a password or an invite
+    pub fn is_open(&self) -> bool {
+        self.password_hash.is_none() && !self.invite_only
+    }
+
+    /// Create an invite valid for `ttl`, returning its token
+    pub fn create_invite(&mut self, ttl: Duration, single_use: bool) -> String {
+        let now = Instant::now();
+        self.invites.retain(|_, invite| invite.expires_at > now);
+        if self.invites.len() >= MAX_INVITES {
+            let oldest = self
+                .invites
+                .iter()
+                .min_by_key(|(_, invite)| invite.expires_at)
+                .map(|(token, _)| token.clone());
+            if let Some(token) = oldest {
+                self.invites.remove(&token);
+            }
+        }
+
+        let token = Uuid::new_v4().simple().to_string();
+        let invite = Invite {
+            expires_at: now + ttl,
+            single_use,
+        };
+        self.invites.insert(token.clone(), invite);
+        token
+    }
+
+    /// Check an invite, using it up if it is single-use
+    pub fn redeem_invite(&mut self, token: &str) -> bool {
+        let Some(invite) = self.invites.get(token).copied() else {
+            return false;
+        };
+        if invite.expires_at <= Instant::now() {
+            self.invites.remove(token);
+            return false;
+        }
+        if invite.single_use {
+            self.invites.remove(token);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_round_trip() {
+        let hash = hash_password("hunter2").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+        assert!(!verify_password("hunter2", "not a hash"));
+    }
+
+    #[test]
+    fn test_password_length() {
+        assert!(check_password("").is_err());
+        assert!(check_password("x").is_ok());
+        assert!(check_password(&"x".repeat(MAX_PASSWORD_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_invites() {
+        let mut access = RoomAccess::default();
+        let reusable = access.create_invite(Duration::from_secs(60), false);
+        let once = access.create_invite(Duration::from_secs(60), true);
+        let expired = access.create_invite(Duration::ZERO, false);
+
+        assert!(access.redeem_invite(&reusable));
+        assert!(access.redeem_invite(&reusable));
+        assert!(access.redeem_invite(&once));
+        assert!(!access.redeem_invite(&once));
+        assert!(!access.redeem_invite(&expired));
+        assert!(!access.redeem_invite("made-up"));
+    }
+
+    #[test]
+    fn test_invite_cap_drops_oldest() {
+        let mut access = RoomAccess::default();
+        let first = access.create_invite(Duration::from_secs(60), false);
+        for _ in 0..MAX_INVITES {
+            access.create_invite(Duration::from_secs(120), false);
+        }
+        assert_eq!(access.invites.len(), MAX_INVITES);
+        assert!(!access.redeem_invite(&first));
+    }
+}
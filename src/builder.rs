@@ -0,0 +1,225 @@
+//! Embedding API
+//!
+//! `ChatServerBuilder` wires up what `main.rs` otherwise does by hand: the
+//! command channel, the ChatServer actor task, the shared rate limiter and
+//! the accept loop. The resulting `ServerHandle` lets a host application
+//! serve one or more listeners, hand over streams it accepted itself,
+//! query stats, and shut everything down gracefully.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tracing::{error, info, warn};
+
+use crate::codes::RoomCodeGenerator;
+use crate::config::ServerConfig;
+use crate::error::AppError;
+use crate::handler::{handle_connection, Peer};
+use crate::history::MessageStore;
+use crate::listener::{Listener, Stream};
+use crate::ratelimit::RateLimiter;
+use crate::server::{ChatServer, ServerCommand, ServerStats};
+use crate::tls::TlsAcceptor;
+
+/// Builds a ChatServer actor and the handle used to drive it
+///
+/// See the crate-level example.
+pub struct ChatServerBuilder {
+    config: ServerConfig,
+    store: Option<Box<dyn MessageStore>>,
+    codes: Option<Box<dyn RoomCodeGenerator>>,
+    tls: Option<TlsAcceptor>,
+}
+
+impl ChatServerBuilder {
+    /// Start from the given settings
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            config,
+            store: None,
+            codes: None,
+            tls: None,
+        }
+    }
+
+    /// Use the given message history backend (in-memory by default)
+    pub fn store(mut self, store: Box<dyn MessageStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Use the given room code generator (`rooms.code_style` by default)
+    pub fn code_generator(mut self, codes: Box<dyn RoomCodeGenerator>) -> Self {
+        self.codes = Some(codes);
+        self
+    }
+
+    /// Terminate TLS on TCP connections accepted by `serve`
+    pub fn tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    /// Spawn the ChatServer actor and return its handle
+    ///
+    /// Fails if the settings don't pass `ServerConfig::validate`. Must be
+    /// called from within a Tokio runtime.
+    pub fn build(self) -> Result<ServerHandle, AppError> {
+        self.config.validate()?;
+        let config = Arc::new(self.config);
+        let (cmd_tx, cmd_rx) = mpsc::channel(config.command_buffer);
+
+        let mut server = ChatServer::new(cmd_rx, config.clone());
+        if let Some(store) = self.store {
+            server = server.with_store(store);
+        }
+        if let Some(codes) = self.codes {
+            server = server.with_code_generator(codes);
+        }
+        let (done_tx, done_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            server.run().await;
+            let _ = done_tx.send(true);
+        });
+
+        Ok(ServerHandle {
+            cmd_tx,
+            limiter: Arc::new(RateLimiter::new(config.rate_limit.clone())),
+            config,
+            tls: self.tls,
+            stop: Arc::new(watch::channel(false).0),
+            done: done_rx,
+        })
+    }
+}
+
+/// Handle on a running ChatServer
+///
+/// Cheap to clone; every clone drives the same server.
+#[derive(Clone)]
+pub struct ServerHandle {
+    cmd_tx: mpsc::Sender<ServerCommand>,
+    config: Arc<ServerConfig>,
+    limiter: Arc<RateLimiter>,
+    tls: Option<TlsAcceptor>,
+    /// Set once `shutdown` is called; stops every `serve` loop
+    stop: Arc<watch::Sender<bool>>,
+    /// Set once the actor has finished
+    done: watch::Receiver<bool>,
+}
+
+impl ServerHandle {
+    /// Sender for the actor's command channel
+    pub fn sender(&self) -> mpsc::Sender<ServerCommand> {
+        self.cmd_tx.clone()
+    }
+
+    /// The settings the server was built with
+    pub fn config(&self) -> &Arc<ServerConfig> {
+        &self.config
+    }
+
+    /// Accept connections on `listener` until `shutdown` is called
+    ///
+    /// After shutdown the listener is closed and its connections get up
+    /// to `shutdown.drain_timeout` to flush before they are cut off; this
+    /// returns once they are gone.
+    pub async fn serve(&self, listener: Listener) {
+        let mut connections = JoinSet::new();
+        let mut stop = self.stop.subscribe();
+        loop {
+            tokio::select! {
+                result = listener.accept() => match result {
+                    Ok((stream, peer)) => {
+                        info!("New connection from {}", peer);
+                        let handle = self.clone();
+                        connections.spawn(async move { handle.serve_accepted(stream, peer).await });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                },
+                // Reap finished handlers so the set doesn't grow forever
+                Some(_) = connections.join_next() => {}
+                _ = stop.wait_for(|stop| *stop) => break,
+            }
+        }
+        drop(listener);
+
+        info!("Draining {} connections", connections.len());
+        let drain = async { while connections.join_next().await.is_some() {} };
+        if timeout(self.config.shutdown.drain_timeout, drain)
+            .await
+            .is_err()
+        {
+            warn!("{} connections did not drain in time", connections.len());
+            connections.shutdown().await;
+        }
+    }
+
+    /// Run a connection the host application accepted itself
+    ///
+    /// Works over any byte stream; TLS, if any, must already be applied.
+    pub async fn serve_stream<S>(&self, stream: S, peer: Peer) -> Result<(), AppError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        handle_connection(
+            stream,
+            peer,
+            self.cmd_tx.clone(),
+            self.config.clone(),
+            self.limiter.clone(),
+        )
+        .await
+    }
+
+    /// Current client and room counts
+    pub async fn stats(&self) -> Result<ServerStats, AppError> {
+        let (reply, rx) = oneshot::channel();
+        self.cmd_tx
+            .send(ServerCommand::Stats { reply })
+            .await
+            .map_err(|_| AppError::ChannelSend)?;
+        rx.await.map_err(|_| AppError::ChannelSend)
+    }
+
+    /// Stop accepting, notify every client and stop the actor
+    ///
+    /// Returns once the actor has finished; `serve` calls return after
+    /// their connections drain. Calling this more than once is harmless.
+    pub async fn shutdown(&self) {
+        if !self.stop.send_replace(true) {
+            let reconnect_after = self.config.shutdown.reconnect_after;
+            let _ = self
+                .cmd_tx
+                .send(ServerCommand::Shutdown {
+                    reason: "server shutting down".to_string(),
+                    reconnect_after: (!reconnect_after.is_zero()).then_some(reconnect_after),
+                })
+                .await;
+        }
+        let _ = self.done.clone().wait_for(|done| *done).await;
+    }
+
+    /// Helper: Apply TLS if configured, then run the connection
+    async fn serve_accepted(&self, stream: Stream, peer: Peer) {
+        // TLS applies to TCP only; the Unix socket is local
+        let result = match (&self.tls, stream) {
+            (Some(tls), Stream::Tcp(stream)) => match tls.accept(stream).await {
+                Ok(stream) => self.serve_stream(stream, peer).await,
+                Err(e) => {
+                    warn!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+            },
+            (_, stream) => self.serve_stream(stream, peer).await,
+        };
+        if let Err(e) = result {
+            error!("Connection handler error: {}", e);
+        }
+    }
+}
@@ -2,11 +2,27 @@
 //!
 //! Represents a connected client with their state and communication channel.
 
-use tokio::sync::mpsc;
+use std::collections::VecDeque;
 
 use crate::error::SendError;
 use crate::message::ServerMessage;
-use crate::types::ClientId;
+use crate::outbox::{self, DeliveryPolicy, OutboxReceiver, OutboxSender};
+use crate::ratelimit::TokenBucket;
+use crate::types::{ClientId, MessageId, ResumeToken};
+
+/// Maximum messages buffered for a client while it is reconnecting
+pub const MAX_PENDING_MESSAGES: usize = 100;
+
+/// Acknowledged `client_msg_id`s remembered per client to catch retries
+pub const MAX_REMEMBERED_ACKS: usize = 64;
+
+/// Acknowledgement of a chat message that carried a `client_msg_id`
+#[derive(Debug, Clone)]
+struct Ack {
+    client_msg_id: String,
+    message_id: MessageId,
+    server_ts: u64,
+}
 
 /// Connected client information
 ///
@@ -18,31 +34,136 @@ pub struct Client {
     pub id: ClientId,
     /// Username (None before setup)
     pub username: Option<String>,
-    /// Server → Client message channel
-    pub sender: mpsc::Sender<ServerMessage>,
+    /// Server → Client message queue
+    pub sender: OutboxSender,
     /// Currently typing flag
     pub is_typing: bool,
+    /// Token that lets a new connection reclaim this client
+    pub resume_token: ResumeToken,
+    /// Buffer collecting messages while the connection is lost
+    ///
+    /// Some while detached: the seat is held for resume.
+    parked: Option<OutboxReceiver>,
+    /// Latest acknowledgements, oldest first
+    acks: VecDeque<Ack>,
+    /// Attempts left to join protected rooms (None until the first one)
+    pub join_attempts: Option<TokenBucket>,
 }
 
 impl Client {
-    /// Create a new client with the given ID and sender channel
-    pub fn new(id: ClientId, sender: mpsc::Sender<ServerMessage>) -> Self {
+    /// Create a new client with the given ID, sender channel and resume token
+    pub fn new(id: ClientId, sender: OutboxSender, resume_token: ResumeToken) -> Self {
         Self {
             id,
             username: None,
             sender,
             is_typing: false,
+            resume_token,
+            parked: None,
+            acks: VecDeque::new(),
+            join_attempts: None,
         }
     }
 
     /// Send a message to this client
     ///
-    /// Returns an error if the channel is closed (client disconnected).
-    pub async fn send(&self, msg: ServerMessage) -> Result<(), SendError> {
-        self.sender
-            .send(msg)
-            .await
-            .map_err(|_| SendError::ChannelClosed)
+    /// Never waits: a full queue is resolved by the outbox delivery policy.
+    /// While detached, messages are buffered (oldest dropped first) for resume.
+    /// Returns an error if the client disconnected or is being dropped
+    /// as a slow consumer.
+    pub fn send(&self, msg: ServerMessage) -> Result<(), SendError> {
+        self.sender.push(msg)
+    }
+
+    /// Stop accepting messages; already queued ones are still delivered
+    pub fn close(&self) {
+        self.sender.close();
+    }
+
+    /// Check if the connection is lost and the seat is held for resume
+    pub fn is_detached(&self) -> bool {
+        self.parked.is_some()
+    }
+
+    /// Mark the connection as lost while keeping the client's state
+    ///
+    /// Further messages are buffered until `reattach`.
+    pub fn detach(&mut self) {
+        let (sender, parked) = outbox::channel(MAX_PENDING_MESSAGES, DeliveryPolicy::DropOldest);
+        self.sender = sender;
+        self.parked = Some(parked);
+        self.is_typing = false;
+    }
+
+    /// Attach a new connection and deliver everything buffered meanwhile
+    ///
+    /// `prelude` is sent first so the client learns it has resumed
+    /// before the buffered messages arrive. Only the newest buffered
+    /// messages that fit in the new queue behind it are delivered, so the
+    /// replay never sets off the delivery policy.
+    pub fn reattach(
+        &mut self,
+        sender: OutboxSender,
+        resume_token: ResumeToken,
+        prelude: ServerMessage,
+    ) {
+        self.sender = sender;
+        self.resume_token = resume_token;
+
+        let _ = self.sender.push(prelude);
+        if let Some(mut parked) = self.parked.take() {
+            let mut buffered = Vec::new();
+            while let Some(msg) = parked.try_recv() {
+                buffered.push(msg);
+            }
+            let room = self.sender.capacity().saturating_sub(self.sender.len());
+            let skip = buffered.len().saturating_sub(room);
+            for msg in buffered.into_iter().skip(skip) {
+                let _ = self.sender.push(msg);
+            }
+        }
+    }
+
+    /// Build the `ChatAck` for a message, remembering its `client_msg_id`
+    pub fn record_ack(
+        &mut self,
+        client_msg_id: Option<String>,
+        message_id: MessageId,
+        server_ts: u64,
+    ) -> ServerMessage {
+        if let Some(id) = &client_msg_id {
+            if self.acks.len() == MAX_REMEMBERED_ACKS {
+                self.acks.pop_front();
+            }
+            self.acks.push_back(Ack {
+                client_msg_id: id.clone(),
+                message_id,
+                server_ts,
+            });
+        }
+        ServerMessage::ChatAck {
+            client_msg_id,
+            message_id,
+            server_ts,
+        }
+    }
+
+    /// The `ChatAck` already sent for `client_msg_id`, if it is remembered
+    pub fn find_ack(&self, client_msg_id: &str) -> Option<ServerMessage> {
+        let ack = self
+            .acks
+            .iter()
+            .find(|a| a.client_msg_id == client_msg_id)?;
+        Some(ServerMessage::ChatAck {
+            client_msg_id: Some(ack.client_msg_id.clone()),
+            message_id: ack.message_id,
+            server_ts: ack.server_ts,
+        })
+    }
+
+    /// Number of messages dropped for this client by the delivery policy
+    pub fn dropped_messages(&self) -> u64 {
+        self.sender.dropped_count()
     }
 
     /// Get the display name for this client
@@ -74,18 +195,19 @@ mod tests {
 
     #[tokio::test]
     async fn test_client_creation() {
-        let (tx, _rx) = mpsc::channel(32);
-        let client = Client::new(ClientId::new(), tx);
+        let (tx, _rx) = outbox::channel(32, DeliveryPolicy::default());
+        let client = Client::new(ClientId::new(), tx, ResumeToken::generate());
 
         assert!(client.username.is_none());
         assert!(!client.is_typing);
         assert_eq!(client.display_name(), "Unknown");
+        assert_eq!(client.dropped_messages(), 0);
     }
 
     #[tokio::test]
     async fn test_client_username() {
-        let (tx, _rx) = mpsc::channel(32);
-        let mut client = Client::new(ClientId::new(), tx);
+        let (tx, _rx) = outbox::channel(32, DeliveryPolicy::default());
+        let mut client = Client::new(ClientId::new(), tx, ResumeToken::generate());
 
         assert!(!client.has_username());
 
@@ -94,4 +216,86 @@ mod tests {
         assert!(client.has_username());
         assert_eq!(client.display_name(), "Alice");
     }
+
+    #[tokio::test]
+    async fn test_client_remembers_acks() {
+        let (tx, _rx) = outbox::channel(32, DeliveryPolicy::default());
+        let mut client = Client::new(ClientId::new(), tx, ResumeToken::generate());
+
+        client.record_ack(None, MessageId(1), 100);
+        client.record_ack(Some("a".to_string()), MessageId(2), 200);
+        assert!(matches!(
+            client.find_ack("a"),
+            Some(ServerMessage::ChatAck {
+                message_id: MessageId(2),
+                server_ts: 200,
+                ..
+            })
+        ));
+        assert!(client.find_ack("b").is_none());
+
+        // Only the latest acknowledgements are kept
+        for i in 0..MAX_REMEMBERED_ACKS {
+            client.record_ack(Some(i.to_string()), MessageId(3 + i as u64), 300);
+        }
+        assert!(client.find_ack("a").is_none());
+        assert!(client.find_ack("0").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_client_detach_and_reattach() {
+        let (tx, _rx) = outbox::channel(32, DeliveryPolicy::default());
+        let mut client = Client::new(ClientId::new(), tx, ResumeToken::generate());
+
+        client.detach();
+        assert!(client.is_detached());
+        client.send(ServerMessage::PartnerTyping).unwrap();
+        client.send(ServerMessage::PartnerLeft).unwrap();
+
+        let (tx, mut rx) = outbox::channel(32, DeliveryPolicy::default());
+        let token = ResumeToken::generate();
+        client.reattach(tx, token.clone(), ServerMessage::PartnerStopTyping);
+
+        assert!(!client.is_detached());
+        assert_eq!(client.resume_token, token);
+        assert!(matches!(
+            rx.recv().await,
+            Some(ServerMessage::PartnerStopTyping)
+        ));
+        assert!(matches!(
+            rx.recv().await,
+            Some(ServerMessage::PartnerTyping)
+        ));
+        assert!(matches!(rx.recv().await, Some(ServerMessage::PartnerLeft)));
+    }
+
+    #[tokio::test]
+    async fn test_reattach_fits_new_queue() {
+        let (tx, _rx) = outbox::channel(32, DeliveryPolicy::default());
+        let mut client = Client::new(ClientId::new(), tx, ResumeToken::generate());
+
+        client.detach();
+        for _ in 0..6 {
+            client.send(ServerMessage::PartnerTyping).unwrap();
+        }
+        client.send(ServerMessage::PartnerLeft).unwrap();
+
+        // The prelude and the newest buffered messages fill the queue exactly
+        let (tx, mut rx) = outbox::channel(4, DeliveryPolicy::DisconnectSlowConsumer);
+        let token = ResumeToken::generate();
+        client.reattach(tx, token, ServerMessage::PartnerStopTyping);
+
+        assert_eq!(client.dropped_messages(), 0);
+        let received: Vec<_> = std::iter::from_fn(|| rx.try_recv()).collect();
+        assert!(matches!(
+            received.as_slice(),
+            [
+                ServerMessage::PartnerStopTyping,
+                ServerMessage::PartnerTyping,
+                ServerMessage::PartnerTyping,
+                ServerMessage::PartnerLeft,
+            ]
+        ));
+        assert!(client.send(ServerMessage::PartnerTyping).is_ok());
+    }
 }
@@ -0,0 +1,185 @@
+//! Room code generators
+//!
+//! Codes are meant to be read aloud and typed in by hand, so the built-in
+//! generators avoid characters that are easy to confuse:
+//! - `CrockfordCodes`: Crockford base32 ("7KQ2XM"), no I, L, O or U
+//! - `DigitCodes`: digits only ("482915"), easy on phone keypads
+//! - `WordCodes`: words and a number ("AMBER-CRAB-42")
+//!
+//! Every generated code is already in the form `RoomCode::from_string`
+//! produces, so a code typed back in (in any case, with O for 0 and I or
+//! L for 1, or grouped with spaces or dashes) finds its room.
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::types::RoomCode;
+
+/// Tries at finding a free code before room creation fails
+pub const MAX_CODE_ATTEMPTS: usize = 32;
+
+/// Default number of words in a word-list code
+pub const DEFAULT_CODE_WORDS: usize = 2;
+
+/// Most words allowed in a word-list code
+pub const MAX_CODE_WORDS: usize = 4;
+
+/// Crockford base32 alphabet
+const CROCKFORD: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Words used by `WordCodes`
+///
+/// None of them contain I, L, O or U, which room code input maps to
+/// digits (or leaves out of the alphabet).
+const WORDS: &[&str] = &[
+    "AMBER", "APEX", "ARCH", "ASPEN", "BADGE", "BAKER", "BEACH", "BEAR", "BERRY", "BRASS", "BRAVE",
+    "BREAD", "BREEZE", "CAMERA", "CANDY", "CANVAS", "CAPE", "CARD", "CEDAR", "CHART", "CHEF",
+    "CHERRY", "CHESS", "CRAB", "CRANE", "CREAM", "CREST", "DANCE", "DART", "DAWN", "DEER",
+    "DESERT", "DESK", "DREAM", "EARTH", "EMBER", "FEAST", "FERN", "FERRET", "FERRY", "FRAME",
+    "GARDEN", "GARNET", "GEAR", "GRAPE", "GRASS", "HAMMER", "HARP", "HARVEST", "HAWK", "HEART",
+    "HEDGE", "JACKET", "JADE", "JASPER", "KARMA", "KAYAK", "MARKET", "MARSH", "MASK", "MESA",
+    "NECTAR", "NEST", "PANTHER", "PAPER", "PEACH", "PECAN", "PEPPER", "RANCH", "RAVEN", "REEF",
+    "SAGE", "SAND", "SCARF", "SHARK", "SKY", "SPARK", "SPEAR", "SPHERE", "STAR", "STEW", "STREAM",
+    "SWAN", "SWEATER", "TANK", "TAPESTRY", "THREAD", "THYME", "TREE", "TREK", "VASE", "WAVE",
+    "WHEAT", "WREATH", "WREN", "YACHT", "YARD", "ZEBRA", "ZEPHYR", "ZEST",
+];
+
+/// Check if the separated groups of a code form a `WordCodes` code:
+/// list words followed by a two-digit number
+pub(crate) fn is_word_code(groups: &[&str]) -> bool {
+    let Some((number, words)) = groups.split_last() else {
+        return false;
+    };
+    !words.is_empty()
+        && words.iter().all(|word| WORDS.contains(word))
+        && number.len() == 2
+        && number.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Source of new room codes
+///
+/// Owned by the ChatServer actor, which retries when a code is already
+/// taken. Generated codes must be unchanged by `RoomCode::from_string`,
+/// or clients typing them in won't find the room: in particular, only
+/// word-list codes may contain separators.
+pub trait RoomCodeGenerator: Send {
+    /// Generate a random code
+    fn generate(&mut self) -> RoomCode;
+}
+
+/// Crockford base32 codes of a fixed length
+#[derive(Debug, Clone)]
+pub struct CrockfordCodes {
+    length: usize,
+}
+
+impl CrockfordCodes {
+    /// Generate codes of `length` characters
+    pub fn new(length: usize) -> Self {
+        Self { length }
+    }
+}
+
+impl RoomCodeGenerator for CrockfordCodes {
+    fn generate(&mut self) -> RoomCode {
+        let mut rng = rand::thread_rng();
+        let code = (0..self.length)
+            .map(|_| CROCKFORD[rng.gen_range(0..CROCKFORD.len())] as char)
+            .collect();
+        RoomCode(code)
+    }
+}
+
+/// Digit-only codes of a fixed length
+#[derive(Debug, Clone)]
+pub struct DigitCodes {
+    length: usize,
+}
+
+impl DigitCodes {
+    /// Generate codes of `length` digits
+    pub fn new(length: usize) -> Self {
+        Self { length }
+    }
+}
+
+impl RoomCodeGenerator for DigitCodes {
+    fn generate(&mut self) -> RoomCode {
+        let mut rng = rand::thread_rng();
+        let code = (0..self.length)
+            .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+            .collect();
+        RoomCode(code)
+    }
+}
+
+/// Dash-separated words followed by a two-digit number
+#[derive(Debug, Clone)]
+pub struct WordCodes {
+    words: usize,
+}
+
+impl WordCodes {
+    /// Generate codes of `words` words plus the number
+    pub fn new(words: usize) -> Self {
+        Self { words }
+    }
+}
+
+impl RoomCodeGenerator for WordCodes {
+    fn generate(&mut self) -> RoomCode {
+        let mut rng = rand::thread_rng();
+        let mut parts: Vec<String> = WORDS
+            .choose_multiple(&mut rng, self.words)
+            .map(|word| word.to_string())
+            .collect();
+        parts.push(format!("{:02}", rng.gen_range(0..100)));
+        RoomCode(parts.join("-"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that typing a generated code back in finds it
+    fn assert_round_trips(generator: &mut dyn RoomCodeGenerator) {
+        for _ in 0..100 {
+            let code = generator.generate();
+            assert_eq!(RoomCode::from_string(code.0.clone()), code);
+        }
+    }
+
+    #[test]
+    fn test_crockford_codes() {
+        let mut generator = CrockfordCodes::new(8);
+        let code = generator.generate();
+        assert_eq!(code.0.len(), 8);
+        assert!(code.0.bytes().all(|c| CROCKFORD.contains(&c)));
+        assert_round_trips(&mut generator);
+    }
+
+    #[test]
+    fn test_digit_codes() {
+        let mut generator = DigitCodes::new(6);
+        let code = generator.generate();
+        assert_eq!(code.0.len(), 6);
+        assert!(code.0.chars().all(|c| c.is_ascii_digit()));
+        assert_round_trips(&mut generator);
+    }
+
+    #[test]
+    fn test_word_codes() {
+        let mut generator = WordCodes::new(3);
+        let code = generator.generate();
+        let parts: Vec<&str> = code.0.split('-').collect();
+        assert_eq!(parts.len(), 4);
+        assert!(parts[..3].iter().all(|part| WORDS.contains(part)));
+        assert_eq!(parts[3].len(), 2);
+
+        assert!(WORDS
+            .iter()
+            .all(|word| !word.contains(['I', 'L', 'O', 'U'])));
+        assert_round_trips(&mut generator);
+    }
+}
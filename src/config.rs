@@ -0,0 +1,800 @@
+//! Server configuration
+//!
+//! `ServerConfig` collects every tunable of the binary. Values are layered,
+//! later sources overriding earlier ones:
+//! 1. Built-in defaults
+//! 2. TOML file (`--config` / `CHAT_CONFIG`)
+//! 3. `CHAT_*` environment variables
+//! 4. Command-line flags
+//!
+//! Every section is optional in the file; missing keys keep their defaults.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use humantime_serde::re::humantime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::codes::{self, CrockfordCodes, DigitCodes, RoomCodeGenerator, WordCodes};
+use crate::error::AppError;
+use crate::handler::ConnectionConfig;
+use crate::history::{self, FileStore, MemoryStore, MessageStore};
+use crate::listener::{FileMode, ListenMode, UnixConfig};
+use crate::message::ClientMessage;
+use crate::outbox::DeliveryPolicy;
+use crate::ratelimit::{RateLimit, RateLimitConfig};
+use crate::room;
+use crate::server::DEFAULT_RESUME_GRACE;
+use crate::tls::{ClientAuth, TlsConfig};
+use crate::types;
+use crate::username::UsernamePolicy;
+
+/// Default server address
+pub const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+/// Default channel buffer size for server commands
+pub const DEFAULT_COMMAND_BUFFER: usize = 256;
+
+/// Default address of the Prometheus metrics endpoint
+pub const DEFAULT_METRICS_BIND: &str = "127.0.0.1:9091";
+
+/// Default time connections get to flush after a shutdown notice
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default reconnect delay suggested to clients on shutdown
+pub const DEFAULT_RECONNECT_AFTER: Duration = Duration::from_secs(5);
+
+/// Default lifetime of a room invite when the host doesn't choose one
+pub const DEFAULT_INVITE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Default longest lifetime a host may give a room invite
+pub const DEFAULT_MAX_INVITE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default limit on password and invite attempts per client
+pub const DEFAULT_JOIN_ATTEMPTS: RateLimit = RateLimit::new(5, 0.05);
+
+/// Default number of room passwords hashed or checked at once
+pub const DEFAULT_MAX_PASSWORD_TASKS: usize = 4;
+
+/// Default age at which a room is closed
+pub const DEFAULT_MAX_ROOM_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default time a room may go without messages or joins
+pub const DEFAULT_ROOM_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Default time a host may wait alone for a guest
+pub const DEFAULT_GUEST_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Default time between checks for expired rooms
+pub const DEFAULT_REAP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default log filter (used when RUST_LOG is not set)
+pub const DEFAULT_LOG_FILTER: &str = "chat_server_v1=info";
+
+/// Effective server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Which listeners to start (tcp, unix, both)
+    pub listen: ListenMode,
+    /// Address the WebSocket TCP listener binds to
+    pub bind: String,
+    /// Unix domain socket settings
+    pub unix: UnixConfig,
+    /// Capacity of the handler → ChatServer command channel
+    pub command_buffer: usize,
+    /// Per-connection settings
+    pub connection: ConnectionConfig,
+    /// TLS (wss://) settings
+    pub tls: TlsConfig,
+    /// Room settings
+    pub rooms: RoomConfig,
+    /// Username rules
+    pub usernames: UsernamePolicy,
+    /// Message history settings
+    pub history: HistoryConfig,
+    /// Per-message-type rate limits
+    pub rate_limit: RateLimitConfig,
+    /// Graceful shutdown settings
+    pub shutdown: ShutdownConfig,
+    /// Metrics endpoint settings
+    pub metrics: MetricsConfig,
+    /// Logging settings
+    pub log: LogConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: ListenMode::default(),
+            bind: DEFAULT_BIND.to_string(),
+            unix: UnixConfig::default(),
+            command_buffer: DEFAULT_COMMAND_BUFFER,
+            connection: ConnectionConfig::default(),
+            tls: TlsConfig::default(),
+            rooms: RoomConfig::default(),
+            usernames: UsernamePolicy::default(),
+            history: HistoryConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            metrics: MetricsConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+/// How room codes are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CodeStyle {
+    /// Crockford base32, e.g. 7KQ2XM
+    #[default]
+    Crockford,
+    /// Digits only, e.g. 482915
+    Digits,
+    /// Words and a number, e.g. AMBER-CRAB-42
+    Words,
+}
+
+/// Room settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoomConfig {
+    /// Kind of room codes to generate
+    pub code_style: CodeStyle,
+    /// Number of characters in generated room codes (crockford, digits)
+    pub code_length: usize,
+    /// Number of words in generated room codes (words)
+    pub code_words: usize,
+    /// Largest capacity a client may request for a room
+    pub max_capacity: usize,
+    /// How long a dropped client's seat is held for resume (0 disables resume)
+    #[serde(with = "humantime_serde")]
+    pub resume_grace: Duration,
+    /// Lifetime of an invite when the host doesn't choose one
+    #[serde(with = "humantime_serde")]
+    pub invite_ttl: Duration,
+    /// Longest lifetime a host may give an invite
+    #[serde(with = "humantime_serde")]
+    pub max_invite_ttl: Duration,
+    /// Joins of protected rooms a client may attempt (password or invite)
+    pub join_attempts: RateLimit,
+    /// Room passwords hashed or checked at once, across all clients
+    pub max_password_tasks: usize,
+    /// Close rooms this old (0 disables)
+    #[serde(with = "humantime_serde")]
+    pub max_lifetime: Duration,
+    /// Close rooms without messages or joins for this long (0 disables)
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: Duration,
+    /// Close rooms whose host has waited alone this long (0 disables)
+    #[serde(with = "humantime_serde")]
+    pub guest_timeout: Duration,
+    /// Time between checks for expired rooms
+    #[serde(with = "humantime_serde")]
+    pub reap_interval: Duration,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        Self {
+            code_style: CodeStyle::default(),
+            code_length: types::DEFAULT_CODE_LENGTH,
+            code_words: codes::DEFAULT_CODE_WORDS,
+            max_capacity: room::MAX_CAPACITY,
+            resume_grace: DEFAULT_RESUME_GRACE,
+            invite_ttl: DEFAULT_INVITE_TTL,
+            max_invite_ttl: DEFAULT_MAX_INVITE_TTL,
+            join_attempts: DEFAULT_JOIN_ATTEMPTS,
+            max_password_tasks: DEFAULT_MAX_PASSWORD_TASKS,
+            max_lifetime: DEFAULT_MAX_ROOM_LIFETIME,
+            idle_timeout: DEFAULT_ROOM_IDLE_TIMEOUT,
+            guest_timeout: DEFAULT_GUEST_TIMEOUT,
+            reap_interval: DEFAULT_REAP_INTERVAL,
+        }
+    }
+}
+
+/// Where room history is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryBackend {
+    /// Bounded ring buffer per room, lost on restart
+    #[default]
+    Memory,
+    /// Ring buffer mirrored to one JSON-lines file per room under `history.dir`
+    File,
+}
+
+impl RoomConfig {
+    /// Create the configured room code generator
+    pub fn code_generator(&self) -> Box<dyn RoomCodeGenerator> {
+        match self.code_style {
+            CodeStyle::Crockford => Box::new(CrockfordCodes::new(self.code_length)),
+            CodeStyle::Digits => Box::new(DigitCodes::new(self.code_length)),
+            CodeStyle::Words => Box::new(WordCodes::new(self.code_words)),
+        }
+    }
+}
+
+/// Message history settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Storage backend
+    pub backend: HistoryBackend,
+    /// Messages kept per room
+    pub capacity: usize,
+    /// Directory used by the file backend
+    pub dir: PathBuf,
+    /// Messages replayed to a client joining a room
+    pub replay_limit: usize,
+    /// Largest page a client may fetch at once
+    pub max_fetch_limit: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            backend: HistoryBackend::default(),
+            capacity: history::DEFAULT_ROOM_HISTORY,
+            dir: PathBuf::from("history"),
+            replay_limit: history::REPLAY_LIMIT,
+            max_fetch_limit: history::MAX_FETCH_LIMIT,
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// Open the configured history backend
+    pub fn open_store(&self) -> Result<Box<dyn MessageStore>, AppError> {
+        Ok(match self.backend {
+            HistoryBackend::Memory => Box::new(MemoryStore::new(self.capacity)),
+            HistoryBackend::File => Box::new(FileStore::open(&self.dir, self.capacity)?),
+        })
+    }
+}
+
+/// Graceful shutdown settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// How long connections get to flush their queues before being cut off
+    #[serde(with = "humantime_serde")]
+    pub drain_timeout: Duration,
+    /// Reconnect delay suggested to clients (0 leaves it out)
+    #[serde(with = "humantime_serde")]
+    pub reconnect_after: Duration,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+            reconnect_after: DEFAULT_RECONNECT_AFTER,
+        }
+    }
+}
+
+/// Metrics endpoint settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` at all
+    pub enabled: bool,
+    /// Address the metrics endpoint binds to (separate from the chat port)
+    pub bind: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            bind: DEFAULT_METRICS_BIND.to_string(),
+        }
+    }
+}
+
+/// Logging settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `tracing` filter directive, e.g. `chat_server_v1=debug`
+    pub filter: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: DEFAULT_LOG_FILTER.to_string(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Parse a TOML document (missing keys keep their defaults)
+    pub fn from_toml(text: &str) -> Result<Self, AppError> {
+        toml::from_str(text).map_err(|e| AppError::Config(e.to_string()))
+    }
+
+    /// Render the configuration as TOML
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("config is always representable as TOML")
+    }
+
+    /// Build the effective configuration from the file, env vars and flags
+    pub fn load(cli: &Cli) -> Result<Self, AppError> {
+        let mut config = match &cli.config {
+            Some(path) => {
+                let text = fs::read_to_string(path).map_err(|e| {
+                    AppError::Config(format!("cannot read {}: {}", path.display(), e))
+                })?;
+                Self::from_toml(&text)?
+            }
+            None => Self::default(),
+        };
+        cli.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that every value is usable
+    pub fn validate(&self) -> Result<(), AppError> {
+        let invalid = |reason: &str| Err(AppError::Config(reason.to_string()));
+
+        if self.command_buffer == 0 {
+            return invalid("command_buffer must be at least 1");
+        }
+        if self.connection.outbox_capacity == 0 {
+            return invalid("connection.outbox_capacity must be at least 1");
+        }
+        if self.connection.ping_interval.is_zero() || self.connection.pong_timeout.is_zero() {
+            return invalid("connection.ping_interval and pong_timeout must be non-zero");
+        }
+        if self.connection.max_content_chars == 0 || self.connection.max_content_bytes == 0 {
+            return invalid(
+                "connection.max_content_chars and max_content_bytes must be at least 1",
+            );
+        }
+        if self.connection.max_frame_size < self.connection.min_frame_size() {
+            return Err(AppError::Config(format!(
+                "connection.max_frame_size must be at least {} to fit escaped content of \
+                 connection.max_content_bytes",
+                self.connection.min_frame_size()
+            )));
+        }
+        if self.tls.handshake_timeout.is_zero() {
+            return invalid("tls.handshake_timeout must be non-zero");
+        }
+        if !(types::MIN_CODE_LENGTH..=types::MAX_CODE_LENGTH).contains(&self.rooms.code_length) {
+            return Err(AppError::Config(format!(
+                "rooms.code_length must be between {} and {}",
+                types::MIN_CODE_LENGTH,
+                types::MAX_CODE_LENGTH
+            )));
+        }
+        if !(1..=codes::MAX_CODE_WORDS).contains(&self.rooms.code_words) {
+            return Err(AppError::Config(format!(
+                "rooms.code_words must be between 1 and {}",
+                codes::MAX_CODE_WORDS
+            )));
+        }
+        if self.rooms.max_capacity < room::DEFAULT_CAPACITY {
+            return Err(AppError::Config(format!(
+                "rooms.max_capacity must be at least {}",
+                room::DEFAULT_CAPACITY
+            )));
+        }
+        if self.rooms.invite_ttl < Duration::from_secs(1)
+            || self.rooms.invite_ttl > self.rooms.max_invite_ttl
+        {
+            return invalid(
+                "rooms.invite_ttl must be at least 1s and at most rooms.max_invite_ttl",
+            );
+        }
+        if self.rooms.join_attempts.burst == 0 || self.rooms.join_attempts.per_second <= 0.0 {
+            return invalid("rooms.join_attempts needs burst >= 1 and per_second > 0");
+        }
+        if self.rooms.max_password_tasks == 0 {
+            return invalid("rooms.max_password_tasks must be at least 1");
+        }
+        if self.rooms.reap_interval.is_zero() {
+            return invalid("rooms.reap_interval must be non-zero");
+        }
+        if self.usernames.min_length == 0 || self.usernames.max_length < self.usernames.min_length {
+            return invalid("usernames.min_length must be between 1 and usernames.max_length");
+        }
+        if self.usernames.allowed.is_empty() {
+            return invalid("usernames.allowed must name at least one character class");
+        }
+        if self.history.max_fetch_limit == 0 {
+            return invalid("history.max_fetch_limit must be at least 1");
+        }
+        if self.rate_limit.violation_window.is_zero() {
+            return invalid("rate_limit.violation_window must be non-zero");
+        }
+        for (name, limit) in &self.rate_limit.limits {
+            if !ClientMessage::TYPES.contains(&name.as_str()) {
+                return Err(AppError::Config(format!(
+                    "rate_limit.limits.{} is not a client message type",
+                    name
+                )));
+            }
+            if limit.burst == 0 || limit.per_second <= 0.0 {
+                return Err(AppError::Config(format!(
+                    "rate_limit.limits.{} needs burst >= 1 and per_second > 0",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Command-line flags
+///
+/// Each flag can also be set through the `CHAT_*` variable shown in
+/// `--help`; a flag on the command line wins over the variable.
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "WebSocket chat server")]
+pub struct Cli {
+    /// Bind address (shorthand for --bind)
+    #[arg(value_name = "ADDR")]
+    pub addr: Option<String>,
+
+    /// TOML config file
+    #[arg(long, short, env = "CHAT_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Listeners to start (tcp, unix, both)
+    #[arg(long, env = "CHAT_LISTEN", value_parser = parse_enum::<ListenMode>)]
+    pub listen: Option<ListenMode>,
+
+    /// Bind address
+    #[arg(long, env = "CHAT_BIND")]
+    pub bind: Option<String>,
+
+    /// Unix domain socket path
+    #[arg(long, env = "CHAT_UNIX_SOCKET")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Permissions of the Unix socket file, in octal (e.g. 660)
+    #[arg(long, env = "CHAT_UNIX_MODE")]
+    pub unix_mode: Option<FileMode>,
+
+    /// Command channel capacity
+    #[arg(long, env = "CHAT_COMMAND_BUFFER")]
+    pub command_buffer: Option<usize>,
+
+    /// Per-client outbound queue capacity
+    #[arg(long, env = "CHAT_OUTBOX_CAPACITY")]
+    pub outbox_capacity: Option<usize>,
+
+    /// What to do when a client's queue is full
+    /// (drop_oldest, drop_typing_first, disconnect_slow_consumer)
+    #[arg(long, env = "CHAT_DELIVERY_POLICY", value_parser = parse_enum::<DeliveryPolicy>)]
+    pub delivery_policy: Option<DeliveryPolicy>,
+
+    /// Time between server pings, e.g. 20s
+    #[arg(long, env = "CHAT_PING_INTERVAL", value_parser = humantime::parse_duration)]
+    pub ping_interval: Option<Duration>,
+
+    /// Time a client has to answer a ping
+    #[arg(long, env = "CHAT_PONG_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub pong_timeout: Option<Duration>,
+
+    /// Disconnect clients silent for this long (0 disables)
+    #[arg(long, env = "CHAT_IDLE_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub idle_timeout: Option<Duration>,
+
+    /// Largest incoming WebSocket frame, in bytes
+    #[arg(long, env = "CHAT_MAX_FRAME_SIZE")]
+    pub max_frame_size: Option<usize>,
+
+    /// Longest chat message, in characters
+    #[arg(long, env = "CHAT_MAX_CONTENT_CHARS")]
+    pub max_content_chars: Option<usize>,
+
+    /// Serve wss:// using the configured certificate (true, false)
+    #[arg(long, env = "CHAT_TLS")]
+    pub tls: Option<bool>,
+
+    /// TLS certificate chain (PEM)
+    #[arg(long, env = "CHAT_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// TLS private key (PEM)
+    #[arg(long, env = "CHAT_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Client certificate authentication (off, optional, required)
+    #[arg(long, env = "CHAT_TLS_CLIENT_AUTH", value_parser = parse_enum::<ClientAuth>)]
+    pub tls_client_auth: Option<ClientAuth>,
+
+    /// CAs trusted to sign client certificates (PEM)
+    #[arg(long, env = "CHAT_TLS_CLIENT_CA")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// Kind of room codes to generate (crockford, digits, words)
+    #[arg(long, env = "CHAT_ROOM_CODE_STYLE", value_parser = parse_enum::<CodeStyle>)]
+    pub room_code_style: Option<CodeStyle>,
+
+    /// Length of generated room codes
+    #[arg(long, env = "CHAT_ROOM_CODE_LENGTH")]
+    pub room_code_length: Option<usize>,
+
+    /// Largest capacity a client may request for a room
+    #[arg(long, env = "CHAT_MAX_ROOM_CAPACITY")]
+    pub max_room_capacity: Option<usize>,
+
+    /// How long a dropped client's seat is held (0 disables resume)
+    #[arg(long, env = "CHAT_RESUME_GRACE", value_parser = humantime::parse_duration)]
+    pub resume_grace: Option<Duration>,
+
+    /// Close rooms this old (0 disables)
+    #[arg(long, env = "CHAT_MAX_ROOM_LIFETIME", value_parser = humantime::parse_duration)]
+    pub max_room_lifetime: Option<Duration>,
+
+    /// Close rooms without messages or joins for this long (0 disables)
+    #[arg(long, env = "CHAT_ROOM_IDLE_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub room_idle_timeout: Option<Duration>,
+
+    /// Close rooms whose host has waited alone this long (0 disables)
+    #[arg(long, env = "CHAT_GUEST_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub guest_timeout: Option<Duration>,
+
+    /// Longest allowed username, in characters
+    #[arg(long, env = "CHAT_MAX_USERNAME_LENGTH")]
+    pub max_username_length: Option<usize>,
+
+    /// Allow only one client at a time per username (true, false)
+    #[arg(long, env = "CHAT_UNIQUE_USERNAMES")]
+    pub unique_usernames: Option<bool>,
+
+    /// History backend (memory, file)
+    #[arg(long, env = "CHAT_HISTORY_BACKEND", value_parser = parse_enum::<HistoryBackend>)]
+    pub history_backend: Option<HistoryBackend>,
+
+    /// Messages kept per room by the memory backend
+    #[arg(long, env = "CHAT_HISTORY_CAPACITY")]
+    pub history_capacity: Option<usize>,
+
+    /// Directory used by the file history backend
+    #[arg(long, env = "CHAT_HISTORY_DIR")]
+    pub history_dir: Option<PathBuf>,
+
+    /// Enforce per-message-type rate limits (true, false)
+    #[arg(long, env = "CHAT_RATE_LIMIT")]
+    pub rate_limit: Option<bool>,
+
+    /// Time connections get to flush after a shutdown notice
+    #[arg(long, env = "CHAT_DRAIN_TIMEOUT", value_parser = humantime::parse_duration)]
+    pub drain_timeout: Option<Duration>,
+
+    /// Reconnect delay suggested to clients on shutdown (0 leaves it out)
+    #[arg(long, env = "CHAT_RECONNECT_AFTER", value_parser = humantime::parse_duration)]
+    pub reconnect_after: Option<Duration>,
+
+    /// Serve Prometheus metrics (true, false)
+    #[arg(long, env = "CHAT_METRICS")]
+    pub metrics: Option<bool>,
+
+    /// Metrics endpoint address
+    #[arg(long, env = "CHAT_METRICS_BIND")]
+    pub metrics_bind: Option<String>,
+
+    /// Log filter, e.g. chat_server_v1=debug (RUST_LOG wins if set)
+    #[arg(long, env = "CHAT_LOG")]
+    pub log: Option<String>,
+}
+
+impl Cli {
+    /// Override config values with the flags (and env vars) that were given
+    pub fn apply(&self, config: &mut ServerConfig) {
+        fn set<T: Clone>(target: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                *target = value.clone();
+            }
+        }
+
+        // The positional address is a command-line value, so it beats CHAT_BIND
+        set(&mut config.listen, &self.listen);
+        set(&mut config.bind, &self.addr.clone().or(self.bind.clone()));
+        set(&mut config.unix.path, &self.unix_socket);
+        set(&mut config.unix.mode, &self.unix_mode);
+        set(&mut config.command_buffer, &self.command_buffer);
+        set(
+            &mut config.connection.outbox_capacity,
+            &self.outbox_capacity,
+        );
+        set(
+            &mut config.connection.delivery_policy,
+            &self.delivery_policy,
+        );
+        set(&mut config.connection.ping_interval, &self.ping_interval);
+        set(&mut config.connection.pong_timeout, &self.pong_timeout);
+        set(&mut config.connection.idle_timeout, &self.idle_timeout);
+        set(&mut config.connection.max_frame_size, &self.max_frame_size);
+        set(
+            &mut config.connection.max_content_chars,
+            &self.max_content_chars,
+        );
+        set(&mut config.tls.enabled, &self.tls);
+        set(&mut config.tls.cert, &self.tls_cert);
+        set(&mut config.tls.key, &self.tls_key);
+        set(&mut config.tls.client_auth, &self.tls_client_auth);
+        set(&mut config.tls.client_ca, &self.tls_client_ca);
+        set(&mut config.rooms.code_style, &self.room_code_style);
+        set(&mut config.rooms.code_length, &self.room_code_length);
+        set(&mut config.rooms.max_capacity, &self.max_room_capacity);
+        set(&mut config.rooms.resume_grace, &self.resume_grace);
+        set(&mut config.rooms.max_lifetime, &self.max_room_lifetime);
+        set(&mut config.rooms.idle_timeout, &self.room_idle_timeout);
+        set(&mut config.rooms.guest_timeout, &self.guest_timeout);
+        set(&mut config.usernames.max_length, &self.max_username_length);
+        set(&mut config.usernames.unique, &self.unique_usernames);
+        set(&mut config.history.backend, &self.history_backend);
+        set(&mut config.history.capacity, &self.history_capacity);
+        set(&mut config.history.dir, &self.history_dir);
+        set(&mut config.rate_limit.enabled, &self.rate_limit);
+        set(&mut config.shutdown.drain_timeout, &self.drain_timeout);
+        set(&mut config.shutdown.reconnect_after, &self.reconnect_after);
+        set(&mut config.metrics.enabled, &self.metrics);
+        set(&mut config.metrics.bind, &self.metrics_bind);
+        set(&mut config.log.filter, &self.log);
+    }
+}
+
+/// Helper: Parse a snake_case enum value the same way the TOML file does
+fn parse_enum<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    T::deserialize(serde::de::value::StrDeserializer::<serde::de::value::Error>::new(value))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config = ServerConfig::from_toml(
+            r#"
+            bind = "0.0.0.0:9000"
+
+            [connection]
+            delivery_policy = "drop_typing_first"
+            idle_timeout = "5m"
+
+            [rooms]
+            code_length = 8
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.bind, "0.0.0.0:9000");
+        assert_eq!(config.command_buffer, DEFAULT_COMMAND_BUFFER);
+        assert_eq!(
+            config.connection.delivery_policy,
+            DeliveryPolicy::DropTypingFirst
+        );
+        assert_eq!(config.connection.idle_timeout, Duration::from_secs(300));
+        assert_eq!(config.rooms.code_length, 8);
+        assert_eq!(config.rooms.max_capacity, room::MAX_CAPACITY);
+        assert_eq!(config.history.backend, HistoryBackend::Memory);
+    }
+
+    #[test]
+    fn test_unknown_key_is_rejected() {
+        let err = ServerConfig::from_toml("[rooms]\ncode_lenght = 8\n").unwrap_err();
+        assert!(err.to_string().contains("code_lenght"));
+    }
+
+    #[test]
+    fn test_flags_override_file() {
+        let path = std::env::temp_dir().join(format!("chat-config-{}.toml", uuid::Uuid::new_v4()));
+        fs::write(
+            &path,
+            "bind = \"0.0.0.0:9000\"\n[history]\nbackend = \"file\"\n",
+        )
+        .unwrap();
+
+        let cli = Cli::try_parse_from([
+            "chat_server_v1",
+            "--config",
+            path.to_str().unwrap(),
+            "--bind",
+            "127.0.0.1:7000",
+            "--history-backend",
+            "memory",
+            "--resume-grace",
+            "1m",
+        ])
+        .unwrap();
+        let config = ServerConfig::load(&cli).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.bind, "127.0.0.1:7000");
+        assert_eq!(config.history.backend, HistoryBackend::Memory);
+        assert_eq!(config.rooms.resume_grace, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_rate_limit_types_are_checked() {
+        let config =
+            ServerConfig::from_toml("[rate_limit.limits.chat]\nburst = 3\nper_second = 1.5\n")
+                .unwrap();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.rate_limit.limits.len(), 1);
+
+        let config =
+            ServerConfig::from_toml("[rate_limit.limits.shout]\nburst = 3\nper_second = 1.5\n")
+                .unwrap();
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut config = ServerConfig::default();
+        config.rooms.code_length = 2;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.rooms.code_words = 0;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.rooms.invite_ttl = Duration::from_secs(2 * 24 * 60 * 60);
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.rooms.invite_ttl = Duration::from_millis(500);
+        config.rooms.max_invite_ttl = Duration::from_millis(500);
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.rooms.reap_interval = Duration::ZERO;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.rooms.max_password_tasks = 0;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let config = ServerConfig {
+            command_buffer: 0,
+            ..ServerConfig::default()
+        };
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.usernames.max_length = 0;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+
+        let mut config = ServerConfig::default();
+        config.connection.max_frame_size = config.connection.min_frame_size() - 1;
+        assert!(matches!(config.validate(), Err(AppError::Config(_))));
+        config.connection.max_frame_size += 1;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_printed_config_round_trips() {
+        let mut config = ServerConfig::default();
+        config.history.backend = HistoryBackend::File;
+        config.connection.idle_timeout = Duration::ZERO;
+        config.listen = ListenMode::Both;
+        config.unix.mode = FileMode(0o600);
+
+        let reparsed = ServerConfig::from_toml(&config.to_toml()).unwrap();
+        assert_eq!(reparsed.to_toml(), config.to_toml());
+        assert_eq!(reparsed.history.backend, HistoryBackend::File);
+        assert_eq!(reparsed.listen, ListenMode::Both);
+        assert_eq!(reparsed.unix.mode, FileMode(0o600));
+    }
+}
@@ -5,6 +5,8 @@
 
 use thiserror::Error;
 
+use crate::types::MessageId;
+
 /// Application-level errors
 ///
 /// Covers both fatal errors (connection termination) and
@@ -12,17 +14,35 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum AppError {
     /// WebSocket protocol error (fatal)
+    ///
+    /// Boxed to keep `AppError` small; tungstenite's error is large.
     #[error("WebSocket error: {0}")]
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 
     /// JSON serialization/deserialization error
     #[error("JSON serialization error: {0}")]
     Json(#[from] serde_json::Error),
 
+    /// Well-formed JSON that is not a valid client message
+    #[error("Invalid message: {reason}")]
+    InvalidMessage {
+        /// Offending field (`type` for an unknown or missing tag)
+        field: Option<String>,
+        reason: String,
+    },
+
     /// IO error (fatal)
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
+    /// Invalid server configuration (fatal - server does not start)
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// Unusable TLS certificate, key or CA file
+    #[error("TLS error: {0}")]
+    Tls(String),
+
     /// Channel send error (fatal - internal channel broken)
     #[error("Channel send error")]
     ChannelSend,
@@ -31,14 +51,50 @@ pub enum AppError {
     #[error("Room not found: {0}")]
     RoomNotFound(String),
 
-    /// Room is full (already has 2 participants)
+    /// Room is full (every seat is taken)
     #[error("Room is full")]
     RoomFull,
 
+    /// Every generated room code was already taken
+    #[error("No free room code after {attempts} attempts")]
+    RoomCodeUnavailable { attempts: usize },
+
+    /// Room password missing or wrong
+    #[error("Wrong password")]
+    WrongPassword,
+
+    /// Invite missing, unknown, expired or already used
+    #[error("Invite invalid")]
+    InviteInvalid,
+
+    /// Only the room's host may do this
+    #[error("Not the room host")]
+    NotRoomHost,
+
+    /// Room password could not be hashed
+    #[error("Password hashing error: {0}")]
+    PasswordHash(String),
+
+    /// Requested room capacity is out of range
+    #[error("Invalid room capacity: {capacity}")]
+    InvalidCapacity {
+        capacity: usize,
+        /// Largest capacity the server allows
+        max: usize,
+    },
+
     /// Username is required but not set
     #[error("Username required")]
     UsernameRequired,
 
+    /// Requested username breaks the username policy
+    #[error("Invalid username: {0}")]
+    InvalidUsername(String),
+
+    /// Another client already uses this (or a look-alike) username
+    #[error("Username taken: {0}")]
+    UsernameTaken(String),
+
     /// Client is not in any room
     #[error("Not in room")]
     NotInRoom,
@@ -46,6 +102,52 @@ pub enum AppError {
     /// Client is already in a room
     #[error("Already in room")]
     AlreadyInRoom,
+
+    /// Client is already waiting for a random partner
+    #[error("Already searching")]
+    AlreadySearching,
+
+    /// Client is not waiting for a random partner
+    #[error("Not searching")]
+    NotSearching,
+
+    /// Resume token is unknown, expired, or still in use
+    #[error("Resume failed")]
+    ResumeFailed,
+
+    /// No such message in the client's room (or it was deleted)
+    #[error("Message not found: {0}")]
+    MessageNotFound(MessageId),
+
+    /// A reply refers to a message not in the room's history
+    #[error("Reply target not found: {0}")]
+    ReplyNotFound(MessageId),
+
+    /// Client tried to edit or delete someone else's message
+    #[error("Not the message author")]
+    NotMessageAuthor,
+
+    /// Reaction is not a single emoji
+    #[error("Invalid emoji: {0}")]
+    InvalidEmoji(String),
+
+    /// Message already has the maximum number of distinct reactions
+    #[error("Too many reactions (max {max})")]
+    TooManyReactions { max: usize },
+
+    /// Chat content exceeds the configured size limits
+    #[error("Message too large (max {max_chars} chars, {max_bytes} bytes)")]
+    MessageTooLarge { max_chars: usize, max_bytes: usize },
+
+    /// Client exceeded the rate limit for a message type
+    #[error("Rate limited, retry in {retry_after_ms} ms")]
+    RateLimited { retry_after_ms: u64 },
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for AppError {
+    fn from(err: tokio_tungstenite::tungstenite::Error) -> Self {
+        AppError::WebSocket(Box::new(err))
+    }
 }
 
 /// Message send errors
@@ -56,4 +158,8 @@ pub enum SendError {
     /// The receiving end of the channel has been closed
     #[error("Channel closed")]
     ChannelClosed,
+
+    /// The client fell too far behind and is being disconnected
+    #[error("Slow consumer")]
+    SlowConsumer,
 }
@@ -3,48 +3,242 @@
 //! Handles individual client connections: WebSocket handshake,
 //! message parsing, and bidirectional communication with the ChatServer.
 
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
 use futures_util::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
-use tokio_tungstenite::tungstenite::Message;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::{self, Instant, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
 use tracing::{debug, error, info, warn};
 
+use crate::config::ServerConfig;
 use crate::error::AppError;
-use crate::message::{ClientMessage, ServerMessage};
-use crate::server::ServerCommand;
-use crate::types::ClientId;
+use crate::message::{ClientMessage, ServerMessage, MAX_CLIENT_MSG_ID_LEN};
+use crate::metrics::metrics;
+use crate::outbox::{self, DeliveryPolicy, OutboxSender};
+use crate::ratelimit::{ConnectionLimiter, RateLimiter, Verdict};
+use crate::server::{self, ServerCommand};
+use crate::types::{ClientId, ResumeToken};
+
+/// Default size of each client's outbound message queue
+pub const DEFAULT_OUTBOX_CAPACITY: usize = 32;
+
+/// Default time between server-initiated pings
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Default time a client has to answer a ping
+pub const DEFAULT_PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time a client may go without sending any message
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Default largest WebSocket frame (and message) accepted, in bytes
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 64 * 1024;
+
+/// Default longest chat content, in characters
+pub const DEFAULT_MAX_CONTENT_CHARS: usize = 2000;
+
+/// Default longest chat content, in UTF-8 bytes
+pub const DEFAULT_MAX_CONTENT_BYTES: usize = 8 * 1024;
 
-/// Handle a new TCP connection
+/// Longest JSON escape of one content byte (`\u0001`)
+const MAX_ESCAPE_LEN: usize = 6;
+
+/// Bytes of a chat frame besides its content: the other fields, with
+/// room for a fully escaped `client_msg_id`
+const CHAT_ENVELOPE_LEN: usize = 128 + MAX_ESCAPE_LEN * MAX_CLIENT_MSG_ID_LEN;
+
+/// Close code sent when a client stops answering pings
+pub const CLOSE_HEARTBEAT_TIMEOUT: u16 = 4000;
+
+/// Close code sent when a client has been idle too long
+pub const CLOSE_IDLE_TIMEOUT: u16 = 4001;
+
+/// Close code sent when a client keeps exceeding its rate limits
+pub const CLOSE_RATE_LIMITED: u16 = 4002;
+
+/// Per-connection settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionConfig {
+    /// Maximum number of queued Server → Client messages
+    pub outbox_capacity: usize,
+    /// What to do when the queue is full
+    pub delivery_policy: DeliveryPolicy,
+    /// Time between server-initiated WebSocket pings
+    #[serde(with = "humantime_serde")]
+    pub ping_interval: Duration,
+    /// Time a client has to answer a ping before it is disconnected
+    #[serde(with = "humantime_serde")]
+    pub pong_timeout: Duration,
+    /// Time without any client message before disconnecting (zero = never)
+    #[serde(with = "humantime_serde")]
+    pub idle_timeout: Duration,
+    /// Largest incoming frame or message, in bytes; bigger ones close the connection
+    pub max_frame_size: usize,
+    /// Longest chat content, in characters
+    pub max_content_chars: usize,
+    /// Longest chat content, in UTF-8 bytes
+    pub max_content_bytes: usize,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            outbox_capacity: DEFAULT_OUTBOX_CAPACITY,
+            delivery_policy: DeliveryPolicy::default(),
+            ping_interval: DEFAULT_PING_INTERVAL,
+            pong_timeout: DEFAULT_PONG_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_content_chars: DEFAULT_MAX_CONTENT_CHARS,
+            max_content_bytes: DEFAULT_MAX_CONTENT_BYTES,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// WebSocket settings for the handshake
+    fn websocket_config(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_frame_size: Some(self.max_frame_size),
+            max_message_size: Some(self.max_frame_size),
+            ..WebSocketConfig::default()
+        }
+    }
+
+    /// Smallest frame size that fits any content within the limits
+    ///
+    /// JSON escapes can make content up to six times longer on the wire,
+    /// and such messages must get `message_too_large` rather than a 1009
+    /// close.
+    pub fn min_frame_size(&self) -> usize {
+        MAX_ESCAPE_LEN * self.max_content_bytes + CHAT_ENVELOPE_LEN
+    }
+
+    /// Check chat content against the size limits
+    ///
+    /// Applies to new and edited messages, and also bounds the
+    /// `client_msg_id`. Messages without chat content always pass.
+    pub fn check_content(&self, msg: &ClientMessage) -> Result<(), AppError> {
+        let (content, client_msg_id) = match msg {
+            ClientMessage::Chat {
+                content,
+                client_msg_id,
+                ..
+            } => (content, client_msg_id.as_ref()),
+            ClientMessage::EditMessage { content, .. } => (content, None),
+            _ => return Ok(()),
+        };
+        if client_msg_id.is_some_and(|id| id.is_empty() || id.len() > MAX_CLIENT_MSG_ID_LEN)
+        {
+            return Err(AppError::InvalidMessage {
+                field: Some("client_msg_id".to_string()),
+                reason: format!("client_msg_id must be 1 to {} bytes", MAX_CLIENT_MSG_ID_LEN),
+            });
+        }
+        if content.trim().is_empty() {
+            return Err(AppError::InvalidMessage {
+                field: Some("content".to_string()),
+                reason: "content must not be empty".to_string(),
+            });
+        }
+        if content.len() > self.max_content_bytes
+            || content.chars().count() > self.max_content_chars
+        {
+            return Err(AppError::MessageTooLarge {
+                max_chars: self.max_content_chars,
+                max_bytes: self.max_content_bytes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Where a connection comes from (used for logging)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Peer {
+    /// TCP peer, with or without TLS
+    Tcp(SocketAddr),
+    /// Unix domain socket client, by the path of the listening socket
+    Unix(PathBuf),
+    /// Anything else, e.g. an in-process stream
+    Other(String),
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Peer::Tcp(addr) => write!(f, "{}", addr),
+            Peer::Unix(path) => write!(f, "unix:{}", path.display()),
+            Peer::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Handle a new client connection
 ///
 /// Performs WebSocket handshake, sets up bidirectional communication,
-/// and manages the connection lifecycle.
-pub async fn handle_connection(
-    stream: TcpStream,
+/// and manages the connection lifecycle. Client messages are checked
+/// against `limiter` before they reach the ChatServer.
+///
+/// Works over any byte stream: plain TCP, TLS, a Unix socket, or an in-memory
+/// `tokio::io::duplex` pipe in tests.
+pub async fn handle_connection<S>(
+    stream: S,
+    peer: Peer,
     cmd_tx: mpsc::Sender<ServerCommand>,
-) -> Result<(), AppError> {
-    let peer_addr = stream
-        .peer_addr()
-        .map(|a| a.to_string())
-        .unwrap_or_else(|_| "unknown".to_string());
-
-    debug!("New TCP connection from {}", peer_addr);
+    config: Arc<ServerConfig>,
+    limiter: Arc<RateLimiter>,
+) -> Result<(), AppError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    debug!("New connection from {}", peer);
 
     // WebSocket handshake
-    let ws_stream = tokio_tungstenite::accept_async(stream).await?;
+    let ws_config = config.connection.websocket_config();
+    let ws_stream = tokio_tungstenite::accept_async_with_config(stream, Some(ws_config)).await?;
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
-    // Generate client ID
+    // Generate client ID and resume token
     let client_id = ClientId::new();
-    info!("Client {} connected from {}", client_id, peer_addr);
+    let resume_token = ResumeToken::generate();
+    info!("Client {} connected from {}", client_id, peer);
+
+    // Current client ID (changes if the connection resumes a session)
+    let (id_tx, id_rx) = watch::channel(client_id);
 
-    // Create channel for server -> client messages
-    let (msg_tx, mut msg_rx) = mpsc::channel::<ServerMessage>(32);
+    // Create queue for server -> client messages
+    let (msg_tx, mut msg_rx) = outbox::channel(
+        config.connection.outbox_capacity,
+        config.connection.delivery_policy,
+    );
+
+    // Keep a handle for reporting malformed frames straight back to the client
+    let error_tx = msg_tx.clone();
+
+    // Liveness signals from the read task to the write task's timers
+    let (pong_tx, mut pong_rx) = watch::channel(());
+    let (activity_tx, activity_rx) = watch::channel(Instant::now());
+
+    // Close frame for the write task when the read task cuts the client off
+    let (kick_tx, mut kick_rx) = oneshot::channel::<CloseFrame<'static>>();
 
     // Register with ChatServer
     if cmd_tx
         .send(ServerCommand::Connect {
             client_id,
             sender: msg_tx,
+            resume_token: resume_token.clone(),
         })
         .await
         .is_err()
@@ -56,33 +250,95 @@ pub async fn handle_connection(
     // Send connection success message
     let connected_msg = ServerMessage::Connected {
         client_id: client_id.to_string(),
+        resume_token: resume_token.to_string(),
     };
     let json = serde_json::to_string(&connected_msg)?;
-    ws_sender.send(Message::Text(json.into())).await?;
+    ws_sender.send(Message::Text(json)).await?;
 
-    // Clone cmd_tx for read task
+    // Clone cmd_tx and content limits for read task
     let cmd_tx_read = cmd_tx.clone();
+    let limits = config.connection.clone();
 
     // Spawn read task (WebSocket -> ServerCommand)
     let read_task = tokio::spawn(async move {
+        let mut client_id = client_id;
+        let mut limiter = limiter.connection();
         while let Some(msg_result) = ws_receiver.next().await {
+            if let Ok(Message::Text(_) | Message::Binary(_)) = &msg_result {
+                activity_tx.send_replace(Instant::now());
+            }
             match msg_result {
-                Ok(Message::Text(text)) => {
-                    match serde_json::from_str::<ClientMessage>(&text) {
-                        Ok(client_msg) => {
+                Ok(Message::Text(text)) => match ClientMessage::parse(&text) {
+                    Ok(client_msg) => {
+                        // Enforce rate limits before anything reaches the server
+                        match limiter.check(client_msg.name(), client_id) {
+                            Verdict::Allow => {}
+                            Verdict::Limited(retry_after) => {
+                                debug!("Rate limited {} for {:?}", client_id, retry_after);
+                                let err = AppError::RateLimited {
+                                    retry_after_ms: retry_after.as_millis().max(1) as u64,
+                                };
+                                let _ = error_tx.push(err.into());
+                                continue;
+                            }
+                            Verdict::Disconnect => {
+                                warn!("Client {} keeps exceeding rate limits, dropping connection",
+                                    client_id);
+                                let frame = close_frame(CLOSE_RATE_LIMITED, "rate limit exceeded");
+                                let _ = kick_tx.send(frame);
+                                break;
+                            }
+                        }
+                        if let Err(e) = limits.check_content(&client_msg) {
+                            debug!("Rejected message from {}: {}", client_id, e);
+                            if let Some(frame) = reject(client_id, &error_tx, &mut limiter, e) {
+                                let _ = kick_tx.send(frame);
+                                break;
+                            }
+                            continue;
+                        }
+
+                        if let ClientMessage::Resume { token } = client_msg {
+                            let (reply_tx, reply_rx) = oneshot::channel();
+                            let cmd = ServerCommand::Resume {
+                                client_id,
+                                token,
+                                reply: reply_tx,
+                            };
+                            if cmd_tx_read.send(cmd).await.is_err() {
+                                debug!("Server closed, ending read task for {}", client_id);
+                                break;
+                            }
+                            if let Ok(Some(resumed_id)) = reply_rx.await {
+                                client_id = resumed_id;
+                                let _ = id_tx.send(resumed_id);
+                            }
+                        } else {
                             let cmd = client_message_to_command(client_id, client_msg);
                             if cmd_tx_read.send(cmd).await.is_err() {
                                 debug!("Server closed, ending read task for {}", client_id);
                                 break;
                             }
                         }
-                        Err(e) => {
-                            warn!("Invalid JSON from {}: {}", client_id, e);
-                            // Note: We can't easily send an error back here
-                            // as we don't have access to msg_tx in this task.
-                            // The server should handle invalid messages gracefully.
+                    }
+                    Err(e) => {
+                        warn!("Invalid message from {}: {}", client_id, e);
+                        if let Some(frame) = reject(client_id, &error_tx, &mut limiter, e) {
+                            let _ = kick_tx.send(frame);
+                            break;
                         }
                     }
+                },
+                Ok(Message::Binary(_)) => {
+                    warn!("Binary frame from {}", client_id);
+                    let err = AppError::InvalidMessage {
+                        field: None,
+                        reason: "binary frames are not supported, send JSON text".to_string(),
+                    };
+                    if let Some(frame) = reject(client_id, &error_tx, &mut limiter, err) {
+                        let _ = kick_tx.send(frame);
+                        break;
+                    }
                 }
                 Ok(Message::Close(_)) => {
                     debug!("Client {} sent close frame", client_id);
@@ -95,9 +351,15 @@ pub async fn handle_connection(
                 }
                 Ok(Message::Pong(_)) => {
                     debug!("Pong from {}", client_id);
+                    let _ = pong_tx.send(());
                 }
                 Ok(_) => {
-                    // Binary or other message types - ignore
+                    // Raw frames are never yielded when reading - ignore
+                }
+                Err(WsError::Capacity(e)) => {
+                    warn!("Oversized frame from {}: {}", client_id, e);
+                    let _ = kick_tx.send(close_frame(CloseCode::Size.into(), "message too large"));
+                    break;
                 }
                 Err(e) => {
                     error!("WebSocket error for {}: {}", client_id, e);
@@ -110,24 +372,91 @@ pub async fn handle_connection(
 
     // Spawn write task (ServerMessage -> WebSocket)
     let write_task = tokio::spawn(async move {
-        while let Some(msg) = msg_rx.recv().await {
-            match serde_json::to_string(&msg) {
-                Ok(json) => {
-                    if ws_sender.send(Message::Text(json.into())).await.is_err() {
-                        debug!("WebSocket send failed, ending write task");
-                        break;
+        let config = &config.connection;
+        let mut ping_timer =
+            time::interval_at(Instant::now() + config.ping_interval, config.ping_interval);
+        ping_timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        // When the oldest unanswered ping was sent
+        let mut ping_sent: Option<Instant> = None;
+
+        // Set once the server announced it is shutting down
+        let mut going_away = false;
+
+        let close_frame = loop {
+            let pong_deadline = ping_sent.map(|sent| sent + config.pong_timeout);
+            let idle_deadline = (!config.idle_timeout.is_zero())
+                .then(|| *activity_rx.borrow() + config.idle_timeout);
+
+            tokio::select! {
+                msg = msg_rx.recv() => {
+                    let Some(msg) = msg else {
+                        break going_away
+                            .then(|| close_frame(CloseCode::Away.into(), "server shutting down"));
+                    };
+                    going_away |= matches!(msg, ServerMessage::ServerShutdown { .. });
+                    if let ServerMessage::Error { code, .. } = &msg {
+                        metrics().errors.with_label_values(&[code.as_str()]).inc();
+                    }
+                    match serde_json::to_string(&msg) {
+                        Ok(json) => {
+                            // A peer that stopped reading can block this write forever,
+                            // so give up as soon as the outbox is aborted
+                            tokio::select! {
+                                result = ws_sender.send(Message::Text(json)) => {
+                                    if result.is_err() {
+                                        debug!("WebSocket send failed, ending write task");
+                                        break None;
+                                    }
+                                }
+                                _ = msg_rx.aborted() => break None,
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to serialize message: {}", e);
+                            // Continue - don't break on serialization errors
+                        }
                     }
                 }
-                Err(e) => {
-                    error!("Failed to serialize message: {}", e);
-                    // Continue - don't break on serialization errors
+                _ = ping_timer.tick() => {
+                    if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                        debug!("WebSocket ping failed, ending write task");
+                        break None;
+                    }
+                    ping_sent.get_or_insert_with(Instant::now);
+                }
+                Ok(()) = pong_rx.changed() => ping_sent = None,
+                _ = server::sleep_until_some(pong_deadline) => {
+                    warn!("Client {} stopped answering pings, dropping connection", client_id);
+                    break Some(close_frame(CLOSE_HEARTBEAT_TIMEOUT, "heartbeat timeout"));
+                }
+                Ok(frame) = &mut kick_rx => break Some(frame),
+                _ = server::sleep_until_some(idle_deadline) => {
+                    // The client may have sent something since the deadline was taken
+                    let idle_for = activity_rx.borrow().elapsed();
+                    if idle_for >= config.idle_timeout {
+                        info!("Client {} idle for {:?}, dropping connection", client_id, idle_for);
+                        break Some(close_frame(CLOSE_IDLE_TIMEOUT, "idle timeout"));
+                    }
                 }
             }
+        };
+
+        if msg_rx.is_aborted() {
+            warn!("Client {} is too slow, dropping connection", client_id);
+            return;
         }
         debug!("Write task ended for client");
 
-        // Send close frame when done
-        let _ = ws_sender.close().await;
+        // Send close frame when done. The peer may be gone without a trace,
+        // so don't let the close hold up the disconnect.
+        let close = async {
+            match close_frame {
+                Some(frame) => ws_sender.send(Message::Close(Some(frame))).await,
+                None => ws_sender.close().await,
+            }
+        };
+        let _ = time::timeout(config.pong_timeout, close).await;
     });
 
     // Wait for either task to complete
@@ -140,7 +469,8 @@ pub async fn handle_connection(
         }
     }
 
-    // Send disconnect command
+    // Send disconnect command (for the resumed session, if any)
+    let client_id = *id_rx.borrow();
     let _ = cmd_tx
         .send(ServerCommand::Disconnect { client_id })
         .await;
@@ -150,15 +480,208 @@ pub async fn handle_connection(
     Ok(())
 }
 
+/// Helper: Report a rejected client message and count it as a violation
+///
+/// Returns the close frame to send if the client has used up its
+/// violation allowance.
+fn reject(
+    client_id: ClientId,
+    error_tx: &OutboxSender,
+    limiter: &mut ConnectionLimiter,
+    err: AppError,
+) -> Option<CloseFrame<'static>> {
+    let _ = error_tx.push(err.into());
+    if limiter.charge_violation() {
+        return None;
+    }
+    warn!("Client {} keeps sending invalid messages, dropping connection", client_id);
+    Some(close_frame(CLOSE_RATE_LIMITED, "rate limit exceeded"))
+}
+
+/// Helper: Build a close frame
+fn close_frame(code: u16, reason: &'static str) -> CloseFrame<'static> {
+    CloseFrame {
+        code: code.into(),
+        reason: reason.into(),
+    }
+}
+
 /// Convert a ClientMessage to a ServerCommand
 fn client_message_to_command(client_id: ClientId, msg: ClientMessage) -> ServerCommand {
     match msg {
         ClientMessage::SetUsername { username } => ServerCommand::SetUsername { client_id, username },
-        ClientMessage::CreateRoom => ServerCommand::CreateRoom { client_id },
-        ClientMessage::JoinRoom { room_code } => ServerCommand::JoinRoom { client_id, room_code },
-        ClientMessage::Chat { content } => ServerCommand::Chat { client_id, content },
+        ClientMessage::CreateRoom {
+            capacity,
+            password,
+            invite_only,
+        } => ServerCommand::CreateRoom {
+            client_id,
+            capacity,
+            password,
+            invite_only,
+        },
+        ClientMessage::JoinRoom {
+            room_code,
+            password,
+            invite,
+        } => ServerCommand::JoinRoom {
+            client_id,
+            room_code,
+            password,
+            invite,
+        },
+        ClientMessage::CreateInvite {
+            single_use,
+            ttl_secs,
+        } => ServerCommand::CreateInvite {
+            client_id,
+            single_use,
+            ttl_secs,
+        },
+        ClientMessage::Chat {
+            content,
+            client_msg_id,
+            reply_to,
+        } => ServerCommand::Chat {
+            client_id,
+            content,
+            client_msg_id,
+            reply_to,
+        },
+        ClientMessage::FetchHistory { before, limit } => ServerCommand::FetchHistory {
+            client_id,
+            before,
+            limit,
+        },
+        ClientMessage::MarkRead { up_to_message_id } => ServerCommand::MarkRead {
+            client_id,
+            up_to_message_id,
+        },
+        ClientMessage::EditMessage {
+            message_id,
+            content,
+        } => ServerCommand::EditMessage {
+            client_id,
+            message_id,
+            content,
+        },
+        ClientMessage::DeleteMessage { message_id } => ServerCommand::DeleteMessage {
+            client_id,
+            message_id,
+        },
+        ClientMessage::React { message_id, emoji } => ServerCommand::React {
+            client_id,
+            message_id,
+            emoji,
+        },
+        ClientMessage::Unreact { message_id, emoji } => ServerCommand::Unreact {
+            client_id,
+            message_id,
+            emoji,
+        },
         ClientMessage::Typing => ServerCommand::Typing { client_id },
         ClientMessage::StopTyping => ServerCommand::StopTyping { client_id },
         ClientMessage::LeaveRoom => ServerCommand::LeaveRoom { client_id },
+        ClientMessage::FindPartner { tags } => ServerCommand::FindPartner { client_id, tags },
+        ClientMessage::CancelFind => ServerCommand::CancelFind { client_id },
+        ClientMessage::Resume { .. } => unreachable!("Resume is handled by the read task"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tokio::io::DuplexStream;
+    use tokio_tungstenite::WebSocketStream;
+
+    const WAIT: Duration = Duration::from_secs(2);
+
+    /// Run a handler over an in-memory pipe
+    ///
+    /// Returns the client end and the commands the handler produces.
+    async fn connect() -> (WebSocketStream<DuplexStream>, mpsc::Receiver<ServerCommand>) {
+        let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+        let (cmd_tx, cmd_rx) = mpsc::channel(16);
+        let config = Arc::new(ServerConfig::default());
+        let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+        let peer = Peer::Other("duplex".to_string());
+        tokio::spawn(handle_connection(server_io, peer, cmd_tx, config, limiter));
+
+        let (ws, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+            .await
+            .unwrap();
+        (ws, cmd_rx)
+    }
+
+    async fn next_command(cmd_rx: &mut mpsc::Receiver<ServerCommand>) -> ServerCommand {
+        time::timeout(WAIT, cmd_rx.recv()).await.unwrap().unwrap()
+    }
+
+    async fn recv(ws: &mut WebSocketStream<DuplexStream>) -> Value {
+        match time::timeout(WAIT, ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+            other => panic!("Expected text frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_lifecycle() {
+        let (mut ws, mut cmd_rx) = connect().await;
+
+        let ServerCommand::Connect { client_id, .. } = next_command(&mut cmd_rx).await else {
+            panic!("Expected Connect");
+        };
+        let connected = recv(&mut ws).await;
+        assert_eq!(connected["type"], "connected");
+        assert_eq!(connected["client_id"], client_id.to_string());
+
+        let chat = r#"{"type":"chat","content":"Hello"}"#;
+        ws.send(Message::Text(chat.to_string())).await.unwrap();
+        match next_command(&mut cmd_rx).await {
+            ServerCommand::Chat {
+                client_id: id,
+                content,
+                client_msg_id: None,
+                reply_to: None,
+            } => {
+                assert_eq!(id, client_id);
+                assert_eq!(content, "Hello");
+            }
+            other => panic!("Expected Chat, got {:?}", other),
+        }
+
+        ws.close(None).await.unwrap();
+        match next_command(&mut cmd_rx).await {
+            ServerCommand::Disconnect { client_id: id } => assert_eq!(id, client_id),
+            other => panic!("Expected Disconnect, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outbox_messages_are_delivered() {
+        let (mut ws, mut cmd_rx) = connect().await;
+        let ServerCommand::Connect { sender, .. } = next_command(&mut cmd_rx).await else {
+            panic!("Expected Connect");
+        };
+        recv(&mut ws).await;
+
+        sender.push(ServerMessage::PartnerTyping).unwrap();
+        assert_eq!(recv(&mut ws).await["type"], "partner_typing");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_message_never_reaches_server() {
+        let (mut ws, mut cmd_rx) = connect().await;
+        next_command(&mut cmd_rx).await;
+        recv(&mut ws).await;
+
+        ws.send(Message::Text(r#"{"type":"chat"}"#.to_string()))
+            .await
+            .unwrap();
+        let error = recv(&mut ws).await;
+        assert_eq!(error["code"], "invalid_message");
+        assert_eq!(error["field"], "content");
+        assert!(cmd_rx.try_recv().is_err());
     }
 }
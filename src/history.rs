@@ -0,0 +1,627 @@
+//! Per-room message history
+//!
+//! Chat messages are recorded through the `MessageStore` trait so rooms can
+//! replay recent history to late joiners and clients can page backwards.
+//! Two implementations are provided:
+//! - `MemoryStore`: bounded ring buffer per room (default)
+//! - `FileStore`: the same ring buffer, mirrored to one JSON-lines file per
+//!   room in a directory
+//!
+//! Edited messages keep their ID and gain an `edited_ts`; deleted ones
+//! stay behind as tombstones with empty content.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::error::AppError;
+use crate::types::{MessageId, RoomCode};
+
+/// Default number of messages kept per room by `MemoryStore`
+pub const DEFAULT_ROOM_HISTORY: usize = 200;
+
+/// Number of messages replayed to a client joining a room
+pub const REPLAY_LIMIT: usize = 50;
+
+/// Maximum page size for `FetchHistory`
+pub const MAX_FETCH_LIMIT: usize = 100;
+
+/// Characters of a replied-to message quoted in a reply
+pub const REPLY_SNIPPET_CHARS: usize = 100;
+
+/// A chat message as recorded in history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredMessage {
+    /// Server-assigned message ID
+    pub message_id: MessageId,
+    /// Sender's display name
+    pub from: String,
+    /// Message content
+    pub content: String,
+    /// Server timestamp (milliseconds since the Unix epoch)
+    pub server_ts: u64,
+    /// When the content was last edited (milliseconds since the Unix epoch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_ts: Option<u64>,
+    /// Deleted by its author (`content` is empty)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// The message this one replies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<ReplyTo>,
+}
+
+/// A quote of the message a reply refers to
+///
+/// Taken when the reply is sent, so it stays readable even after the
+/// quoted message has left the history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplyTo {
+    /// ID of the quoted message
+    pub message_id: MessageId,
+    /// Its sender's display name
+    pub from: String,
+    /// Its content, cut to `REPLY_SNIPPET_CHARS` characters ("…" marks a cut)
+    pub snippet: String,
+}
+
+impl ReplyTo {
+    /// Quote a stored message
+    pub fn quote(message: &StoredMessage) -> Self {
+        let mut snippet: String = message.content.chars().take(REPLY_SNIPPET_CHARS).collect();
+        if snippet.len() < message.content.len() {
+            snippet.push('…');
+        }
+        Self {
+            message_id: message.message_id,
+            from: message.from.clone(),
+            snippet,
+        }
+    }
+}
+
+/// Current time in milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Storage backend for room message history
+///
+/// Messages are returned oldest first. Implementations are owned by the
+/// ChatServer actor, so no internal locking is required.
+pub trait MessageStore: Send {
+    /// Record a message in a room's history
+    fn append(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError>;
+
+    /// Get up to `limit` messages older than `before` (or the newest if None)
+    fn before(
+        &self,
+        room: &RoomCode,
+        before: Option<MessageId>,
+        limit: usize,
+    ) -> Result<Vec<StoredMessage>, AppError>;
+
+    /// Replace a recorded message with its edited or deleted version
+    ///
+    /// Messages that are no longer kept are left alone.
+    fn update(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError>;
+
+    /// Get a single message, if it is still kept
+    fn get(&self, room: &RoomCode, id: MessageId) -> Result<Option<StoredMessage>, AppError> {
+        let page = self.before(room, Some(id.next()), 1)?;
+        Ok(page.into_iter().find(|m| m.message_id == id))
+    }
+
+    /// Forget a room's history
+    fn remove_room(&mut self, room: &RoomCode) -> Result<(), AppError>;
+
+    /// Highest message ID already stored (used to seed new IDs)
+    fn last_message_id(&self) -> MessageId {
+        MessageId(0)
+    }
+
+    /// Get up to `limit` of the newest messages
+    fn recent(&self, room: &RoomCode, limit: usize) -> Result<Vec<StoredMessage>, AppError> {
+        self.before(room, None, limit)
+    }
+}
+
+/// Helper: Take the last `limit` messages older than `before`
+fn page<'a>(
+    messages: impl DoubleEndedIterator<Item = &'a StoredMessage>,
+    before: Option<MessageId>,
+    limit: usize,
+) -> Vec<StoredMessage> {
+    let mut page: Vec<StoredMessage> = messages
+        .rev()
+        .filter(|m| before.is_none_or(|b| m.message_id < b))
+        .take(limit)
+        .cloned()
+        .collect();
+    page.reverse();
+    page
+}
+
+/// In-memory history: a bounded ring buffer per room
+#[derive(Debug)]
+pub struct MemoryStore {
+    /// Messages kept per room; older ones are evicted
+    capacity: usize,
+    rooms: HashMap<RoomCode, VecDeque<StoredMessage>>,
+}
+
+impl MemoryStore {
+    /// Create a store keeping at most `capacity` messages per room
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            rooms: HashMap::new(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_ROOM_HISTORY)
+    }
+}
+
+impl MessageStore for MemoryStore {
+    fn append(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let messages = self.rooms.entry(room.clone()).or_default();
+        if messages.len() >= self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(message);
+        Ok(())
+    }
+
+    fn before(
+        &self,
+        room: &RoomCode,
+        before: Option<MessageId>,
+        limit: usize,
+    ) -> Result<Vec<StoredMessage>, AppError> {
+        Ok(self
+            .rooms
+            .get(room)
+            .map(|messages| page(messages.iter(), before, limit))
+            .unwrap_or_default())
+    }
+
+    fn update(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError> {
+        let Some(messages) = self.rooms.get_mut(room) else {
+            return Ok(());
+        };
+        if let Ok(index) = messages.binary_search_by_key(&message.message_id, |m| m.message_id) {
+            messages[index] = message;
+        }
+        Ok(())
+    }
+
+    fn remove_room(&mut self, room: &RoomCode) -> Result<(), AppError> {
+        self.rooms.remove(room);
+        Ok(())
+    }
+}
+
+/// File-backed history: one JSON-lines file per room
+///
+/// Reads are served from an in-memory copy holding the newest `capacity`
+/// messages of each room, so commands never wait on the disk. Writes go
+/// to the files in order on a background thread; edits and deletes are
+/// appended as new versions of a message (the last one wins), and a file
+/// is rewritten from the in-memory copy once it holds twice `capacity`
+/// lines. Write errors are logged, not returned.
+///
+/// Files left by an earlier run are loaded on open. Rooms don't outlive
+/// the process, though, and a new room discards whatever history its code
+/// had, so the files mostly serve as a transcript of open rooms.
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    /// The newest `capacity` messages of every room
+    cache: MemoryStore,
+    /// Lines in each room's file, to know when to rewrite it
+    lines: HashMap<RoomCode, usize>,
+    last_id: MessageId,
+    writer: Option<Writer>,
+}
+
+/// File operation for the writer thread
+#[derive(Debug)]
+enum WriteOp {
+    /// Append JSON lines to a file
+    Append { path: PathBuf, lines: String },
+    /// Replace a file's contents
+    Rewrite { path: PathBuf, lines: String },
+    /// Delete a file
+    Remove { path: PathBuf },
+}
+
+impl WriteOp {
+    fn path(&self) -> &Path {
+        match self {
+            WriteOp::Append { path, .. }
+            | WriteOp::Rewrite { path, .. }
+            | WriteOp::Remove { path } => path,
+        }
+    }
+
+    fn apply(&self) -> std::io::Result<()> {
+        match self {
+            WriteOp::Append { path, lines } => OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?
+                .write_all(lines.as_bytes()),
+            WriteOp::Rewrite { path, lines } => {
+                // Write aside and rename, so a crash leaves the old file intact
+                let tmp = path.with_extension("jsonl.tmp");
+                fs::write(&tmp, lines)?;
+                fs::rename(&tmp, path)
+            }
+            WriteOp::Remove { path } => match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+/// Background thread running a FileStore's file operations in order
+#[derive(Debug)]
+struct Writer {
+    ops: mpsc::Sender<WriteOp>,
+    thread: JoinHandle<()>,
+}
+
+impl Writer {
+    fn spawn() -> Result<Self, AppError> {
+        let (ops, rx) = mpsc::channel::<WriteOp>();
+        let thread = thread::Builder::new()
+            .name("history-writer".to_string())
+            .spawn(move || {
+                for op in rx {
+                    if let Err(e) = op.apply() {
+                        error!("History write to {} failed: {}", op.path().display(), e);
+                    }
+                }
+            })?;
+        Ok(Self { ops, thread })
+    }
+}
+
+impl FileStore {
+    /// Open (creating if needed) a history directory, keeping `capacity`
+    /// messages per room
+    pub fn open(dir: impl Into<PathBuf>, capacity: usize) -> Result<Self, AppError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut store = Self {
+            dir,
+            cache: MemoryStore::new(capacity),
+            lines: HashMap::new(),
+            last_id: MessageId(0),
+            writer: Some(Writer::spawn()?),
+        };
+
+        // Load what an earlier run left, continuing numbering after it
+        for entry in fs::read_dir(&store.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "jsonl") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let room = RoomCode(stem.to_string());
+            let (messages, lines) = read_messages(&path)?;
+            if let Some(last) = messages.last() {
+                store.last_id = store.last_id.max(last.message_id);
+            }
+            for message in messages {
+                store.cache.append(&room, message)?;
+            }
+            store.lines.insert(room.clone(), lines);
+            store.compact_if_needed(&room)?;
+        }
+
+        Ok(store)
+    }
+
+    /// Path of a room's history file
+    fn room_path(&self, room: &RoomCode) -> PathBuf {
+        self.dir.join(format!("{}.jsonl", room))
+    }
+
+    /// Helper: Queue a file operation for the writer thread
+    fn write(&self, op: WriteOp) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.ops.send(op);
+        }
+    }
+
+    /// Helper: Append one version of a message to a room's file
+    fn write_line(&mut self, room: &RoomCode, message: &StoredMessage) -> Result<(), AppError> {
+        let mut line = serde_json::to_string(message)?;
+        line.push('\n');
+        self.write(WriteOp::Append {
+            path: self.room_path(room),
+            lines: line,
+        });
+        *self.lines.entry(room.clone()).or_default() += 1;
+        self.compact_if_needed(room)
+    }
+
+    /// Helper: Rewrite a room's file from memory once it is twice as long
+    /// as the history it holds
+    fn compact_if_needed(&mut self, room: &RoomCode) -> Result<(), AppError> {
+        let capacity = self.cache.capacity.max(1);
+        let lines = self.lines.get(room).copied().unwrap_or_default();
+        if lines < 2 * capacity {
+            return Ok(());
+        }
+
+        let messages = self.cache.recent(room, capacity)?;
+        let mut lines = String::new();
+        for message in &messages {
+            lines.push_str(&serde_json::to_string(message)?);
+            lines.push('\n');
+        }
+        self.write(WriteOp::Rewrite {
+            path: self.room_path(room),
+            lines,
+        });
+        self.lines.insert(room.clone(), messages.len());
+        Ok(())
+    }
+}
+
+impl Drop for FileStore {
+    /// Finish pending writes before the files are used again
+    fn drop(&mut self) {
+        if let Some(Writer { ops, thread }) = self.writer.take() {
+            drop(ops);
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Helper: Read every message in a history file, and count its lines
+///
+/// A later line with an ID already seen replaces the earlier version.
+fn read_messages(path: &Path) -> Result<(Vec<StoredMessage>, usize), AppError> {
+    let file = File::open(path)?;
+    let mut messages: Vec<StoredMessage> = Vec::new();
+    let mut lines = 0;
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        lines += 1;
+        let message: StoredMessage = serde_json::from_str(&line)?;
+        let found = messages.binary_search_by_key(&message.message_id, |m| m.message_id);
+        match found {
+            Ok(index) => messages[index] = message,
+            Err(_) => messages.push(message),
+        }
+    }
+    Ok((messages, lines))
+}
+
+impl MessageStore for FileStore {
+    fn append(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError> {
+        self.last_id = self.last_id.max(message.message_id);
+        if self.cache.capacity == 0 {
+            return Ok(());
+        }
+        self.cache.append(room, message.clone())?;
+        self.write_line(room, &message)
+    }
+
+    fn before(
+        &self,
+        room: &RoomCode,
+        before: Option<MessageId>,
+        limit: usize,
+    ) -> Result<Vec<StoredMessage>, AppError> {
+        self.cache.before(room, before, limit)
+    }
+
+    fn update(&mut self, room: &RoomCode, message: StoredMessage) -> Result<(), AppError> {
+        if self.cache.get(room, message.message_id)?.is_none() {
+            return Ok(());
+        }
+        self.cache.update(room, message.clone())?;
+        self.write_line(room, &message)
+    }
+
+    fn remove_room(&mut self, room: &RoomCode) -> Result<(), AppError> {
+        self.cache.remove_room(room)?;
+        if self.lines.remove(room).is_some() {
+            self.write(WriteOp::Remove {
+                path: self.room_path(room),
+            });
+        }
+        Ok(())
+    }
+
+    fn last_message_id(&self) -> MessageId {
+        self.last_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: u64) -> StoredMessage {
+        StoredMessage {
+            message_id: MessageId(id),
+            from: "Alice".to_string(),
+            content: format!("message {}", id),
+            server_ts: 1_700_000_000_000 + id,
+            edited_ts: None,
+            deleted: false,
+            reply_to: None,
+        }
+    }
+
+    fn ids(messages: &[StoredMessage]) -> Vec<u64> {
+        messages.iter().map(|m| m.message_id.0).collect()
+    }
+
+    fn check_paging(store: &mut dyn MessageStore) {
+        let room = RoomCode::from_string("ABC123".to_string());
+        for id in 1..=5 {
+            store.append(&room, message(id)).unwrap();
+        }
+
+        assert_eq!(ids(&store.recent(&room, 2).unwrap()), vec![4, 5]);
+        assert_eq!(
+            ids(&store.before(&room, Some(MessageId(4)), 2).unwrap()),
+            vec![2, 3]
+        );
+        assert_eq!(
+            ids(&store.before(&room, Some(MessageId(2)), 10).unwrap()),
+            vec![1]
+        );
+
+        let other = RoomCode::from_string("XYZ789".to_string());
+        assert!(store.recent(&other, 10).unwrap().is_empty());
+
+        store.remove_room(&room).unwrap();
+        assert!(store.recent(&room, 10).unwrap().is_empty());
+    }
+
+    fn check_update(store: &mut dyn MessageStore) {
+        let room = RoomCode::from_string("EDIT01".to_string());
+        for id in 1..=3 {
+            store.append(&room, message(id)).unwrap();
+        }
+
+        let edited = StoredMessage {
+            content: "edited".to_string(),
+            edited_ts: Some(1_800_000_000_000),
+            ..message(2)
+        };
+        store.update(&room, edited.clone()).unwrap();
+        assert_eq!(store.get(&room, MessageId(2)).unwrap(), Some(edited));
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![1, 2, 3]);
+
+        // Unknown IDs and rooms are ignored
+        store.update(&room, message(9)).unwrap();
+        let other = RoomCode::from_string("XYZ789".to_string());
+        store.update(&other, message(1)).unwrap();
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![1, 2, 3]);
+        assert!(store.recent(&other, 10).unwrap().is_empty());
+        assert_eq!(store.get(&room, MessageId(9)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_store_paging() {
+        check_paging(&mut MemoryStore::default());
+    }
+
+    #[test]
+    fn test_memory_store_update() {
+        check_update(&mut MemoryStore::default());
+    }
+
+    #[test]
+    fn test_reply_snippet_is_truncated() {
+        let short = message(1);
+        assert_eq!(ReplyTo::quote(&short).snippet, "message 1");
+
+        let long = StoredMessage {
+            content: "ä".repeat(REPLY_SNIPPET_CHARS + 1),
+            ..message(2)
+        };
+        let quote = ReplyTo::quote(&long);
+        assert_eq!(quote.message_id, MessageId(2));
+        assert_eq!(quote.from, "Alice");
+        assert_eq!(quote.snippet, format!("{}…", "ä".repeat(REPLY_SNIPPET_CHARS)));
+    }
+
+    #[test]
+    fn test_memory_store_evicts_oldest() {
+        let mut store = MemoryStore::new(3);
+        let room = RoomCode::from_string("ABC123".to_string());
+        for id in 1..=5 {
+            store.append(&room, message(id)).unwrap();
+        }
+
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_file_store_paging_and_reopen() {
+        let dir = std::env::temp_dir().join(format!("chat-history-{}", uuid::Uuid::new_v4()));
+
+        let mut store = FileStore::open(&dir, DEFAULT_ROOM_HISTORY).unwrap();
+        check_paging(&mut store);
+        check_update(&mut store);
+
+        // History and ID numbering survive a reopen
+        let room = RoomCode::from_string("KEEP01".to_string());
+        store.append(&room, message(7)).unwrap();
+        drop(store);
+
+        let store = FileStore::open(&dir, DEFAULT_ROOM_HISTORY).unwrap();
+        assert_eq!(store.last_message_id(), MessageId(7));
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![7]);
+
+        // Edits are replayed over the original on reopen
+        let edit = RoomCode::from_string("EDIT01".to_string());
+        let edited = store.get(&edit, MessageId(2)).unwrap().unwrap();
+        assert_eq!(edited.content, "edited");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_is_compacted() {
+        let dir = std::env::temp_dir().join(format!("chat-history-{}", uuid::Uuid::new_v4()));
+        let room = RoomCode::from_string("ABC123".to_string());
+
+        let mut store = FileStore::open(&dir, 3).unwrap();
+        for id in 1..=10 {
+            store.append(&room, message(id)).unwrap();
+        }
+        let deleted = StoredMessage {
+            deleted: true,
+            ..message(10)
+        };
+        store.update(&room, deleted).unwrap();
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![8, 9, 10]);
+        drop(store);
+
+        // The file never grows past twice the capacity
+        let path = dir.join(format!("{}.jsonl", room));
+        let lines = fs::read_to_string(path).unwrap().lines().count();
+        assert!(lines < 6, "{} lines", lines);
+
+        let store = FileStore::open(&dir, 3).unwrap();
+        assert_eq!(store.last_message_id(), MessageId(10));
+        assert_eq!(ids(&store.recent(&room, 10).unwrap()), vec![8, 9, 10]);
+        assert!(store.get(&room, MessageId(10)).unwrap().unwrap().deleted);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -6,11 +6,13 @@
 //! # Features
 //! - WebSocket connection handling
 //! - Username setup
-//! - Room creation with 6-character codes
+//! - Room creation with short codes (6 characters by default)
 //! - Room joining
 //! - Real-time chat messaging
+//! - Per-room message history with replay on join
 //! - Typing indicators
-//! - Disconnection handling
+//! - Disconnection handling with session resume
+//! - Layered configuration (TOML file, `CHAT_*` env vars, CLI flags)
 //!
 //! # Architecture
 //! Uses the Actor pattern with `mpsc` channels:
@@ -21,36 +23,63 @@
 //! # Example
 //! ```ignore
 //! use tokio::net::TcpListener;
-//! use tokio::sync::mpsc;
-//! use chat_server_v1::{ChatServer, handle_connection};
+//! use chat_server_v1::{ChatServerBuilder, ServerConfig};
 //!
 //! #[tokio::main]
 //! async fn main() {
-//!     let listener = TcpListener::bind("127.0.0.1:8080").await.unwrap();
-//!     let (cmd_tx, cmd_rx) = mpsc::channel(256);
+//!     let config = ServerConfig::default();
+//!     let listener = TcpListener::bind(&config.bind).await.unwrap();
+//!     let handle = ChatServerBuilder::new(config).build().unwrap();
 //!
-//!     tokio::spawn(ChatServer::new(cmd_rx).run());
+//!     let server = handle.clone();
+//!     tokio::spawn(async move { server.serve(listener.into()).await });
 //!
-//!     while let Ok((stream, _)) = listener.accept().await {
-//!         let cmd_tx = cmd_tx.clone();
-//!         tokio::spawn(handle_connection(stream, cmd_tx));
-//!     }
+//!     tokio::signal::ctrl_c().await.unwrap();
+//!     handle.shutdown().await;
 //! }
 //! ```
+//!
+//! For lower-level control, create the `ChatServer` actor yourself and
+//! run `handle_connection` for each stream.
 
+pub mod access;
+pub mod builder;
 pub mod client;
+pub mod codes;
+pub mod config;
 pub mod error;
 pub mod handler;
+pub mod history;
+pub mod listener;
+pub mod matchmaking;
 pub mod message;
+pub mod metrics;
+pub mod outbox;
+pub mod ratelimit;
+pub mod reaction;
 pub mod room;
 pub mod server;
+pub mod tls;
 pub mod types;
+pub mod username;
 
 // Re-export main types for convenience
+pub use access::RoomAccess;
+pub use builder::{ChatServerBuilder, ServerHandle};
 pub use client::Client;
+pub use codes::{CrockfordCodes, DigitCodes, RoomCodeGenerator, WordCodes};
+pub use config::{Cli, CodeStyle, HistoryBackend, RoomConfig, ServerConfig};
 pub use error::{AppError, SendError};
-pub use handler::handle_connection;
+pub use handler::{handle_connection, ConnectionConfig, Peer};
+pub use history::{FileStore, MemoryStore, MessageStore, ReplyTo, StoredMessage};
+pub use listener::{FileMode, ListenMode, Listener, Stream, UnixConfig};
+pub use matchmaking::MatchQueue;
 pub use message::{ClientMessage, ErrorCode, ServerMessage};
-pub use room::Room;
-pub use server::{ChatServer, ServerCommand};
-pub use types::{ClientId, RoomCode};
+pub use outbox::{DeliveryPolicy, OutboxReceiver, OutboxSender};
+pub use ratelimit::{RateLimit, RateLimitConfig, RateLimiter};
+pub use reaction::Reactions;
+pub use room::{ExpiryReason, Room};
+pub use server::{ChatServer, ServerCommand, ServerStats};
+pub use tls::{ClientAuth, TlsAcceptor, TlsConfig};
+pub use types::{ClientId, MessageId, ResumeToken, RoomCode};
+pub use username::{CharClass, UsernamePolicy};
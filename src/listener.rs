@@ -0,0 +1,347 @@
+//! Listeners for incoming connections
+//!
+//! The server can accept on TCP, on a Unix domain socket, or on both at
+//! once. Every listener feeds the same accept loop and therefore the same
+//! `ChatServer` command channel; `handle_connection` doesn't care which
+//! one a stream came from.
+//!
+//! A Unix socket path left behind by a crashed server is removed at bind
+//! time, but only if nothing is listening on it any more. The socket file
+//! is removed again when the listener is dropped.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::error::AppError;
+use crate::handler::Peer;
+
+/// Default path of the Unix domain socket
+pub const DEFAULT_UNIX_PATH: &str = "chat.sock";
+
+/// Default permissions of the Unix domain socket file
+pub const DEFAULT_UNIX_MODE: FileMode = FileMode(0o660);
+
+/// Which listeners to start
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenMode {
+    /// TCP on `bind` only
+    #[default]
+    Tcp,
+    /// Unix domain socket on `unix.path` only
+    Unix,
+    /// Both at once
+    Both,
+}
+
+impl ListenMode {
+    fn tcp(self) -> bool {
+        matches!(self, ListenMode::Tcp | ListenMode::Both)
+    }
+
+    fn unix(self) -> bool {
+        matches!(self, ListenMode::Unix | ListenMode::Both)
+    }
+}
+
+/// Unix permission bits, written in octal (`"660"`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMode(pub u32);
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04o}", self.0)
+    }
+}
+
+impl FromStr for FileMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix("0o").unwrap_or(s);
+        match u32::from_str_radix(digits, 8) {
+            Ok(mode) if mode <= 0o7777 => Ok(FileMode(mode)),
+            _ => Err(format!(
+                "invalid file mode {:?}, expected octal like 660",
+                s
+            )),
+        }
+    }
+}
+
+impl Serialize for FileMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Unix domain socket settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnixConfig {
+    /// Socket file path
+    pub path: PathBuf,
+    /// Permissions set on the socket file after binding
+    pub mode: FileMode,
+}
+
+impl Default for UnixConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_UNIX_PATH),
+            mode: DEFAULT_UNIX_MODE,
+        }
+    }
+}
+
+/// An accepted connection from any listener
+#[derive(Debug)]
+pub enum Stream {
+    /// From the TCP listener (TLS, if enabled, is not yet applied)
+    Tcp(TcpStream),
+    /// From the Unix socket listener
+    #[cfg(unix)]
+    Unix(tokio::net::UnixStream),
+}
+
+/// The TCP and/or Unix listeners of a server
+#[derive(Debug)]
+pub struct Listener {
+    tcp: Option<TcpListener>,
+    #[cfg(unix)]
+    unix: Option<unix::UnixSocket>,
+}
+
+impl Listener {
+    /// Bind the listeners selected by `mode`
+    pub async fn bind(mode: ListenMode, tcp: &str, unix: &UnixConfig) -> Result<Self, AppError> {
+        let tcp = match mode.tcp() {
+            true => Some(TcpListener::bind(tcp).await?),
+            false => None,
+        };
+
+        #[cfg(unix)]
+        let unix = match mode.unix() {
+            true => Some(unix::UnixSocket::bind(unix)?),
+            false => None,
+        };
+        #[cfg(not(unix))]
+        if mode.unix() {
+            let _ = unix;
+            return Err(AppError::Config(
+                "Unix domain sockets are not supported on this platform".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            tcp,
+            #[cfg(unix)]
+            unix,
+        })
+    }
+
+    /// Human-readable addresses being listened on, e.g. for logging
+    pub fn addresses(&self) -> Vec<String> {
+        let mut addrs = Vec::new();
+        if let Some(Ok(addr)) = self.tcp.as_ref().map(TcpListener::local_addr) {
+            addrs.push(addr.to_string());
+        }
+        #[cfg(unix)]
+        if let Some(socket) = &self.unix {
+            addrs.push(format!("unix:{}", socket.path.display()));
+        }
+        addrs
+    }
+
+    /// Wait for the next connection on any listener
+    pub async fn accept(&self) -> io::Result<(Stream, Peer)> {
+        let tcp = async {
+            match &self.tcp {
+                Some(listener) => {
+                    let (stream, addr) = listener.accept().await?;
+                    Ok((Stream::Tcp(stream), Peer::Tcp(addr)))
+                }
+                None => std::future::pending().await,
+            }
+        };
+
+        #[cfg(unix)]
+        let unix = async {
+            match &self.unix {
+                Some(socket) => {
+                    let (stream, _) = socket.listener.accept().await?;
+                    Ok((Stream::Unix(stream), Peer::Unix(socket.path.clone())))
+                }
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let unix = std::future::pending();
+
+        tokio::select! {
+            result = tcp => result,
+            result = unix => result,
+        }
+    }
+}
+
+/// Wrap an already bound TCP listener
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Self {
+            tcp: Some(listener),
+            #[cfg(unix)]
+            unix: None,
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::fs::{self, Permissions};
+    use std::io;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::path::{Path, PathBuf};
+
+    use tokio::net::UnixListener;
+    use tracing::{info, warn};
+
+    use super::UnixConfig;
+    use crate::error::AppError;
+
+    /// A bound Unix listener that removes its socket file when dropped
+    #[derive(Debug)]
+    pub(super) struct UnixSocket {
+        pub(super) listener: UnixListener,
+        pub(super) path: PathBuf,
+    }
+
+    impl UnixSocket {
+        pub(super) fn bind(config: &UnixConfig) -> Result<Self, AppError> {
+            let path = &config.path;
+            remove_stale(path)?;
+
+            let listener = UnixListener::bind(path)
+                .map_err(|e| AppError::Config(format!("cannot bind {}: {}", path.display(), e)))?;
+            let socket = Self {
+                listener,
+                path: path.clone(),
+            };
+            fs::set_permissions(path, Permissions::from_mode(config.mode.0))?;
+            Ok(socket)
+        }
+    }
+
+    impl Drop for UnixSocket {
+        fn drop(&mut self) {
+            if let Err(e) = fs::remove_file(&self.path) {
+                warn!("Failed to remove {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    /// Helper: Remove a socket file nobody is listening on
+    ///
+    /// Refuses to touch anything that isn't a socket, or a socket that
+    /// another process still accepts on.
+    fn remove_stale(path: &Path) -> Result<(), AppError> {
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if !metadata.file_type().is_socket() {
+            return Err(AppError::Config(format!(
+                "{} exists and is not a socket",
+                path.display()
+            )));
+        }
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => Err(AppError::Config(format!(
+                "{} is in use by another server",
+                path.display()
+            ))),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                info!("Removing stale socket {}", path.display());
+                fs::remove_file(path)?;
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_mode_parsing() {
+        assert_eq!("660".parse(), Ok(FileMode(0o660)));
+        assert_eq!("0600".parse(), Ok(FileMode(0o600)));
+        assert_eq!("0o777".parse(), Ok(FileMode(0o777)));
+        assert!("888".parse::<FileMode>().is_err());
+        assert!("17777".parse::<FileMode>().is_err());
+        assert_eq!(FileMode(0o660).to_string(), "0660");
+    }
+}
@@ -1,67 +1,119 @@
 //! 1:1 WebSocket Chat Server - Entry Point
 //!
-//! Starts the TCP listener and ChatServer actor, accepting connections.
-
-use std::env;
+//! Loads the configuration, starts the TCP and/or Unix listeners and the
+//! ChatServer actor, and accepts connections until SIGINT/SIGTERM, then
+//! shuts down gracefully.
 
+use clap::Parser;
 use tokio::net::TcpListener;
-use tokio::sync::mpsc;
 use tracing::{error, info};
 use tracing_subscriber::EnvFilter;
 
-use chat_server_v1::{handle_connection, ChatServer};
-
-/// Default server address
-const DEFAULT_ADDR: &str = "127.0.0.1:8080";
-
-/// Channel buffer size for server commands
-const CHANNEL_BUFFER_SIZE: usize = 256;
+use chat_server_v1::{metrics, ChatServerBuilder, Cli, Listener, ServerConfig, TlsAcceptor};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Defaults < config file < CHAT_* env vars < command-line flags
+    let cli = Cli::parse();
+    let config = match ServerConfig::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    if cli.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+
     // Initialize logging with environment filter
-    // Use RUST_LOG env var to control log level
+    // RUST_LOG wins over the configured filter when set
     // e.g., RUST_LOG=debug or RUST_LOG=chat_server_v1=trace
     tracing_subscriber::fmt()
         .with_env_filter(
             EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| EnvFilter::new("chat_server_v1=info")),
+                .unwrap_or_else(|_| EnvFilter::new(&config.log.filter)),
         )
         .init();
 
-    // Get bind address from command line or use default
-    let addr = env::args()
-        .nth(1)
-        .unwrap_or_else(|| DEFAULT_ADDR.to_string());
-
-    // Start TCP listener
-    let listener = TcpListener::bind(&addr).await?;
-    info!("WebSocket Chat Server listening on {}", addr);
+    // Load the certificate before binding so a bad one fails fast
+    let tls = if config.tls.enabled {
+        let acceptor = TlsAcceptor::new(&config.tls)?;
+        if !config.tls.reload_interval.is_zero() {
+            tokio::spawn(acceptor.clone().watch(config.tls.reload_interval));
+        }
+        Some(acceptor)
+    } else {
+        None
+    };
 
-    // Create ChatServer actor channel and start
-    let (cmd_tx, cmd_rx) = mpsc::channel(CHANNEL_BUFFER_SIZE);
-    let server = ChatServer::new(cmd_rx);
-    tokio::spawn(server.run());
+    // Start the TCP and/or Unix listeners
+    let listener = Listener::bind(config.listen, &config.bind, &config.unix).await?;
+    let scheme = if tls.is_some() { "wss" } else { "ws" };
+    for addr in listener.addresses() {
+        info!("WebSocket Chat Server listening on {}://{}", scheme, addr);
+    }
 
+    // Start the ChatServer actor
+    let mut builder = ChatServerBuilder::new(config.clone()).store(config.history.open_store()?);
+    if let Some(tls) = tls {
+        builder = builder.tls(tls);
+    }
+    let handle = builder.build()?;
     info!("ChatServer actor started");
 
-    // Connection accept loop
-    loop {
-        match listener.accept().await {
-            Ok((stream, addr)) => {
-                info!("New connection from {}", addr);
-                let cmd_tx = cmd_tx.clone();
+    // Metrics endpoint on its own port
+    if config.metrics.enabled {
+        let metrics_listener = TcpListener::bind(&config.metrics.bind).await?;
+        info!("Metrics available at http://{}/metrics", config.metrics.bind);
+        tokio::spawn(metrics::serve(metrics_listener));
+    }
+
+    // Accept until SIGINT/SIGTERM, then stop accepting, tell every client
+    // and give connections a bounded time to flush and close
+    let serve = handle.serve(listener);
+    tokio::pin!(serve);
+    tokio::select! {
+        _ = &mut serve => {}
+        _ = shutdown_signal() => {
+            handle.shutdown().await;
+            serve.await;
+        }
+    }
+
+    info!("Server stopped");
+    Ok(())
+}
 
-                // Spawn handler task for each connection
-                tokio::spawn(async move {
-                    if let Err(e) = handle_connection(stream, cmd_tx).await {
-                        error!("Connection handler error: {}", e);
-                    }
-                });
+/// Wait for SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
             }
             Err(e) => {
-                error!("Failed to accept connection: {}", e);
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
             }
         }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl+C"),
+        _ = terminate => info!("Received SIGTERM"),
     }
 }
@@ -0,0 +1,212 @@
+//! Random-partner matchmaking
+//!
+//! Clients looking for a stranger to talk to wait in a single FIFO queue.
+//! A newcomer is paired with the longest-waiting compatible client: one
+//! who shares at least one tag with them, or any client at all if either
+//! side gave no tags.
+
+use std::collections::VecDeque;
+
+use crate::error::AppError;
+use crate::types::ClientId;
+
+/// Most tags a client may search with
+pub const MAX_TAGS: usize = 8;
+
+/// Longest accepted tag, in characters
+pub const MAX_TAG_CHARS: usize = 32;
+
+/// Trim, lowercase and deduplicate search tags
+pub fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, AppError> {
+    let invalid = |reason: String| AppError::InvalidMessage {
+        field: Some("tags".to_string()),
+        reason,
+    };
+    if tags.len() > MAX_TAGS {
+        return Err(invalid(format!("at most {} tags are allowed", MAX_TAGS)));
+    }
+
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        let length = tag.chars().count();
+        if length == 0 || length > MAX_TAG_CHARS {
+            return Err(invalid(format!(
+                "tags must be 1-{} characters long",
+                MAX_TAG_CHARS
+            )));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Result of looking for a partner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Search {
+    /// Paired with a waiting client, who left the queue from `vacated`
+    Matched { partner: ClientId, vacated: usize },
+    /// Nobody compatible is waiting; the client is queued at `position`
+    Waiting { position: usize },
+}
+
+/// A client waiting for a partner
+#[derive(Debug)]
+struct Waiter {
+    client_id: ClientId,
+    tags: Vec<String>,
+}
+
+impl Waiter {
+    /// Check if this waiter may be paired with someone searching for `tags`
+    fn accepts(&self, tags: &[String]) -> bool {
+        self.tags.is_empty() || tags.is_empty() || self.tags.iter().any(|t| tags.contains(t))
+    }
+}
+
+/// Clients waiting for a random partner, longest-waiting first
+///
+/// Positions are 1-based, as shown to clients.
+#[derive(Debug, Default)]
+pub struct MatchQueue {
+    waiting: VecDeque<Waiter>,
+}
+
+impl MatchQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of waiting clients
+    pub fn len(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Check if nobody is waiting
+    pub fn is_empty(&self) -> bool {
+        self.waiting.is_empty()
+    }
+
+    /// Get a client's place in the queue
+    pub fn position(&self, client_id: ClientId) -> Option<usize> {
+        self.waiting
+            .iter()
+            .position(|w| w.client_id == client_id)
+            .map(|index| index + 1)
+    }
+
+    /// Pair the client with a compatible waiter, or queue them
+    ///
+    /// `tags` should already be normalized.
+    pub fn find(&mut self, client_id: ClientId, tags: Vec<String>) -> Search {
+        match self.waiting.iter().position(|w| w.accepts(&tags)) {
+            Some(index) => {
+                let partner = self.waiting.remove(index).expect("index is in range");
+                Search::Matched {
+                    partner: partner.client_id,
+                    vacated: index + 1,
+                }
+            }
+            None => {
+                self.waiting.push_back(Waiter { client_id, tags });
+                Search::Waiting {
+                    position: self.waiting.len(),
+                }
+            }
+        }
+    }
+
+    /// Take a client out of the queue, returning the position they left
+    pub fn remove(&mut self, client_id: ClientId) -> Option<usize> {
+        let index = self.waiting.iter().position(|w| w.client_id == client_id)?;
+        self.waiting.remove(index);
+        Some(index + 1)
+    }
+
+    /// Waiting clients from `position` on, with their positions
+    pub fn from_position(&self, position: usize) -> Vec<(ClientId, usize)> {
+        self.waiting
+            .iter()
+            .enumerate()
+            .skip(position.saturating_sub(1))
+            .map(|(index, w)| (w.client_id, index + 1))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_tags() {
+        assert_eq!(
+            normalize_tags(tags(&[" Rust ", "rust", "GO"])).unwrap(),
+            tags(&["rust", "go"])
+        );
+        assert!(normalize_tags(tags(&["  "])).is_err());
+        assert!(normalize_tags(tags(&[&"x".repeat(MAX_TAG_CHARS + 1)])).is_err());
+        assert!(normalize_tags(vec!["a".to_string(); MAX_TAGS + 1]).is_err());
+    }
+
+    #[test]
+    fn test_first_compatible_waiter_is_matched() {
+        let (alice, bob, carol, dave) = (
+            ClientId::new(),
+            ClientId::new(),
+            ClientId::new(),
+            ClientId::new(),
+        );
+        let mut queue = MatchQueue::new();
+
+        assert_eq!(
+            queue.find(alice, tags(&["music"])),
+            Search::Waiting { position: 1 }
+        );
+        assert_eq!(
+            queue.find(bob, tags(&["games"])),
+            Search::Waiting { position: 2 }
+        );
+
+        // Carol shares a tag with Bob only; Dave will talk to anyone
+        assert_eq!(
+            queue.find(carol, tags(&["games", "films"])),
+            Search::Matched {
+                partner: bob,
+                vacated: 2
+            }
+        );
+        assert_eq!(
+            queue.find(dave, Vec::new()),
+            Search::Matched {
+                partner: alice,
+                vacated: 1
+            }
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_remove_and_positions() {
+        let clients: Vec<ClientId> = (0..4).map(|_| ClientId::new()).collect();
+        let mut queue = MatchQueue::new();
+        for &client_id in &clients {
+            queue.find(client_id, tags(&[&client_id.to_string()]));
+        }
+
+        assert_eq!(queue.remove(clients[1]), Some(2));
+        assert_eq!(queue.remove(clients[1]), None);
+        assert_eq!(queue.position(clients[3]), Some(3));
+        assert_eq!(
+            queue.from_position(2),
+            vec![(clients[2], 2), (clients[3], 3)]
+        );
+    }
+}
@@ -4,8 +4,21 @@
 //! for type-safe serialization/deserialization.
 
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::error::AppError;
+use crate::history::{ReplyTo, StoredMessage};
+use crate::room::{self, ExpiryReason};
+use crate::types::MessageId;
+
+/// Longest accepted `client_msg_id`, in bytes
+pub const MAX_CLIENT_MSG_ID_LEN: usize = 64;
+
+/// Most fields a rejected frame may have for the bad one to be looked for
+///
+/// No client message has anywhere near this many, and each probe re-parses
+/// the frame, so larger frames are rejected without naming a field.
+const MAX_PROBED_FIELDS: usize = 8;
 
 /// Client → Server message
 ///
@@ -15,18 +28,82 @@ use crate::error::AppError;
 pub enum ClientMessage {
     /// Set username (required before room operations)
     SetUsername { username: String },
-    /// Create a new room
-    CreateRoom,
-    /// Join an existing room by code
-    JoinRoom { room_code: String },
+    /// Create a new room (capacity defaults to 2 for 1:1 chat)
+    ///
+    /// A `password` is required to join unless the joiner has an invite;
+    /// an `invite_only` room can only be joined with an invite.
+    CreateRoom {
+        #[serde(default)]
+        capacity: Option<usize>,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        invite_only: bool,
+    },
+    /// Join an existing room by code, with its password or an invite
+    JoinRoom {
+        room_code: String,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        invite: Option<String>,
+    },
+    /// Create an invite to your room (host only)
+    ///
+    /// `ttl_secs` defaults to the server's invite lifetime.
+    CreateInvite {
+        #[serde(default)]
+        single_use: bool,
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    },
     /// Send a chat message
-    Chat { content: String },
+    ///
+    /// `client_msg_id` is echoed in the `chat_ack`; resending a message
+    /// with the same id is acknowledged again but not delivered twice.
+    /// `reply_to` quotes a message still in the room's history.
+    Chat {
+        content: String,
+        #[serde(default)]
+        client_msg_id: Option<String>,
+        #[serde(default)]
+        reply_to: Option<MessageId>,
+    },
+    /// Fetch older room history (newest page if `before` is omitted)
+    FetchHistory {
+        #[serde(default)]
+        before: Option<MessageId>,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Mark every room message up to this ID as read
+    MarkRead { up_to_message_id: MessageId },
+    /// Replace the content of one of your own messages
+    EditMessage {
+        message_id: MessageId,
+        content: String,
+    },
+    /// Delete one of your own messages
+    DeleteMessage { message_id: MessageId },
+    /// Add an emoji reaction to a room message
+    React { message_id: MessageId, emoji: String },
+    /// Take back an emoji reaction
+    Unreact { message_id: MessageId, emoji: String },
     /// Indicate typing started
     Typing,
     /// Indicate typing stopped
     StopTyping,
     /// Leave the current room
     LeaveRoom,
+    /// Wait for a random partner, preferably one sharing a tag
+    FindPartner {
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// Stop waiting for a random partner
+    CancelFind,
+    /// Reclaim a dropped session (must be sent before anything else)
+    Resume { token: String },
 }
 
 /// Server → Client message
@@ -35,79 +112,489 @@ pub enum ClientMessage {
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ServerMessage {
-    /// Connection successful, client ID issued
-    Connected { client_id: String },
+    /// Connection successful, client ID and resume token issued
+    Connected {
+        client_id: String,
+        resume_token: String,
+    },
+    /// Previous session reclaimed; buffered messages follow
+    ///
+    /// `resume_token` replaces the token used to resume.
+    ///
+    /// `unread` counts room messages not yet marked read.
+    Resumed {
+        client_id: String,
+        resume_token: String,
+        username: Option<String>,
+        room_code: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unread: Option<u64>,
+    },
     /// Username set successfully
     UsernameSet { username: String },
     /// Room created successfully
-    RoomCreated { room_code: String },
+    RoomCreated {
+        room_code: String,
+        capacity: usize,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        password_protected: bool,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        invite_only: bool,
+    },
+    /// Invite created; pass `invite` on to whoever should join
+    InviteCreated {
+        invite: String,
+        single_use: bool,
+        expires_in_secs: u64,
+    },
     /// Room joined successfully
+    ///
+    /// `partner` is the host's name. `members` lists everyone in a group
+    /// room (including the joiner) and is omitted for 1:1 rooms.
+    /// `history` replays the most recent messages, oldest first.
+    /// `unread` counts room messages the joiner hasn't marked read.
     RoomJoined {
         room_code: String,
         partner: Option<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        members: Vec<String>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        history: Vec<StoredMessage>,
+        unread: u64,
     },
-    /// Partner joined the room
+    /// Partner joined the room (1:1 rooms)
     PartnerJoined { username: String },
+    /// Partner changed their username (1:1 rooms)
+    PartnerRenamed {
+        old_username: String,
+        username: String,
+    },
+    /// A member joined a group room
+    MemberJoined {
+        username: String,
+        members: Vec<String>,
+    },
+    /// A member left a group room
+    MemberLeft {
+        username: String,
+        members: Vec<String>,
+    },
+    /// A member of a group room changed their username
+    MemberRenamed {
+        old_username: String,
+        username: String,
+        members: Vec<String>,
+    },
     /// Chat message received
-    Chat { from: String, content: String },
+    ///
+    /// `reply_to` quotes the message this one replies to.
+    Chat {
+        message_id: MessageId,
+        from: String,
+        content: String,
+        server_ts: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reply_to: Option<ReplyTo>,
+    },
+    /// Your chat message was recorded and relayed to the room
+    ChatAck {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        client_msg_id: Option<String>,
+        message_id: MessageId,
+        server_ts: u64,
+    },
+    /// A page of room history, oldest first
+    History {
+        messages: Vec<StoredMessage>,
+        has_more: bool,
+    },
+    /// A member read every message up to `up_to_message_id`
+    PartnerRead {
+        username: String,
+        up_to_message_id: MessageId,
+    },
+    /// A message was edited by its author (also sent to the author)
+    MessageEdited {
+        message_id: MessageId,
+        content: String,
+        edited_ts: u64,
+    },
+    /// A message was deleted by its author (also sent to the author)
+    MessageDeleted { message_id: MessageId },
+    /// A member added (`added`) or removed a reaction; `count` is the
+    /// emoji's new total on the message (sent to every member)
+    ReactionUpdated {
+        message_id: MessageId,
+        emoji: String,
+        username: String,
+        added: bool,
+        count: usize,
+    },
     /// Partner is typing
     PartnerTyping,
     /// Partner stopped typing
     PartnerStopTyping,
-    /// Partner left the room
+    /// Partner left the room (1:1 rooms)
     PartnerLeft,
+    /// A member lost their connection; their seat is held for a while
+    PartnerReconnecting { username: String },
+    /// A member resumed their session
+    PartnerReconnected { username: String },
+    /// Place in the random-partner queue (sent on joining it and as it moves)
+    QueuePosition { position: usize },
+    /// Paired with a random partner in a new 1:1 room
+    Matched { room_code: String, partner: String },
+    /// Left the random-partner queue on request
+    FindCancelled,
+    /// The server closed the room; every member is now out of it
+    RoomExpired { reason: ExpiryReason },
+    /// The server is going down; the connection closes after this
+    ///
+    /// `reconnect_after` suggests how long to wait before reconnecting (ms).
+    ServerShutdown {
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reconnect_after: Option<u64>,
+    },
     /// Error occurred
-    Error { code: ErrorCode, message: String },
+    ///
+    /// `field` names the offending field of a rejected client message.
+    Error {
+        #[serde(flatten)]
+        code: ErrorCode,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        field: Option<String>,
+    },
+}
+
+impl ClientMessage {
+    /// The `type` tag of every client message
+    pub const TYPES: [&'static str; 17] = [
+        "set_username",
+        "create_room",
+        "join_room",
+        "create_invite",
+        "chat",
+        "fetch_history",
+        "mark_read",
+        "edit_message",
+        "delete_message",
+        "react",
+        "unreact",
+        "typing",
+        "stop_typing",
+        "leave_room",
+        "find_partner",
+        "cancel_find",
+        "resume",
+    ];
+
+    /// The `type` tag of this message (used for per-type rate limits)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientMessage::SetUsername { .. } => "set_username",
+            ClientMessage::CreateRoom { .. } => "create_room",
+            ClientMessage::JoinRoom { .. } => "join_room",
+            ClientMessage::CreateInvite { .. } => "create_invite",
+            ClientMessage::Chat { .. } => "chat",
+            ClientMessage::FetchHistory { .. } => "fetch_history",
+            ClientMessage::MarkRead { .. } => "mark_read",
+            ClientMessage::EditMessage { .. } => "edit_message",
+            ClientMessage::DeleteMessage { .. } => "delete_message",
+            ClientMessage::React { .. } => "react",
+            ClientMessage::Unreact { .. } => "unreact",
+            ClientMessage::Typing => "typing",
+            ClientMessage::StopTyping => "stop_typing",
+            ClientMessage::LeaveRoom => "leave_room",
+            ClientMessage::FindPartner { .. } => "find_partner",
+            ClientMessage::CancelFind => "cancel_find",
+            ClientMessage::Resume { .. } => "resume",
+        }
+    }
+
+    /// Parse a client message from a text frame
+    ///
+    /// Unlike a bare `serde_json::from_str`, failures report which field
+    /// (or the `type` tag) was wrong so the client can be told precisely.
+    pub fn parse(text: &str) -> Result<Self, AppError> {
+        let value: Value = serde_json::from_str(text)?;
+
+        let Some(object) = value.as_object() else {
+            return Err(invalid_message(None, "expected a JSON object"));
+        };
+
+        match object.get("type") {
+            Some(Value::String(_)) => {}
+            Some(_) => return Err(invalid_message(Some("type"), "`type` must be a string")),
+            None => return Err(invalid_message(Some("type"), "missing field `type`")),
+        }
+
+        serde_json::from_value::<Self>(value.clone()).map_err(|e| {
+            let reason = e.to_string();
+            let field = backticked_after(&reason, "missing field ")
+                .or_else(|| backticked_after(&reason, "unknown field "))
+                .or_else(|| {
+                    backticked_after(&reason, "unknown variant ").map(|_| "type".to_string())
+                })
+                .or_else(|| find_invalid_field(object));
+            AppError::InvalidMessage { field, reason }
+        })
+    }
+}
+
+/// Helper: Build an `AppError::InvalidMessage`
+fn invalid_message(field: Option<&str>, reason: &str) -> AppError {
+    AppError::InvalidMessage {
+        field: field.map(str::to_string),
+        reason: reason.to_string(),
+    }
+}
+
+/// Helper: Extract the backticked name following `prefix` in a serde error
+fn backticked_after(reason: &str, prefix: &str) -> Option<String> {
+    let rest = &reason[reason.find(prefix)? + prefix.len()..];
+    let rest = rest.strip_prefix('`')?;
+    Some(rest[..rest.find('`')?].to_string())
+}
+
+/// Helper: Find the field whose value has the wrong type
+///
+/// serde does not report the field for type mismatches inside a tagged
+/// enum, so drop one field at a time: the culprit is the field whose
+/// removal makes the message parse (optional field) or turns the error
+/// into "missing field" for that same field (required field).
+fn find_invalid_field(object: &Map<String, Value>) -> Option<String> {
+    if object.len() > MAX_PROBED_FIELDS {
+        return None;
+    }
+    object.keys().filter(|key| *key != "type").find_map(|key| {
+        let mut trimmed = object.clone();
+        trimmed.remove(key);
+        match serde_json::from_value::<ClientMessage>(Value::Object(trimmed)) {
+            Ok(_) => Some(key.clone()),
+            Err(e) if backticked_after(&e.to_string(), "missing field ").as_ref() == Some(key) => {
+                Some(key.clone())
+            }
+            Err(_) => None,
+        }
+    })
+}
+
+impl ServerMessage {
+    /// Check if this message is a transient indicator that is safe to drop
+    ///
+    /// Used by the outbox delivery policy when a client falls behind.
+    pub fn is_ephemeral(&self) -> bool {
+        matches!(
+            self,
+            ServerMessage::PartnerTyping | ServerMessage::PartnerStopTyping
+        )
+    }
 }
 
 /// Error codes for ServerMessage::Error
 ///
 /// Represents different error scenarios that can be communicated to clients.
+/// Serialized as the `code` field of the error; extra details of a variant
+/// become sibling fields.
 #[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(tag = "code", rename_all = "snake_case")]
 pub enum ErrorCode {
     /// Attempted action without setting username
     UsernameRequired,
+    /// Username breaks the username policy
+    InvalidUsername,
+    /// Username (or a look-alike) is already in use
+    UsernameTaken,
     /// Non-existent room code
     RoomNotFound,
-    /// Room already has 2 people
+    /// Room has no free seats
     RoomFull,
+    /// No unused room code could be found; try again later
+    RoomCodeUnavailable,
+    /// Room password missing or wrong
+    WrongPassword,
+    /// Invite missing, unknown, expired or already used
+    InviteInvalid,
+    /// Only the room's host may do this
+    NotRoomHost,
+    /// Requested room capacity is out of range
+    InvalidCapacity,
     /// Attempted chat without joining a room
     NotInRoom,
     /// Already in a room
     AlreadyInRoom,
+    /// Already waiting for a random partner
+    AlreadySearching,
+    /// Not waiting for a random partner
+    NotSearching,
     /// Invalid message format
     InvalidMessage,
+    /// Resume token is unknown, expired, or still in use
+    ResumeFailed,
+    /// No such message in the current room (or it was deleted)
+    MessageNotFound,
+    /// Only the author may edit or delete a message
+    NotMessageAuthor,
+    /// Reaction is not a single emoji
+    InvalidEmoji,
+    /// The message already has `max` distinct reactions
+    TooManyReactions { max: usize },
+    /// Too many messages; wait `retry_after_ms` before sending this type again
+    RateLimited { retry_after_ms: u64 },
+    /// Chat content is longer than `max_chars` characters or `max_bytes` bytes
+    MessageTooLarge { max_chars: usize, max_bytes: usize },
+}
+
+impl ErrorCode {
+    /// The code as sent on the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::UsernameRequired => "username_required",
+            ErrorCode::InvalidUsername => "invalid_username",
+            ErrorCode::UsernameTaken => "username_taken",
+            ErrorCode::RoomNotFound => "room_not_found",
+            ErrorCode::RoomFull => "room_full",
+            ErrorCode::RoomCodeUnavailable => "room_code_unavailable",
+            ErrorCode::WrongPassword => "wrong_password",
+            ErrorCode::InviteInvalid => "invite_invalid",
+            ErrorCode::NotRoomHost => "not_room_host",
+            ErrorCode::InvalidCapacity => "invalid_capacity",
+            ErrorCode::NotInRoom => "not_in_room",
+            ErrorCode::AlreadyInRoom => "already_in_room",
+            ErrorCode::AlreadySearching => "already_searching",
+            ErrorCode::NotSearching => "not_searching",
+            ErrorCode::InvalidMessage => "invalid_message",
+            ErrorCode::ResumeFailed => "resume_failed",
+            ErrorCode::MessageNotFound => "message_not_found",
+            ErrorCode::NotMessageAuthor => "not_message_author",
+            ErrorCode::InvalidEmoji => "invalid_emoji",
+            ErrorCode::TooManyReactions { .. } => "too_many_reactions",
+            ErrorCode::RateLimited { .. } => "rate_limited",
+            ErrorCode::MessageTooLarge { .. } => "message_too_large",
+        }
+    }
 }
 
 /// Convert AppError to ServerMessage for client notification
 impl From<AppError> for ServerMessage {
     fn from(err: AppError) -> Self {
+        let mut field = None;
         let (code, message) = match &err {
             AppError::UsernameRequired => {
                 (ErrorCode::UsernameRequired, "Username is required".to_string())
             }
+            AppError::InvalidUsername(reason) => {
+                field = Some("username".to_string());
+                (ErrorCode::InvalidUsername, format!("Invalid username: {}", reason))
+            }
+            AppError::UsernameTaken(username) => {
+                (ErrorCode::UsernameTaken, format!("Username '{}' is already taken", username))
+            }
             AppError::RoomNotFound(room_code) => {
                 (ErrorCode::RoomNotFound, format!("Room '{}' not found", room_code))
             }
             AppError::RoomFull => {
                 (ErrorCode::RoomFull, "Room is full".to_string())
             }
+            AppError::RoomCodeUnavailable { .. } => {
+                (ErrorCode::RoomCodeUnavailable, "No free room code, try again later".to_string())
+            }
+            AppError::WrongPassword => {
+                field = Some("password".to_string());
+                (ErrorCode::WrongPassword, "Wrong room password".to_string())
+            }
+            AppError::InviteInvalid => {
+                field = Some("invite".to_string());
+                (ErrorCode::InviteInvalid, "Invite is invalid or expired".to_string())
+            }
+            AppError::NotRoomHost => {
+                (ErrorCode::NotRoomHost, "Only the room host can do that".to_string())
+            }
+            AppError::InvalidCapacity { capacity, max } => {
+                let message = format!(
+                    "Room capacity {} is out of range ({}-{})",
+                    capacity,
+                    room::DEFAULT_CAPACITY,
+                    max
+                );
+                (ErrorCode::InvalidCapacity, message)
+            }
             AppError::NotInRoom => {
                 (ErrorCode::NotInRoom, "You are not in a room".to_string())
             }
             AppError::AlreadyInRoom => {
                 (ErrorCode::AlreadyInRoom, "You are already in a room".to_string())
             }
+            AppError::AlreadySearching => {
+                (ErrorCode::AlreadySearching, "You are already looking for a partner".to_string())
+            }
+            AppError::NotSearching => {
+                (ErrorCode::NotSearching, "You are not looking for a partner".to_string())
+            }
+            AppError::ResumeFailed => {
+                (ErrorCode::ResumeFailed, "Session cannot be resumed".to_string())
+            }
+            AppError::MessageNotFound(message_id) => {
+                field = Some("message_id".to_string());
+                (ErrorCode::MessageNotFound, format!("Message {} not found", message_id))
+            }
+            AppError::ReplyNotFound(message_id) => {
+                field = Some("reply_to".to_string());
+                (ErrorCode::MessageNotFound, format!("Message {} not found", message_id))
+            }
+            AppError::NotMessageAuthor => {
+                field = Some("message_id".to_string());
+                (ErrorCode::NotMessageAuthor, "You can only change your own messages".to_string())
+            }
+            AppError::InvalidEmoji(reason) => {
+                field = Some("emoji".to_string());
+                (ErrorCode::InvalidEmoji, format!("Invalid emoji: {}", reason))
+            }
+            AppError::TooManyReactions { max } => {
+                let message = format!("Message already has {} different reactions", max);
+                (ErrorCode::TooManyReactions { max: *max }, message)
+            }
+            AppError::RateLimited { retry_after_ms } => {
+                let message = format!("Too many messages, retry in {} ms", retry_after_ms);
+                (ErrorCode::RateLimited { retry_after_ms: *retry_after_ms }, message)
+            }
+            AppError::MessageTooLarge {
+                max_chars,
+                max_bytes,
+            } => {
+                field = Some("content".to_string());
+                let message = format!(
+                    "Message is too large (limit {} characters, {} bytes)",
+                    max_chars, max_bytes
+                );
+                let code = ErrorCode::MessageTooLarge {
+                    max_chars: *max_chars,
+                    max_bytes: *max_bytes,
+                };
+                (code, message)
+            }
             AppError::Json(e) => {
                 (ErrorCode::InvalidMessage, format!("Invalid message format: {}", e))
             }
+            AppError::InvalidMessage {
+                field: bad_field,
+                reason,
+            } => {
+                field = bad_field.clone();
+                (ErrorCode::InvalidMessage, format!("Invalid message: {}", reason))
+            }
             // Fatal errors are not typically converted (connection closes)
             _ => {
                 (ErrorCode::InvalidMessage, "Internal error".to_string())
             }
         };
-        ServerMessage::Error { code, message }
+        ServerMessage::Error {
+            code,
+            message,
+            field,
+        }
     }
 }
 
@@ -125,23 +612,204 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_room_capacity_optional() {
+        let msg: ClientMessage = serde_json::from_str(r#"{"type": "create_room"}"#).unwrap();
+        assert!(matches!(msg, ClientMessage::CreateRoom { capacity: None, .. }));
+
+        let json = r#"{"type": "create_room", "capacity": 5}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(msg, ClientMessage::CreateRoom { capacity: Some(5), .. }));
+    }
+
+    #[test]
+    fn test_room_joined_omits_members_for_one_to_one() {
+        let msg = ServerMessage::RoomJoined {
+            room_code: "ABC123".to_string(),
+            partner: Some("Alice".to_string()),
+            members: Vec::new(),
+            history: Vec::new(),
+            unread: 0,
+        };
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(!json.contains("members"));
+        assert!(!json.contains("history"));
+    }
+
+    #[test]
+    fn test_fetch_history_deserialize() {
+        let json = r#"{"type": "fetch_history", "before": 42, "limit": 10}"#;
+        match serde_json::from_str::<ClientMessage>(json).unwrap() {
+            ClientMessage::FetchHistory { before, limit } => {
+                assert_eq!(before, Some(MessageId(42)));
+                assert_eq!(limit, Some(10));
+            }
+            _ => panic!("Wrong variant"),
+        }
+
+        let json = r#"{"type": "fetch_history"}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::FetchHistory {
+                before: None,
+                limit: None
+            }
+        ));
+    }
+
+    #[test]
+    fn test_chat_ack_echoes_client_msg_id() {
+        let json = r#"{"type": "chat", "content": "Hi", "client_msg_id": "m-1"}"#;
+        let msg: ClientMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::Chat { client_msg_id: Some(id), .. } if id == "m-1"
+        ));
+
+        let ack = ServerMessage::ChatAck {
+            client_msg_id: None,
+            message_id: MessageId(7),
+            server_ts: 1000,
+        };
+        let json = serde_json::to_value(&ack).unwrap();
+        assert_eq!(json["type"], "chat_ack");
+        assert_eq!(json["message_id"], 7);
+        assert!(json.get("client_msg_id").is_none());
+    }
+
     #[test]
     fn test_server_message_serialize() {
         let msg = ServerMessage::Connected {
             client_id: "test-id".to_string(),
+            resume_token: "secret".to_string(),
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"type\":\"connected\""));
         assert!(json.contains("\"client_id\":\"test-id\""));
     }
 
+    #[test]
+    fn test_error_code_as_str_matches_wire_format() {
+        for code in [
+            ErrorCode::UsernameRequired,
+            ErrorCode::InvalidUsername,
+            ErrorCode::UsernameTaken,
+            ErrorCode::RoomNotFound,
+            ErrorCode::RoomFull,
+            ErrorCode::RoomCodeUnavailable,
+            ErrorCode::WrongPassword,
+            ErrorCode::InviteInvalid,
+            ErrorCode::NotRoomHost,
+            ErrorCode::InvalidCapacity,
+            ErrorCode::NotInRoom,
+            ErrorCode::AlreadyInRoom,
+            ErrorCode::AlreadySearching,
+            ErrorCode::NotSearching,
+            ErrorCode::InvalidMessage,
+            ErrorCode::ResumeFailed,
+            ErrorCode::MessageNotFound,
+            ErrorCode::NotMessageAuthor,
+            ErrorCode::InvalidEmoji,
+            ErrorCode::TooManyReactions { max: 20 },
+            ErrorCode::RateLimited { retry_after_ms: 1 },
+            ErrorCode::MessageTooLarge {
+                max_chars: 1,
+                max_bytes: 4,
+            },
+        ] {
+            assert_eq!(serde_json::to_value(&code).unwrap()["code"], code.as_str());
+        }
+    }
+
+    #[test]
+    fn test_rate_limited_serialize() {
+        let msg: ServerMessage = AppError::RateLimited {
+            retry_after_ms: 1500,
+        }
+        .into();
+        let json = serde_json::to_value(&msg).unwrap();
+        assert_eq!(json["type"], "error");
+        assert_eq!(json["code"], "rate_limited");
+        assert_eq!(json["retry_after_ms"], 1500);
+    }
+
     #[test]
     fn test_error_code_serialize() {
         let msg = ServerMessage::Error {
             code: ErrorCode::RoomNotFound,
             message: "Test".to_string(),
+            field: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("\"code\":\"room_not_found\""));
+        assert!(!json.contains("field"));
+    }
+
+    fn parse_error_field(text: &str) -> Option<String> {
+        match ClientMessage::parse(text) {
+            Err(AppError::InvalidMessage { field, .. }) => field,
+            other => panic!("Expected InvalidMessage, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_message() {
+        let msg = ClientMessage::parse(r#"{"type": "chat", "content": "Hi"}"#).unwrap();
+        assert!(matches!(
+            msg,
+            ClientMessage::Chat { content, client_msg_id: None, reply_to: None } if content == "Hi"
+        ));
+    }
+
+    #[test]
+    fn test_parse_reports_bad_tag() {
+        assert_eq!(parse_error_field(r#"{"type": "dance"}"#).as_deref(), Some("type"));
+        assert_eq!(parse_error_field(r#"{"content": "Hi"}"#).as_deref(), Some("type"));
+        assert_eq!(parse_error_field(r#"{"type": 7}"#).as_deref(), Some("type"));
+    }
+
+    #[test]
+    fn test_parse_reports_missing_field() {
+        assert_eq!(parse_error_field(r#"{"type": "chat"}"#).as_deref(), Some("content"));
+    }
+
+    #[test]
+    fn test_parse_reports_wrong_type() {
+        let field = parse_error_field(r#"{"type": "join_room", "room_code": 42}"#);
+        assert_eq!(field.as_deref(), Some("room_code"));
+
+        let field = parse_error_field(r#"{"type": "create_room", "capacity": "lots"}"#);
+        assert_eq!(field.as_deref(), Some("capacity"));
+    }
+
+    #[test]
+    fn test_parse_skips_probe_for_many_fields() {
+        let mut object = Map::new();
+        object.insert("type".to_string(), "chat".into());
+        object.insert("content".to_string(), 5.into());
+        for i in 0..5000 {
+            object.insert(format!("junk{}", i), i.into());
+        }
+        let text = Value::Object(object).to_string();
+        assert_eq!(parse_error_field(&text), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_object() {
+        assert_eq!(parse_error_field("[1, 2, 3]"), None);
+        assert!(matches!(ClientMessage::parse("not json"), Err(AppError::Json(_))));
+    }
+
+    #[test]
+    fn test_invalid_message_error_includes_field() {
+        let msg: ServerMessage = AppError::InvalidMessage {
+            field: Some("content".to_string()),
+            reason: "missing field `content`".to_string(),
+        }
+        .into();
+        let json = serde_json::to_string(&msg).unwrap();
+        assert!(json.contains("\"code\":\"invalid_message\""));
+        assert!(json.contains("\"field\":\"content\""));
     }
 }
@@ -0,0 +1,195 @@
+//! Prometheus metrics
+//!
+//! Metrics live in a process-wide registry (`metrics()`), updated by the
+//! ChatServer actor and connection handlers, and served in the Prometheus
+//! text format by a small HTTP endpoint on its own port (`serve`).
+
+use std::sync::LazyLock;
+
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, error};
+
+/// Largest HTTP request head the metrics endpoint reads
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// All server metrics
+pub struct Metrics {
+    registry: Registry,
+    /// Open connections known to the actor (including held sessions)
+    pub connected_clients: IntGauge,
+    /// Clients that have set a username
+    pub named_clients: IntGauge,
+    /// Rooms that currently exist
+    pub active_rooms: IntGauge,
+    /// Rooms with every seat taken
+    pub full_rooms: IntGauge,
+    /// Clients waiting for a random partner
+    pub searching_clients: IntGauge,
+    /// Commands processed by the actor, by `ServerCommand` variant
+    pub commands: IntCounterVec,
+    /// Error messages sent to clients, by `ErrorCode`
+    pub errors: IntCounterVec,
+    /// Chat content bytes delivered to recipients
+    pub chat_bytes_relayed: IntCounter,
+    /// Time the actor spends processing a command, by variant
+    pub command_duration: HistogramVec,
+    /// Time between a room's creation and its deletion
+    pub room_lifetime: Histogram,
+    /// Rooms closed by the server, by `ExpiryReason`
+    pub rooms_expired: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let metrics = Self {
+            registry: Registry::new(),
+            connected_clients: IntGauge::new(
+                "chat_connected_clients",
+                "Clients known to the server, including held sessions",
+            )?,
+            named_clients: IntGauge::new("chat_named_clients", "Clients with a username")?,
+            active_rooms: IntGauge::new("chat_active_rooms", "Rooms that currently exist")?,
+            full_rooms: IntGauge::new("chat_full_rooms", "Rooms with every seat taken")?,
+            searching_clients: IntGauge::new(
+                "chat_searching_clients",
+                "Clients waiting for a random partner",
+            )?,
+            commands: IntCounterVec::new(
+                Opts::new("chat_commands_total", "Commands processed by the actor"),
+                &["command"],
+            )?,
+            errors: IntCounterVec::new(
+                Opts::new("chat_errors_total", "Error messages sent to clients"),
+                &["code"],
+            )?,
+            chat_bytes_relayed: IntCounter::new(
+                "chat_relayed_bytes_total",
+                "Chat content bytes delivered to recipients",
+            )?,
+            command_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "chat_command_duration_seconds",
+                    "Time the actor spends processing a command",
+                )
+                .buckets(prometheus::exponential_buckets(0.000_01, 4.0, 10)?),
+                &["command"],
+            )?,
+            room_lifetime: Histogram::with_opts(
+                HistogramOpts::new(
+                    "chat_room_lifetime_seconds",
+                    "Time between a room's creation and its deletion",
+                )
+                .buckets(prometheus::exponential_buckets(1.0, 4.0, 10)?),
+            )?,
+            rooms_expired: IntCounterVec::new(
+                Opts::new("chat_rooms_expired_total", "Rooms closed by the server"),
+                &["reason"],
+            )?,
+        };
+
+        let registry = &metrics.registry;
+        registry.register(Box::new(metrics.connected_clients.clone()))?;
+        registry.register(Box::new(metrics.named_clients.clone()))?;
+        registry.register(Box::new(metrics.active_rooms.clone()))?;
+        registry.register(Box::new(metrics.full_rooms.clone()))?;
+        registry.register(Box::new(metrics.searching_clients.clone()))?;
+        registry.register(Box::new(metrics.commands.clone()))?;
+        registry.register(Box::new(metrics.errors.clone()))?;
+        registry.register(Box::new(metrics.chat_bytes_relayed.clone()))?;
+        registry.register(Box::new(metrics.command_duration.clone()))?;
+        registry.register(Box::new(metrics.room_lifetime.clone()))?;
+        registry.register(Box::new(metrics.rooms_expired.clone()))?;
+        Ok(metrics)
+    }
+
+    /// Render every metric in the Prometheus text format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("metric definitions are valid"));
+
+/// Get the process-wide metrics
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Serve `GET /metrics` on the given listener until it fails
+pub async fn serve(listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = handle_request(stream).await {
+                        debug!("Metrics request failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                error!("Failed to accept metrics connection: {}", e);
+            }
+        }
+    }
+}
+
+/// Helper: Answer a single HTTP request and close the connection
+async fn handle_request(mut stream: TcpStream) -> std::io::Result<()> {
+    // Read the request head; the body (if any) is ignored
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request_line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+    let mut parts = request_line.split(|&b| b == b' ');
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            metrics().render(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not Found\n".to_string()),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_metrics() {
+        metrics().commands.with_label_values(&["connect"]).inc();
+        metrics().room_lifetime.observe(2.0);
+
+        let text = metrics().render();
+        assert!(text.contains("chat_connected_clients"));
+        assert!(text.contains("chat_commands_total{command=\"connect\"}"));
+        assert!(text.contains("chat_room_lifetime_seconds_count"));
+    }
+}
@@ -0,0 +1,328 @@
+//! Per-client outbound message queue
+//!
+//! A bounded queue between the ChatServer actor and a connection's write task.
+//! Pushing never waits: when the queue is full, the configured
+//! `DeliveryPolicy` decides what to give up, so a client that stops reading
+//! its socket cannot stall the actor (and with it every other room).
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use crate::error::SendError;
+use crate::message::ServerMessage;
+
+/// What to do when a client's queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryPolicy {
+    /// Drop the oldest queued message to make room
+    #[default]
+    DropOldest,
+    /// Drop typing indicators first, then fall back to the oldest message
+    DropTypingFirst,
+    /// Give up on the client and close its connection
+    DisconnectSlowConsumer,
+}
+
+/// Queue state guarded by the mutex
+#[derive(Debug, Default)]
+struct State {
+    queue: VecDeque<ServerMessage>,
+    closed: bool,
+}
+
+/// State shared between senders and the receiver
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    capacity: usize,
+    policy: DeliveryPolicy,
+    /// Wakes the receiver when a message arrives or the queue closes
+    notify: Notify,
+    /// Wakes the receiver when the queue is aborted (slow consumer)
+    abort_notify: Notify,
+    aborted: AtomicBool,
+    senders: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+/// Create a new outbox with the given capacity and delivery policy
+pub fn channel(capacity: usize, policy: DeliveryPolicy) -> (OutboxSender, OutboxReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State::default()),
+        capacity: capacity.max(1),
+        policy,
+        notify: Notify::new(),
+        abort_notify: Notify::new(),
+        aborted: AtomicBool::new(false),
+        senders: AtomicUsize::new(1),
+        dropped: AtomicU64::new(0),
+    });
+    (
+        OutboxSender {
+            shared: shared.clone(),
+        },
+        OutboxReceiver { shared },
+    )
+}
+
+/// Sending half of an outbox (held by the ChatServer actor)
+#[derive(Debug)]
+pub struct OutboxSender {
+    shared: Arc<Shared>,
+}
+
+impl OutboxSender {
+    /// Queue a message without waiting
+    ///
+    /// Returns `SendError::ChannelClosed` if the receiver is gone and
+    /// `SendError::SlowConsumer` if this push overflowed a queue whose
+    /// policy is `DisconnectSlowConsumer`.
+    pub fn push(&self, msg: ServerMessage) -> Result<(), SendError> {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+
+        if state.closed {
+            return Err(SendError::ChannelClosed);
+        }
+
+        if state.queue.len() >= shared.capacity {
+            match shared.policy {
+                DeliveryPolicy::DropOldest => {
+                    state.queue.pop_front();
+                }
+                DeliveryPolicy::DropTypingFirst => {
+                    if msg.is_ephemeral() {
+                        shared.dropped.fetch_add(1, Ordering::Relaxed);
+                        return Ok(());
+                    }
+                    match state.queue.iter().position(ServerMessage::is_ephemeral) {
+                        Some(index) => {
+                            state.queue.remove(index);
+                        }
+                        None => {
+                            state.queue.pop_front();
+                        }
+                    }
+                }
+                DeliveryPolicy::DisconnectSlowConsumer => {
+                    let discarded = state.queue.len() as u64 + 1;
+                    state.queue.clear();
+                    state.closed = true;
+                    drop(state);
+
+                    shared.dropped.fetch_add(discarded, Ordering::Relaxed);
+                    shared.aborted.store(true, Ordering::Release);
+                    shared.notify.notify_one();
+                    shared.abort_notify.notify_one();
+                    return Err(SendError::SlowConsumer);
+                }
+            }
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        state.queue.push_back(msg);
+        drop(state);
+        shared.notify.notify_one();
+        Ok(())
+    }
+
+    /// Close the queue: no further pushes, queued messages are still delivered
+    pub fn close(&self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.notify.notify_one();
+    }
+
+    /// Check if the queue no longer accepts messages
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+
+    /// Number of messages dropped by the delivery policy so far
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Most messages the queue holds before the delivery policy applies
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Number of messages currently waiting to be written
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().queue.len()
+    }
+
+    /// Check if no messages are waiting to be written
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Clone for OutboxSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for OutboxSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.close();
+        }
+    }
+}
+
+/// Receiving half of an outbox (held by the connection's write task)
+#[derive(Debug)]
+pub struct OutboxReceiver {
+    shared: Arc<Shared>,
+}
+
+impl OutboxReceiver {
+    /// Wait for the next message
+    ///
+    /// Returns None once the queue is closed and drained, or aborted.
+    pub async fn recv(&mut self) -> Option<ServerMessage> {
+        loop {
+            let notified = self.shared.notify.notified();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(msg) = state.queue.pop_front() {
+                    return Some(msg);
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Take the next message if one is queued, without waiting
+    pub fn try_recv(&mut self) -> Option<ServerMessage> {
+        self.shared.state.lock().unwrap().queue.pop_front()
+    }
+
+    /// Wait until the queue is aborted by the `DisconnectSlowConsumer` policy
+    ///
+    /// Lets the write task give up on a socket write that is stuck
+    /// because the peer stopped reading.
+    pub async fn aborted(&self) {
+        loop {
+            let notified = self.shared.abort_notify.notified();
+            if self.is_aborted() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Check if the queue was aborted by the `DisconnectSlowConsumer` policy
+    pub fn is_aborted(&self) -> bool {
+        self.shared.aborted.load(Ordering::Acquire)
+    }
+}
+
+impl Drop for OutboxReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.queue.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MessageId;
+
+    fn chat(content: &str) -> ServerMessage {
+        ServerMessage::Chat {
+            message_id: MessageId::default(),
+            from: "Alice".to_string(),
+            content: content.to_string(),
+            server_ts: 0,
+            reply_to: None,
+        }
+    }
+
+    fn content_of(msg: ServerMessage) -> String {
+        match msg {
+            ServerMessage::Chat { content, .. } => content,
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let (tx, mut rx) = channel(2, DeliveryPolicy::DropOldest);
+
+        for content in ["1", "2", "3"] {
+            tx.push(chat(content)).unwrap();
+        }
+
+        assert_eq!(tx.dropped_count(), 1);
+        assert_eq!(content_of(rx.recv().await.unwrap()), "2");
+        assert_eq!(content_of(rx.recv().await.unwrap()), "3");
+    }
+
+    #[tokio::test]
+    async fn test_drop_typing_first() {
+        let (tx, mut rx) = channel(2, DeliveryPolicy::DropTypingFirst);
+
+        tx.push(chat("1")).unwrap();
+        tx.push(ServerMessage::PartnerTyping).unwrap();
+
+        // Incoming typing indicator is dropped when full
+        tx.push(ServerMessage::PartnerStopTyping).unwrap();
+        assert_eq!(tx.dropped_count(), 1);
+
+        // Incoming chat evicts the queued typing indicator, not the chat
+        tx.push(chat("2")).unwrap();
+        assert_eq!(tx.dropped_count(), 2);
+
+        assert_eq!(content_of(rx.recv().await.unwrap()), "1");
+        assert_eq!(content_of(rx.recv().await.unwrap()), "2");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_slow_consumer() {
+        let (tx, mut rx) = channel(1, DeliveryPolicy::DisconnectSlowConsumer);
+
+        tx.push(chat("1")).unwrap();
+        assert!(matches!(tx.push(chat("2")), Err(SendError::SlowConsumer)));
+        assert!(matches!(tx.push(chat("3")), Err(SendError::ChannelClosed)));
+
+        assert!(rx.is_aborted());
+        rx.aborted().await;
+        assert!(rx.recv().await.is_none());
+        assert_eq!(tx.dropped_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_close_drains_queue() {
+        let (tx, mut rx) = channel(4, DeliveryPolicy::DropOldest);
+
+        tx.push(chat("1")).unwrap();
+        drop(tx);
+
+        assert_eq!(content_of(rx.recv().await.unwrap()), "1");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_push_after_receiver_dropped() {
+        let (tx, rx) = channel(4, DeliveryPolicy::DropOldest);
+        drop(rx);
+
+        assert!(matches!(tx.push(chat("1")), Err(SendError::ChannelClosed)));
+    }
+}
@@ -0,0 +1,383 @@
+//! Token-bucket rate limiting for client messages
+//!
+//! Limits are set per `ClientMessage` type and enforced in the connection
+//! handler, before a command reaches the ChatServer actor. Each message
+//! must fit both the connection's own bucket and the bucket of its
+//! session, which every connection resuming that session shares (so a
+//! reconnect starts with what is left). A connection that keeps
+//! hitting the limit, or sending messages that are rejected outright, is
+//! disconnected.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::types::ClientId;
+
+/// Default number of rejected messages tolerated per `violation_window`
+pub const DEFAULT_MAX_VIOLATIONS: u32 = 10;
+
+/// Default window over which violations are counted
+pub const DEFAULT_VIOLATION_WINDOW: Duration = Duration::from_secs(10);
+
+/// Per-session entries kept before idle ones are pruned
+const MAX_IDLE_SESSIONS: usize = 1024;
+
+/// Limit for one message type
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Messages that can be sent back-to-back
+    pub burst: u32,
+    /// Sustained rate once the burst is used up
+    pub per_second: f64,
+}
+
+impl RateLimit {
+    /// Create a limit of `burst` messages refilled at `per_second`
+    pub const fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// Rate limiting settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Enforce limits at all
+    pub enabled: bool,
+    /// Rejected messages tolerated per window before disconnecting
+    pub max_violations: u32,
+    /// Window over which violations are counted
+    #[serde(with = "humantime_serde")]
+    pub violation_window: Duration,
+    /// Limits by message type (`chat`, `typing`, ...); unlisted types are unlimited
+    pub limits: BTreeMap<String, RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let limits = [
+            ("set_username", RateLimit::new(5, 1.0)),
+            ("create_room", RateLimit::new(5, 0.5)),
+            ("join_room", RateLimit::new(10, 1.0)),
+            ("create_invite", RateLimit::new(5, 0.5)),
+            ("chat", RateLimit::new(20, 10.0)),
+            ("fetch_history", RateLimit::new(10, 2.0)),
+            ("mark_read", RateLimit::new(20, 10.0)),
+            ("edit_message", RateLimit::new(10, 2.0)),
+            ("delete_message", RateLimit::new(10, 2.0)),
+            ("react", RateLimit::new(20, 5.0)),
+            ("unreact", RateLimit::new(20, 5.0)),
+            ("typing", RateLimit::new(20, 10.0)),
+            ("stop_typing", RateLimit::new(20, 10.0)),
+            ("find_partner", RateLimit::new(5, 0.5)),
+            ("cancel_find", RateLimit::new(5, 0.5)),
+            ("resume", RateLimit::new(5, 1.0)),
+        ];
+        Self {
+            enabled: true,
+            max_violations: DEFAULT_MAX_VIOLATIONS,
+            violation_window: DEFAULT_VIOLATION_WINDOW,
+            limits: limits
+                .into_iter()
+                .map(|(name, limit)| (name.to_string(), limit))
+                .collect(),
+        }
+    }
+}
+
+/// A token bucket: `capacity` tokens, refilled continuously
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full bucket
+    pub fn new(capacity: f64, refill_per_sec: f64, now: Instant) -> Self {
+        Self {
+            capacity,
+            refill_per_sec,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    /// Create a full bucket for a message limit
+    pub fn for_limit(limit: &RateLimit, now: Instant) -> Self {
+        Self::new(limit.burst as f64, limit.per_second, now)
+    }
+
+    /// Add the tokens earned since the last update
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Time until a token is available (zero if one is available now)
+    pub fn wait_time(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec)
+        }
+    }
+
+    /// Take a token if one is available
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        if self.wait_time(now).is_zero() {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check if the bucket has refilled completely (it can be forgotten)
+    pub fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+/// Buckets for every limited message type
+#[derive(Debug, Default)]
+struct Buckets(HashMap<&'static str, TokenBucket>);
+
+impl Buckets {
+    fn bucket(
+        &mut self,
+        config: &RateLimitConfig,
+        msg_type: &'static str,
+        now: Instant,
+    ) -> Option<&mut TokenBucket> {
+        let limit = config.limits.get(msg_type)?;
+        Some(
+            self.0
+                .entry(msg_type)
+                .or_insert_with(|| TokenBucket::for_limit(limit, now)),
+        )
+    }
+}
+
+/// Outcome of checking a message against the limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Forward the message
+    Allow,
+    /// Drop the message; the client may retry after this long
+    Limited(Duration),
+    /// Too many violations; close the connection
+    Disconnect,
+}
+
+/// Rate limiter shared by every connection of a server
+///
+/// Holds the configuration and the per-session buckets.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    sessions: Mutex<HashMap<ClientId, Buckets>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter with the given settings
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Create the limiter state for a new connection
+    pub fn connection(self: &Arc<Self>) -> ConnectionLimiter {
+        let max_violations = self.config.max_violations.max(1) as f64;
+        let window = self.config.violation_window.as_secs_f64();
+        ConnectionLimiter {
+            shared: self.clone(),
+            buckets: Buckets::default(),
+            violations: TokenBucket::new(max_violations, max_violations / window, Instant::now()),
+        }
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimitConfig::default())
+    }
+}
+
+/// Rate limiter state of a single connection
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    shared: Arc<RateLimiter>,
+    buckets: Buckets,
+    /// Violation allowance; running out of it means disconnect
+    violations: TokenBucket,
+}
+
+impl ConnectionLimiter {
+    /// Check a message of `msg_type` sent in `session`
+    ///
+    /// `session` is the client ID the connection speaks for, which a
+    /// resume carries over to the new connection. The message is charged
+    /// against the connection's bucket and the session's bucket only if
+    /// both have a token.
+    pub fn check(&mut self, msg_type: &'static str, session: ClientId) -> Verdict {
+        let config = &self.shared.config;
+        if !config.enabled {
+            return Verdict::Allow;
+        }
+        let now = Instant::now();
+
+        let Some(own) = self.buckets.bucket(config, msg_type, now) else {
+            return Verdict::Allow;
+        };
+        let mut wait = own.wait_time(now);
+
+        let mut sessions = self.shared.sessions.lock().unwrap();
+        if !sessions.contains_key(&session) && sessions.len() >= MAX_IDLE_SESSIONS {
+            sessions.retain(|_, buckets| !buckets.0.values_mut().all(|b| b.is_full(now)));
+        }
+        let mut shared = sessions
+            .entry(session)
+            .or_default()
+            .bucket(config, msg_type, now);
+        if let Some(bucket) = shared.as_deref_mut() {
+            wait = wait.max(bucket.wait_time(now));
+        }
+
+        if wait.is_zero() {
+            own.try_take(now);
+            if let Some(bucket) = shared {
+                bucket.try_take(now);
+            }
+            return Verdict::Allow;
+        }
+        drop(sessions);
+
+        if self.violations.try_take(now) {
+            Verdict::Limited(wait)
+        } else {
+            Verdict::Disconnect
+        }
+    }
+
+    /// Count a rejected message (one that could not be parsed, say)
+    ///
+    /// Returns false once the violation allowance is used up and the
+    /// connection should be closed.
+    pub fn charge_violation(&mut self) -> bool {
+        !self.shared.config.enabled || self.violations.try_take(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::advance;
+
+    fn limiter(limit: RateLimit, max_violations: u32) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            max_violations,
+            violation_window: Duration::from_secs(10),
+            limits: BTreeMap::from([("chat".to_string(), limit)]),
+        }))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_bucket_refills_over_time() {
+        let limiter = limiter(RateLimit::new(2, 1.0), 10);
+        let mut conn = limiter.connection();
+        let session = ClientId::new();
+
+        assert_eq!(conn.check("chat", session), Verdict::Allow);
+        assert_eq!(conn.check("chat", session), Verdict::Allow);
+        assert_eq!(
+            conn.check("chat", session),
+            Verdict::Limited(Duration::from_secs(1))
+        );
+
+        advance(Duration::from_millis(500)).await;
+        assert_eq!(
+            conn.check("chat", session),
+            Verdict::Limited(Duration::from_millis(500))
+        );
+
+        advance(Duration::from_millis(500)).await;
+        assert_eq!(conn.check("chat", session), Verdict::Allow);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unlisted_types_are_unlimited() {
+        let limiter = limiter(RateLimit::new(1, 1.0), 10);
+        let mut conn = limiter.connection();
+        let session = ClientId::new();
+
+        for _ in 0..100 {
+            assert_eq!(conn.check("typing", session), Verdict::Allow);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_session_bucket_is_shared_across_connections() {
+        let limiter = limiter(RateLimit::new(2, 1.0), 10);
+        let (alice, bob) = (ClientId::new(), ClientId::new());
+        let mut first = limiter.connection();
+        let mut second = limiter.connection();
+
+        assert_eq!(first.check("chat", alice), Verdict::Allow);
+        assert_eq!(second.check("chat", alice), Verdict::Allow);
+        assert!(matches!(second.check("chat", alice), Verdict::Limited(_)));
+
+        // Another session is unaffected
+        assert_eq!(second.check("chat", bob), Verdict::Allow);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_repeated_violations_disconnect() {
+        let limiter = limiter(RateLimit::new(1, 0.1), 2);
+        let mut conn = limiter.connection();
+        let session = ClientId::new();
+
+        assert_eq!(conn.check("chat", session), Verdict::Allow);
+        assert!(matches!(conn.check("chat", session), Verdict::Limited(_)));
+        assert!(matches!(conn.check("chat", session), Verdict::Limited(_)));
+        assert_eq!(conn.check("chat", session), Verdict::Disconnect);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_violations_are_forgiven_over_time() {
+        let limiter = limiter(RateLimit::new(1, 0.1), 2);
+        let mut conn = limiter.connection();
+        let session = ClientId::new();
+
+        assert_eq!(conn.check("chat", session), Verdict::Allow);
+        assert!(matches!(conn.check("chat", session), Verdict::Limited(_)));
+        assert!(matches!(conn.check("chat", session), Verdict::Limited(_)));
+
+        // The violation allowance refills over the window
+        advance(Duration::from_secs(5)).await;
+        assert!(matches!(conn.check("chat", session), Verdict::Limited(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rejected_messages_count_as_violations() {
+        let limiter = limiter(RateLimit::new(1, 0.1), 2);
+        let mut conn = limiter.connection();
+
+        assert!(conn.charge_violation());
+        assert!(conn.charge_violation());
+        assert!(!conn.charge_violation());
+    }
+}
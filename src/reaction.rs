@@ -0,0 +1,270 @@
+//! Emoji reactions on chat messages
+//!
+//! Each room keeps the reactions to its recent messages in memory; they
+//! are not part of the stored history. A reaction is one emoji (possibly
+//! a multi-codepoint sequence such as a flag or a ZWJ family) and each
+//! client can add any given emoji to a message once.
+
+use unicode_properties::emoji::{
+    is_emoji_presentation_selector, is_regional_indicator, is_tag_character, is_zwj, EmojiStatus,
+};
+use unicode_properties::UnicodeEmoji;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::AppError;
+use crate::types::ClientId;
+
+/// Most distinct emoji a single message can collect
+pub const MAX_REACTIONS_PER_MESSAGE: usize = 20;
+
+/// Longest accepted emoji sequence, in characters
+pub const MAX_EMOJI_CHARS: usize = 16;
+
+/// Combining keycap, as in "1️⃣"
+const KEYCAP: char = '\u{20E3}';
+
+/// Ends a tag sequence, as in "🏴󠁧󠁢󠁳󠁣󠁴󠁿"
+const CANCEL_TAG: char = '\u{E007F}';
+
+/// Check that `emoji` is a single emoji
+///
+/// The text must be one grapheme cluster forming a flag, a keycap, a tag
+/// sequence or a ZWJ sequence of one or more emoji. Each emoji in a ZWJ
+/// sequence is shown as emoji by default, or made so by VS16 or a skin
+/// tone modifier; "©" alone is text, "©️" is an emoji.
+pub fn check_emoji(emoji: &str) -> Result<(), AppError> {
+    let length = emoji.chars().count();
+    if length == 0 || length > MAX_EMOJI_CHARS {
+        return Err(AppError::InvalidEmoji(format!(
+            "must be 1-{} characters long",
+            MAX_EMOJI_CHARS
+        )));
+    }
+    if let Some(c) = emoji
+        .chars()
+        .find(|c| !c.is_emoji_char_or_emoji_component())
+    {
+        return Err(AppError::InvalidEmoji(format!(
+            "character U+{:04X} is not allowed",
+            c as u32
+        )));
+    }
+    if emoji.graphemes(true).count() != 1 {
+        return Err(AppError::InvalidEmoji("must be a single emoji".to_string()));
+    }
+
+    let chars: Vec<char> = emoji.chars().collect();
+    if is_flag(&chars) || is_keycap(&chars) || is_tag_sequence(&chars) || is_zwj_sequence(&chars) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidEmoji("not an emoji".to_string()))
+    }
+}
+
+/// Two regional indicators, as in "🇰🇷"
+fn is_flag(chars: &[char]) -> bool {
+    matches!(chars, [a, b] if is_regional_indicator(*a) && is_regional_indicator(*b))
+}
+
+/// A digit, `#` or `*` with an optional VS16 and a keycap
+fn is_keycap(chars: &[char]) -> bool {
+    let base = match chars {
+        [base, KEYCAP] => base,
+        [base, vs, KEYCAP] if is_emoji_presentation_selector(*vs) => base,
+        _ => return false,
+    };
+    base.is_ascii_digit() || matches!(base, '#' | '*')
+}
+
+/// An emoji followed by tags and a cancel tag, as in subdivision flags
+fn is_tag_sequence(chars: &[char]) -> bool {
+    match chars {
+        [base, tags @ .., CANCEL_TAG] if !tags.is_empty() => {
+            is_emoji_base(*base) && tags.iter().all(|&c| is_tag_character(c) && c != CANCEL_TAG)
+        }
+        _ => false,
+    }
+}
+
+/// Emoji joined by ZWJ; a lone emoji is a sequence of one
+fn is_zwj_sequence(chars: &[char]) -> bool {
+    chars.split(|&c| is_zwj(c)).all(|element| match element {
+        [base] => is_emoji_base(*base) && has_emoji_presentation(*base),
+        [base, vs] if is_emoji_presentation_selector(*vs) => is_emoji_base(*base),
+        [base, modifier] if is_emoji_modifier(*modifier) => is_emoji_modifier_base(*base),
+        _ => false,
+    })
+}
+
+/// An emoji that can stand on its own or start a sequence
+///
+/// Digits, `#` and `*` only count inside a keycap, and regional
+/// indicators only in pairs.
+fn is_emoji_base(c: char) -> bool {
+    c.is_emoji_char() && !c.is_ascii() && !is_regional_indicator(c)
+}
+
+fn has_emoji_presentation(c: char) -> bool {
+    matches!(
+        c.emoji_status(),
+        EmojiStatus::EmojiPresentation
+            | EmojiStatus::EmojiPresentationAndModifierBase
+            | EmojiStatus::EmojiPresentationAndEmojiComponent
+            | EmojiStatus::EmojiPresentationAndModifierAndEmojiComponent
+    )
+}
+
+fn is_emoji_modifier_base(c: char) -> bool {
+    matches!(
+        c.emoji_status(),
+        EmojiStatus::EmojiModifierBase | EmojiStatus::EmojiPresentationAndModifierBase
+    )
+}
+
+/// A skin tone modifier, as in "👍🏽"
+fn is_emoji_modifier(c: char) -> bool {
+    c.emoji_status() == EmojiStatus::EmojiPresentationAndModifierAndEmojiComponent
+}
+
+/// Reactions to one message, in the order each emoji was first used
+#[derive(Debug, Default)]
+pub struct Reactions {
+    entries: Vec<(String, Vec<ClientId>)>,
+}
+
+impl Reactions {
+    /// Add a client's reaction
+    ///
+    /// Returns the emoji's new count, or None if the client had already
+    /// reacted with it.
+    pub fn add(&mut self, emoji: &str, client_id: ClientId) -> Result<Option<usize>, AppError> {
+        let distinct = self.entries.len();
+        match self.entries.iter_mut().find(|(e, _)| e == emoji) {
+            Some((_, clients)) if clients.contains(&client_id) => Ok(None),
+            Some((_, clients)) => {
+                clients.push(client_id);
+                Ok(Some(clients.len()))
+            }
+            None if distinct >= MAX_REACTIONS_PER_MESSAGE => Err(AppError::TooManyReactions {
+                max: MAX_REACTIONS_PER_MESSAGE,
+            }),
+            None => {
+                self.entries.push((emoji.to_string(), vec![client_id]));
+                Ok(Some(1))
+            }
+        }
+    }
+
+    /// Remove a client's reaction
+    ///
+    /// Returns the emoji's new count, or None if the client hadn't
+    /// reacted with it.
+    pub fn remove(&mut self, emoji: &str, client_id: ClientId) -> Option<usize> {
+        let index = self.entries.iter().position(|(e, _)| e == emoji)?;
+        let clients = &mut self.entries[index].1;
+        let before = clients.len();
+        clients.retain(|&id| id != client_id);
+        let count = clients.len();
+        if count == before {
+            return None;
+        }
+        if count == 0 {
+            self.entries.remove(index);
+        }
+        Some(count)
+    }
+
+    /// Check if nobody has reacted
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_emoji() {
+        for emoji in [
+            "👍",
+            "❤️",
+            "©️",
+            "👍🏽",
+            "🇰🇷",
+            "1️⃣",
+            "#⃣",
+            "👩‍👩‍👧",
+            "👨‍👩‍👧‍👦",
+            "🏳️‍🌈",
+            "🧑🏽‍🚀",
+            "🏴󠁧󠁢󠁳󠁣󠁴󠁿",
+        ] {
+            assert!(check_emoji(emoji).is_ok(), "{} should be accepted", emoji);
+        }
+        for emoji in [
+            "",
+            "a",
+            "1",
+            ":+1:",
+            "👍 ",
+            "\u{FE0F}",
+            "👍👍",
+            "©",
+            "°",
+            "❤",
+            "1\u{FE0F}",
+            "🇰",
+            "👍\u{200B}",
+            "\u{200B}👍",
+            "👍\u{200D}",
+            "\u{200D}👍",
+            "👩\u{200D}\u{200D}👧",
+            "a🏽",
+            "👍👍👍👍👍👍👍👍👍👍👍👍👍👍👍👍👍",
+        ] {
+            assert!(
+                check_emoji(emoji).is_err(),
+                "{:?} should be rejected",
+                emoji
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let (alice, bob) = (ClientId::new(), ClientId::new());
+        let mut reactions = Reactions::default();
+
+        assert_eq!(reactions.add("👍", alice).unwrap(), Some(1));
+        assert_eq!(reactions.add("👍", bob).unwrap(), Some(2));
+        assert_eq!(reactions.add("👍", alice).unwrap(), None);
+
+        assert_eq!(reactions.remove("👍", alice), Some(1));
+        assert_eq!(reactions.remove("👍", alice), None);
+        assert_eq!(reactions.remove("🎉", alice), None);
+        assert_eq!(reactions.remove("👍", bob), Some(0));
+        assert!(reactions.is_empty());
+    }
+
+    #[test]
+    fn test_distinct_emoji_cap() {
+        let alice = ClientId::new();
+        let mut reactions = Reactions::default();
+        let emoji: Vec<String> = ('😀'..)
+            .take(MAX_REACTIONS_PER_MESSAGE + 1)
+            .map(String::from)
+            .collect();
+
+        for e in &emoji[..MAX_REACTIONS_PER_MESSAGE] {
+            reactions.add(e, alice).unwrap();
+        }
+        assert!(matches!(
+            reactions.add(&emoji[MAX_REACTIONS_PER_MESSAGE], alice),
+            Err(AppError::TooManyReactions { .. })
+        ));
+
+        // Existing emoji can still be added to
+        assert_eq!(reactions.add(&emoji[0], ClientId::new()).unwrap(), Some(2));
+    }
+}
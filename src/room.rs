@@ -1,105 +1,340 @@
 //! Room struct definition
 //!
-//! Represents a 1:1 chat room with host and optional guest.
+//! Represents a chat room with a host and up to `capacity - 1` other members.
+//! The default capacity of 2 gives the classic 1:1 room.
 
-use std::time::Instant;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 
-use crate::types::{ClientId, RoomCode};
+use serde::Serialize;
 
-/// 1:1 Chat Room
+use crate::access::RoomAccess;
+use crate::config::RoomConfig;
+use crate::error::AppError;
+use crate::reaction::Reactions;
+use crate::types::{ClientId, MessageId, RoomCode};
+
+/// Default room capacity (1:1 chat)
+pub const DEFAULT_CAPACITY: usize = 2;
+
+/// Maximum room capacity a client may request
+pub const MAX_CAPACITY: usize = 32;
+
+/// Latest messages whose ID, author and deletion a room remembers
+///
+/// Read receipts, edits, deletes and reactions only reach these;
+/// `mark_read` for an older message is ignored and editing one fails.
+pub const RECENT_MESSAGES: usize = 1024;
+
+/// Why the server closed a room
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryReason {
+    /// The room reached `rooms.max_lifetime`
+    MaxLifetime,
+    /// Nobody posted or joined for `rooms.idle_timeout`
+    Idle,
+    /// The host waited alone for `rooms.guest_timeout`
+    NoGuest,
+}
+
+impl ExpiryReason {
+    /// Reason as sent to clients
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExpiryReason::MaxLifetime => "max_lifetime",
+            ExpiryReason::Idle => "idle",
+            ExpiryReason::NoGuest => "no_guest",
+        }
+    }
+}
+
+/// A recent message as the room remembers it
+#[derive(Debug, Clone, Copy)]
+struct RecentMessage {
+    id: MessageId,
+    author: ClientId,
+    /// Deleted by its author (a tombstone, whatever the history keeps)
+    deleted: bool,
+}
+
+/// Chat Room
 ///
-/// A room can have at most 2 participants: a host (creator) and a guest.
-/// The host is promoted when the original host leaves.
+/// A room holds up to `capacity` participants in join order.
+/// The first member is the host; when the host leaves, the next
+/// member in join order is promoted.
 #[derive(Debug)]
 pub struct Room {
     /// Room code for identification
     pub code: RoomCode,
-    /// Room creator (host)
-    pub host: ClientId,
-    /// Joined partner (guest)
-    pub guest: Option<ClientId>,
+    /// Participants in join order (the first entry is the host)
+    pub members: Vec<ClientId>,
+    /// Maximum number of participants
+    pub capacity: usize,
     /// Room creation time
     pub created_at: Instant,
+    /// Password and invites required to join
+    pub access: RoomAccess,
+    /// Last message or join
+    last_activity: Instant,
+    /// When the host was last left alone, if they still are
+    alone_since: Option<Instant>,
+    /// Messages posted to the room so far
+    message_count: u64,
+    /// The latest `RECENT_MESSAGES` messages, oldest first
+    recent: VecDeque<RecentMessage>,
+    /// Messages each client has read, kept if they leave and come back
+    read: HashMap<ClientId, u64>,
+    /// Reactions to recent messages (messages without any are left out)
+    reactions: HashMap<MessageId, Reactions>,
 }
 
 impl Room {
-    /// Create a new room with the given code and host
+    /// Create a new 1:1 room with the given code and host
     pub fn new(code: RoomCode, host: ClientId) -> Self {
+        Self::with_capacity(code, host, DEFAULT_CAPACITY)
+    }
+
+    /// Create a new room with the given code, host and capacity
+    pub fn with_capacity(code: RoomCode, host: ClientId, capacity: usize) -> Self {
+        let now = Instant::now();
         Self {
             code,
-            host,
-            guest: None,
-            created_at: Instant::now(),
+            members: vec![host],
+            capacity,
+            created_at: now,
+            access: RoomAccess::default(),
+            last_activity: now,
+            alone_since: Some(now),
+            message_count: 0,
+            recent: VecDeque::new(),
+            read: HashMap::new(),
+            reactions: HashMap::new(),
         }
     }
 
-    /// Check if room is full (2 people)
+    /// Check whether a requested capacity is allowed (up to `max` seats)
+    pub fn is_valid_capacity(capacity: usize, max: usize) -> bool {
+        (DEFAULT_CAPACITY..=max).contains(&capacity)
+    }
+
+    /// Get the current host
+    pub fn host(&self) -> ClientId {
+        self.members[0]
+    }
+
+    /// Check if this is a group room (more than 2 seats)
+    pub fn is_group(&self) -> bool {
+        self.capacity > DEFAULT_CAPACITY
+    }
+
+    /// Check if room is full
     pub fn is_full(&self) -> bool {
-        self.guest.is_some()
+        self.members.len() >= self.capacity
     }
 
-    /// Check if room is empty (only host, no guest)
+    /// Check if room is empty (only host, nobody else)
     pub fn is_empty(&self) -> bool {
-        self.guest.is_none()
+        self.members.len() <= 1
     }
 
     /// Get the partner's ClientId for a given client
     ///
+    /// In a group room this is the first other member in join order.
     /// Returns None if the client is not in the room or has no partner.
     pub fn get_partner(&self, client_id: ClientId) -> Option<ClientId> {
-        if self.host == client_id {
-            self.guest
-        } else if self.guest == Some(client_id) {
-            Some(self.host)
-        } else {
-            None
+        if !self.contains(client_id) {
+            return None;
         }
+        self.members.iter().copied().find(|&id| id != client_id)
+    }
+
+    /// Get every other member of the room in join order
+    ///
+    /// Returns an empty list if the client is not in the room.
+    pub fn others(&self, client_id: ClientId) -> Vec<ClientId> {
+        if !self.contains(client_id) {
+            return Vec::new();
+        }
+        self.members
+            .iter()
+            .copied()
+            .filter(|&id| id != client_id)
+            .collect()
     }
 
     /// Check if a client is in this room
     pub fn contains(&self, client_id: ClientId) -> bool {
-        self.host == client_id || self.guest == Some(client_id)
+        self.members.contains(&client_id)
     }
 
     /// Remove a client from the room (handle leaving)
     ///
     /// Returns true if the room should be deleted (no participants left).
-    /// If the host leaves, the guest is promoted to host.
+    /// If the host leaves, the next member is promoted to host.
     pub fn remove_client(&mut self, client_id: ClientId) -> bool {
-        if self.host == client_id {
-            // If host leaves, promote guest to host
-            if let Some(guest) = self.guest.take() {
-                self.host = guest;
-                false // Keep room
-            } else {
-                true // Delete room (no one left)
-            }
-        } else if self.guest == Some(client_id) {
-            self.guest = None;
-            false // Keep room (host remains)
-        } else {
-            false // Client wasn't in room
+        let before = self.members.len();
+        self.members.retain(|&id| id != client_id);
+        if self.members.len() == 1 && before > 1 {
+            self.alone_since = Some(Instant::now());
         }
+        self.members.is_empty()
     }
 
-    /// Add a guest to the room
+    /// Add a member to the room
     ///
-    /// Returns false if the room is already full.
-    pub fn add_guest(&mut self, guest_id: ClientId) -> bool {
-        if self.is_full() {
+    /// Returns false if the room is already full or the client is already in it.
+    pub fn add_member(&mut self, client_id: ClientId) -> bool {
+        if self.is_full() || self.contains(client_id) {
             false
         } else {
-            self.guest = Some(guest_id);
+            self.members.push(client_id);
+            self.last_activity = Instant::now();
+            self.alone_since = None;
             true
         }
     }
 
     /// Get the number of participants in the room
     pub fn participant_count(&self) -> usize {
-        if self.guest.is_some() {
-            2
+        self.members.len()
+    }
+
+    /// Record a message posted by `author`, who has read it by writing it
+    pub fn record_message(&mut self, message_id: MessageId, author: ClientId) {
+        if self.recent.len() == RECENT_MESSAGES {
+            if let Some(old) = self.recent.pop_front() {
+                self.reactions.remove(&old.id);
+            }
+        }
+        self.recent.push_back(RecentMessage {
+            id: message_id,
+            author,
+            deleted: false,
+        });
+        self.message_count += 1;
+        self.last_activity = Instant::now();
+        self.read.insert(author, self.message_count);
+    }
+
+    /// Mark every message up to `up_to` as read by a client
+    ///
+    /// Returns the ID of the newest message now read, or None if this
+    /// doesn't move the client's read position forward.
+    pub fn mark_read(&mut self, client_id: ClientId, up_to: MessageId) -> Option<MessageId> {
+        // Messages before the window are unknown, so they can't be counted
+        let window_start = self.message_count - self.recent.len() as u64;
+        let in_window = self.recent.partition_point(|m| m.id <= up_to);
+        if in_window == 0 {
+            return None;
+        }
+
+        let position = window_start + in_window as u64;
+        let read = self.read.entry(client_id).or_default();
+        if position <= *read {
+            return None;
+        }
+        *read = position;
+        Some(self.recent[in_window - 1].id)
+    }
+
+    /// Who posted a recent message, if it was posted in this room and
+    /// hasn't been deleted
+    pub fn author_of(&self, message_id: MessageId) -> Option<ClientId> {
+        let index = self
+            .recent
+            .binary_search_by_key(&message_id, |m| m.id)
+            .ok()?;
+        let message = &self.recent[index];
+        (!message.deleted).then_some(message.author)
+    }
+
+    /// Mark a recent message deleted and drop its reactions
+    ///
+    /// Returns false if it isn't a recent message or is already deleted.
+    pub fn delete_message(&mut self, message_id: MessageId) -> bool {
+        let Ok(index) = self.recent.binary_search_by_key(&message_id, |m| m.id) else {
+            return false;
+        };
+        let message = &mut self.recent[index];
+        if message.deleted {
+            return false;
+        }
+        message.deleted = true;
+        self.reactions.remove(&message_id);
+        true
+    }
+
+    /// Add a client's reaction to a recent message
+    ///
+    /// Returns the emoji's new count, or None if nothing changed.
+    pub fn react(
+        &mut self,
+        message_id: MessageId,
+        client_id: ClientId,
+        emoji: &str,
+    ) -> Result<Option<usize>, AppError> {
+        if self.author_of(message_id).is_none() {
+            return Err(AppError::MessageNotFound(message_id));
+        }
+        self.reactions
+            .entry(message_id)
+            .or_default()
+            .add(emoji, client_id)
+    }
+
+    /// Remove a client's reaction to a message
+    ///
+    /// Returns the emoji's new count, or None if nothing changed.
+    pub fn unreact(
+        &mut self,
+        message_id: MessageId,
+        client_id: ClientId,
+        emoji: &str,
+    ) -> Option<usize> {
+        let reactions = self.reactions.get_mut(&message_id)?;
+        let count = reactions.remove(emoji, client_id);
+        if reactions.is_empty() {
+            self.reactions.remove(&message_id);
+        }
+        count
+    }
+
+    /// Start a client's read position with only the newest `replayed`
+    /// messages unread, unless it has read here before
+    pub fn start_reading(&mut self, client_id: ClientId, replayed: usize) {
+        let position = self.message_count.saturating_sub(replayed as u64);
+        self.read.entry(client_id).or_insert(position);
+    }
+
+    /// Number of recent messages a client hasn't read yet
+    pub fn unread_count(&self, client_id: ClientId) -> u64 {
+        // Messages before the window can't be marked read, so they don't count
+        let window_start = self.message_count - self.recent.len() as u64;
+        let read = self.read.get(&client_id).copied().unwrap_or(0);
+        self.message_count - read.max(window_start)
+    }
+
+    /// Check whether the room has outlived one of the configured timeouts
+    ///
+    /// A zero timeout never expires. The lifetime is checked first, then
+    /// the wait for a guest, then idleness.
+    pub fn expiry(&self, config: &RoomConfig, now: Instant) -> Option<ExpiryReason> {
+        let past = |since: Instant, timeout: Duration| {
+            !timeout.is_zero() && now.saturating_duration_since(since) >= timeout
+        };
+
+        if past(self.created_at, config.max_lifetime) {
+            Some(ExpiryReason::MaxLifetime)
+        } else if self
+            .alone_since
+            .is_some_and(|since| past(since, config.guest_timeout))
+        {
+            Some(ExpiryReason::NoGuest)
+        } else if past(self.last_activity, config.idle_timeout) {
+            Some(ExpiryReason::Idle)
         } else {
-            1
+            None
         }
     }
 }
@@ -115,8 +350,9 @@ mod tests {
         let room = Room::new(code.clone(), host_id);
 
         assert_eq!(room.code, code);
-        assert_eq!(room.host, host_id);
-        assert!(room.guest.is_none());
+        assert_eq!(room.host(), host_id);
+        assert_eq!(room.capacity, DEFAULT_CAPACITY);
+        assert!(!room.is_group());
         assert!(!room.is_full());
         assert!(room.is_empty());
         assert_eq!(room.participant_count(), 1);
@@ -128,14 +364,14 @@ mod tests {
         let guest_id = ClientId::new();
         let mut room = Room::new(RoomCode::generate(), host_id);
 
-        assert!(room.add_guest(guest_id));
+        assert!(room.add_member(guest_id));
         assert!(room.is_full());
         assert!(!room.is_empty());
         assert_eq!(room.participant_count(), 2);
 
         // Cannot add another guest
         let another_id = ClientId::new();
-        assert!(!room.add_guest(another_id));
+        assert!(!room.add_member(another_id));
     }
 
     #[test]
@@ -147,7 +383,7 @@ mod tests {
         // No partner before guest joins
         assert!(room.get_partner(host_id).is_none());
 
-        room.add_guest(guest_id);
+        room.add_member(guest_id);
 
         // Both can find their partner
         assert_eq!(room.get_partner(host_id), Some(guest_id));
@@ -169,7 +405,7 @@ mod tests {
         assert!(!room.contains(guest_id));
         assert!(!room.contains(other_id));
 
-        room.add_guest(guest_id);
+        room.add_member(guest_id);
 
         assert!(room.contains(host_id));
         assert!(room.contains(guest_id));
@@ -181,13 +417,13 @@ mod tests {
         let host_id = ClientId::new();
         let guest_id = ClientId::new();
         let mut room = Room::new(RoomCode::generate(), host_id);
-        room.add_guest(guest_id);
+        room.add_member(guest_id);
 
         // Guest leaves
         let should_delete = room.remove_client(guest_id);
         assert!(!should_delete);
-        assert!(room.guest.is_none());
-        assert_eq!(room.host, host_id);
+        assert!(room.is_empty());
+        assert_eq!(room.host(), host_id);
     }
 
     #[test]
@@ -195,13 +431,13 @@ mod tests {
         let host_id = ClientId::new();
         let guest_id = ClientId::new();
         let mut room = Room::new(RoomCode::generate(), host_id);
-        room.add_guest(guest_id);
+        room.add_member(guest_id);
 
         // Host leaves - guest promoted to host
         let should_delete = room.remove_client(host_id);
         assert!(!should_delete);
-        assert_eq!(room.host, guest_id);
-        assert!(room.guest.is_none());
+        assert_eq!(room.host(), guest_id);
+        assert_eq!(room.participant_count(), 1);
     }
 
     #[test]
@@ -213,4 +449,172 @@ mod tests {
         let should_delete = room.remove_client(host_id);
         assert!(should_delete);
     }
+
+    #[test]
+    fn test_group_room_capacity() {
+        let host_id = ClientId::new();
+        let mut room = Room::with_capacity(RoomCode::generate(), host_id, 4);
+        assert!(room.is_group());
+
+        let members: Vec<ClientId> = (0..3).map(|_| ClientId::new()).collect();
+        for &id in &members {
+            assert!(room.add_member(id));
+        }
+
+        assert!(room.is_full());
+        assert_eq!(room.participant_count(), 4);
+        assert!(!room.add_member(ClientId::new()));
+        assert_eq!(room.others(host_id), members);
+    }
+
+    #[test]
+    fn test_group_room_host_promotion_order() {
+        let host_id = ClientId::new();
+        let second = ClientId::new();
+        let third = ClientId::new();
+        let mut room = Room::with_capacity(RoomCode::generate(), host_id, 3);
+        room.add_member(second);
+        room.add_member(third);
+
+        assert!(!room.remove_client(host_id));
+        assert_eq!(room.host(), second);
+        assert_eq!(room.others(second), vec![third]);
+    }
+
+    #[test]
+    fn test_valid_capacity() {
+        assert!(!Room::is_valid_capacity(0, MAX_CAPACITY));
+        assert!(!Room::is_valid_capacity(1, MAX_CAPACITY));
+        assert!(Room::is_valid_capacity(DEFAULT_CAPACITY, MAX_CAPACITY));
+        assert!(Room::is_valid_capacity(MAX_CAPACITY, MAX_CAPACITY));
+        assert!(!Room::is_valid_capacity(MAX_CAPACITY + 1, MAX_CAPACITY));
+        assert!(!Room::is_valid_capacity(8, 4));
+    }
+
+    #[test]
+    fn test_read_positions() {
+        let alice = ClientId::new();
+        let bob = ClientId::new();
+        let mut room = Room::new(RoomCode::generate(), alice);
+        room.add_member(bob);
+
+        // Message IDs are global, so a room's IDs can have gaps
+        room.record_message(MessageId(3), alice);
+        room.record_message(MessageId(7), alice);
+        room.record_message(MessageId(9), alice);
+        assert_eq!(room.unread_count(alice), 0);
+        assert_eq!(room.unread_count(bob), 3);
+
+        assert_eq!(room.mark_read(bob, MessageId(8)), Some(MessageId(7)));
+        assert_eq!(room.unread_count(bob), 1);
+
+        // Read positions only move forward
+        assert_eq!(room.mark_read(bob, MessageId(3)), None);
+        assert_eq!(room.mark_read(bob, MessageId(7)), None);
+        assert_eq!(room.mark_read(bob, MessageId(2)), None);
+        assert_eq!(room.unread_count(bob), 1);
+
+        assert_eq!(room.mark_read(bob, MessageId(100)), Some(MessageId(9)));
+        assert_eq!(room.unread_count(bob), 0);
+    }
+
+    #[test]
+    fn test_message_authors() {
+        let alice = ClientId::new();
+        let bob = ClientId::new();
+        let mut room = Room::new(RoomCode::generate(), alice);
+        room.add_member(bob);
+
+        room.record_message(MessageId(3), alice);
+        room.record_message(MessageId(7), bob);
+        assert_eq!(room.author_of(MessageId(3)), Some(alice));
+        assert_eq!(room.author_of(MessageId(7)), Some(bob));
+        assert_eq!(room.author_of(MessageId(5)), None);
+
+        // Deleted messages have no author to edit or react as
+        assert!(room.delete_message(MessageId(3)));
+        assert!(!room.delete_message(MessageId(3)));
+        assert!(!room.delete_message(MessageId(5)));
+        assert_eq!(room.author_of(MessageId(3)), None);
+        assert!(room.react(MessageId(3), bob, "👍").is_err());
+    }
+
+    #[test]
+    fn test_read_window() {
+        let alice = ClientId::new();
+        let bob = ClientId::new();
+        let mut room = Room::new(RoomCode::generate(), alice);
+        for id in 1..=(RECENT_MESSAGES as u64 + 10) {
+            room.record_message(MessageId(id), alice);
+        }
+        assert_eq!(room.unread_count(bob), RECENT_MESSAGES as u64);
+
+        // Messages that fell out of the window can't be marked
+        assert_eq!(room.mark_read(bob, MessageId(5)), None);
+        assert_eq!(room.mark_read(bob, MessageId(20)), Some(MessageId(20)));
+        assert_eq!(room.unread_count(bob), RECENT_MESSAGES as u64 - 10);
+
+        // A fresh joiner has only the replayed messages unread
+        let carol = ClientId::new();
+        room.add_member(carol);
+        room.start_reading(carol, 50);
+        assert_eq!(room.unread_count(carol), 50);
+        assert_eq!(
+            room.mark_read(carol, MessageId(RECENT_MESSAGES as u64)),
+            Some(MessageId(RECENT_MESSAGES as u64))
+        );
+        assert_eq!(room.unread_count(carol), 10);
+
+        // Coming back keeps the old read position
+        room.start_reading(bob, 50);
+        assert_eq!(room.unread_count(bob), RECENT_MESSAGES as u64 - 10);
+
+        // Likewise their authors are forgotten
+        assert_eq!(room.author_of(MessageId(5)), None);
+        assert_eq!(room.author_of(MessageId(20)), Some(alice));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let config = RoomConfig {
+            max_lifetime: Duration::from_secs(3600),
+            idle_timeout: Duration::from_secs(600),
+            guest_timeout: Duration::from_secs(60),
+            ..RoomConfig::default()
+        };
+        let minutes = |n: u64| Instant::now() + Duration::from_secs(n * 60);
+        let alice = ClientId::new();
+        let bob = ClientId::new();
+        let mut room = Room::new(RoomCode::generate(), alice);
+
+        assert_eq!(room.expiry(&config, Instant::now()), None);
+        assert_eq!(
+            room.expiry(&config, minutes(2)),
+            Some(ExpiryReason::NoGuest)
+        );
+
+        // With a guest only idleness and age count
+        room.add_member(bob);
+        assert_eq!(room.expiry(&config, minutes(2)), None);
+        assert_eq!(room.expiry(&config, minutes(11)), Some(ExpiryReason::Idle));
+        assert_eq!(
+            room.expiry(&config, minutes(61)),
+            Some(ExpiryReason::MaxLifetime)
+        );
+
+        // Left alone again, the host waits for a new guest
+        room.remove_client(bob);
+        assert_eq!(
+            room.expiry(&config, minutes(2)),
+            Some(ExpiryReason::NoGuest)
+        );
+
+        let disabled = RoomConfig {
+            max_lifetime: Duration::ZERO,
+            idle_timeout: Duration::ZERO,
+            guest_timeout: Duration::ZERO,
+            ..RoomConfig::default()
+        };
+        assert_eq!(room.expiry(&disabled, minutes(24 * 60)), None);
+    }
 }
@@ -4,15 +4,32 @@
 //! Uses the Actor pattern with mpsc channels for message passing.
 
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
-use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep_until, Instant, MissedTickBehavior};
+use tracing::{debug, error, info, warn};
 
+use crate::access::{self, RoomAccess};
 use crate::client::Client;
+use crate::codes::{RoomCodeGenerator, MAX_CODE_ATTEMPTS};
+use crate::config::ServerConfig;
 use crate::error::AppError;
+use crate::history::{self, MemoryStore, MessageStore, ReplyTo, StoredMessage};
+use crate::matchmaking::{self, MatchQueue, Search};
 use crate::message::ServerMessage;
-use crate::room::Room;
-use crate::types::{ClientId, RoomCode};
+use crate::metrics::metrics;
+use crate::outbox::OutboxSender;
+use crate::ratelimit::TokenBucket;
+use crate::reaction;
+use crate::room::{self, ExpiryReason, Room};
+use crate::types::{ClientId, MessageId, ResumeToken, RoomCode};
+use crate::username::UsernamePolicy;
+
+/// Default time a dropped client's seat is held for resume
+pub const DEFAULT_RESUME_GRACE: Duration = Duration::from_secs(30);
 
 /// Commands sent from handlers to the ChatServer actor
 #[derive(Debug)]
@@ -20,7 +37,16 @@ pub enum ServerCommand {
     /// New client connected
     Connect {
         client_id: ClientId,
-        sender: mpsc::Sender<ServerMessage>,
+        sender: OutboxSender,
+        resume_token: ResumeToken,
+    },
+    /// Reattach a new connection to a dropped session
+    ///
+    /// `reply` receives the resumed ClientId, or None if resume failed.
+    Resume {
+        client_id: ClientId,
+        token: String,
+        reply: oneshot::Sender<Option<ClientId>>,
     },
     /// Client disconnected
     Disconnect {
@@ -31,19 +57,69 @@ pub enum ServerCommand {
         client_id: ClientId,
         username: String,
     },
-    /// Create a new room
+    /// Create a new room, optionally protected
     CreateRoom {
         client_id: ClientId,
+        capacity: Option<usize>,
+        password: Option<String>,
+        invite_only: bool,
     },
     /// Join an existing room
     JoinRoom {
         client_id: ClientId,
         room_code: String,
+        password: Option<String>,
+        invite: Option<String>,
+    },
+    /// Create an invite to the host's room
+    CreateInvite {
+        client_id: ClientId,
+        single_use: bool,
+        ttl_secs: Option<u64>,
     },
     /// Send a chat message
+    ///
+    /// `client_msg_id` identifies retries of the same message; `reply_to`
+    /// names the message being replied to.
     Chat {
         client_id: ClientId,
         content: String,
+        client_msg_id: Option<String>,
+        reply_to: Option<MessageId>,
+    },
+    /// Fetch a page of room history
+    FetchHistory {
+        client_id: ClientId,
+        before: Option<MessageId>,
+        limit: Option<usize>,
+    },
+    /// Mark room messages up to `up_to_message_id` as read
+    MarkRead {
+        client_id: ClientId,
+        up_to_message_id: MessageId,
+    },
+    /// Replace the content of the client's own message
+    EditMessage {
+        client_id: ClientId,
+        message_id: MessageId,
+        content: String,
+    },
+    /// Delete the client's own message
+    DeleteMessage {
+        client_id: ClientId,
+        message_id: MessageId,
+    },
+    /// Add an emoji reaction to a room message
+    React {
+        client_id: ClientId,
+        message_id: MessageId,
+        emoji: String,
+    },
+    /// Remove an emoji reaction
+    Unreact {
+        client_id: ClientId,
+        message_id: MessageId,
+        emoji: String,
     },
     /// Client started typing
     Typing {
@@ -57,6 +133,91 @@ pub enum ServerCommand {
     LeaveRoom {
         client_id: ClientId,
     },
+    /// Wait for a random partner
+    FindPartner {
+        client_id: ClientId,
+        tags: Vec<String>,
+    },
+    /// Stop waiting for a random partner
+    CancelFind {
+        client_id: ClientId,
+    },
+    /// Report current client and room counts
+    Stats {
+        reply: oneshot::Sender<ServerStats>,
+    },
+    /// Notify every client and stop the actor
+    ///
+    /// Queued messages are still delivered; each connection closes
+    /// once its queue is drained.
+    Shutdown {
+        reason: String,
+        reconnect_after: Option<Duration>,
+    },
+}
+
+impl ServerCommand {
+    /// Short snake_case name of the command (used as a metrics label)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerCommand::Connect { .. } => "connect",
+            ServerCommand::Resume { .. } => "resume",
+            ServerCommand::Disconnect { .. } => "disconnect",
+            ServerCommand::SetUsername { .. } => "set_username",
+            ServerCommand::CreateRoom { .. } => "create_room",
+            ServerCommand::JoinRoom { .. } => "join_room",
+            ServerCommand::CreateInvite { .. } => "create_invite",
+            ServerCommand::Chat { .. } => "chat",
+            ServerCommand::FetchHistory { .. } => "fetch_history",
+            ServerCommand::MarkRead { .. } => "mark_read",
+            ServerCommand::EditMessage { .. } => "edit_message",
+            ServerCommand::DeleteMessage { .. } => "delete_message",
+            ServerCommand::React { .. } => "react",
+            ServerCommand::Unreact { .. } => "unreact",
+            ServerCommand::Typing { .. } => "typing",
+            ServerCommand::StopTyping { .. } => "stop_typing",
+            ServerCommand::LeaveRoom { .. } => "leave_room",
+            ServerCommand::FindPartner { .. } => "find_partner",
+            ServerCommand::CancelFind { .. } => "cancel_find",
+            ServerCommand::Stats { .. } => "stats",
+            ServerCommand::Shutdown { .. } => "shutdown",
+        }
+    }
+}
+
+/// Snapshot of the actor's state, as returned by `ServerCommand::Stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ServerStats {
+    /// Connected clients, including detached ones awaiting resume
+    pub clients: usize,
+    /// Clients that have set a username
+    pub named_clients: usize,
+    /// Dropped clients whose seat is held for resume
+    pub detached_clients: usize,
+    /// Open rooms
+    pub rooms: usize,
+    /// Rooms with every seat taken
+    pub full_rooms: usize,
+    /// Clients waiting for a random partner
+    pub searching_clients: usize,
+}
+
+/// Password work finished on the blocking pool
+#[derive(Debug)]
+enum PasswordTask {
+    /// A new room's password was hashed
+    Hashed {
+        client_id: ClientId,
+        capacity: usize,
+        invite_only: bool,
+        hash: Result<String, AppError>,
+    },
+    /// A joining client's password was checked
+    Verified {
+        client_id: ClientId,
+        room_code: RoomCode,
+        ok: bool,
+    },
 }
 
 /// The main ChatServer actor
@@ -70,71 +231,275 @@ pub struct ChatServer {
     rooms: HashMap<RoomCode, Room>,
     /// Client to room mapping for fast lookup: ClientId -> RoomCode
     client_rooms: HashMap<ClientId, RoomCode>,
+    /// Room message history backend
+    store: Box<dyn MessageStore>,
+    /// ID assigned to the next chat message
+    next_message_id: MessageId,
+    /// Resume token to client mapping: ResumeToken -> ClientId
+    resume_tokens: HashMap<ResumeToken, ClientId>,
+    /// Detached clients and when their held seat expires
+    detached: HashMap<ClientId, Instant>,
+    /// Username owners by skeleton, for uniqueness: skeleton -> ClientId
+    usernames: HashMap<String, ClientId>,
+    /// Password hashing and checks running off the actor
+    password_tasks: JoinSet<PasswordTask>,
+    /// Permits for password work, bounding its memory and thread use
+    password_slots: Arc<Semaphore>,
+    /// Source of new room codes
+    codes: Box<dyn RoomCodeGenerator>,
+    /// Clients waiting for a random partner
+    match_queue: MatchQueue,
+    /// Server settings (room limits, history limits, resume grace)
+    config: Arc<ServerConfig>,
     /// Command receiver channel
     receiver: mpsc::Receiver<ServerCommand>,
 }
 
 impl ChatServer {
-    /// Create a new ChatServer with the given command receiver
-    pub fn new(receiver: mpsc::Receiver<ServerCommand>) -> Self {
+    /// Create a new ChatServer with the given command receiver and settings
+    ///
+    /// History is kept in memory; use `with_store` for another backend.
+    /// Room codes come from the configured generator; use
+    /// `with_code_generator` for another one.
+    pub fn new(receiver: mpsc::Receiver<ServerCommand>, config: Arc<ServerConfig>) -> Self {
         Self {
             clients: HashMap::new(),
             rooms: HashMap::new(),
             client_rooms: HashMap::new(),
+            store: Box::new(MemoryStore::new(config.history.capacity)),
+            next_message_id: MessageId(1),
+            resume_tokens: HashMap::new(),
+            detached: HashMap::new(),
+            usernames: HashMap::new(),
+            password_tasks: JoinSet::new(),
+            password_slots: Arc::new(Semaphore::new(config.rooms.max_password_tasks)),
+            codes: config.rooms.code_generator(),
+            match_queue: MatchQueue::new(),
+            config,
             receiver,
         }
     }
 
+    /// Use the given message history backend
+    pub fn with_store(mut self, store: Box<dyn MessageStore>) -> Self {
+        self.next_message_id = store.last_message_id().next();
+        self.store = store;
+        self
+    }
+
+    /// Use the given room code generator
+    pub fn with_code_generator(mut self, codes: Box<dyn RoomCodeGenerator>) -> Self {
+        self.codes = codes;
+        self
+    }
+
     /// Run the ChatServer event loop
     ///
-    /// Continuously receives and processes commands until all senders are dropped.
+    /// Continuously receives and processes commands until `Shutdown` arrives
+    /// or all senders are dropped.
+    /// Between commands, expires held sessions whose grace period ran out
+    /// and completes joins and room creations waiting on a password.
     pub async fn run(mut self) {
         info!("ChatServer started");
 
-        while let Some(cmd) = self.receiver.recv().await {
-            self.handle_command(cmd).await;
+        let mut reaper = interval(self.config.rooms.reap_interval);
+        reaper.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let next_expiry = self.detached.values().min().copied();
+
+            tokio::select! {
+                cmd = self.receiver.recv() => match cmd {
+                    Some(ServerCommand::Shutdown {
+                        reason,
+                        reconnect_after,
+                    }) => {
+                        metrics().commands.with_label_values(&["shutdown"]).inc();
+                        self.handle_shutdown(reason, reconnect_after);
+                        break;
+                    }
+                    Some(cmd) => {
+                        let command = cmd.name();
+                        let started = Instant::now();
+                        self.handle_command(cmd);
+
+                        let m = metrics();
+                        m.commands.with_label_values(&[command]).inc();
+                        m.command_duration
+                            .with_label_values(&[command])
+                            .observe(started.elapsed().as_secs_f64());
+                        self.update_gauges();
+                    }
+                    None => break,
+                },
+                _ = sleep_until_some(next_expiry) => {
+                    self.expire_sessions();
+                    self.update_gauges();
+                }
+                _ = reaper.tick() => {
+                    self.reap_rooms();
+                    self.update_gauges();
+                }
+                Some(result) = self.password_tasks.join_next() => {
+                    match result {
+                        Ok(task) => self.finish_password_task(task),
+                        Err(e) => error!("Password task failed: {}", e),
+                    }
+                    self.update_gauges();
+                }
+            }
         }
 
         info!("ChatServer shutting down");
     }
 
     /// Process a single command
-    async fn handle_command(&mut self, cmd: ServerCommand) {
+    fn handle_command(&mut self, cmd: ServerCommand) {
         match cmd {
-            ServerCommand::Connect { client_id, sender } => {
-                self.handle_connect(client_id, sender).await;
+            ServerCommand::Connect {
+                client_id,
+                sender,
+                resume_token,
+            } => {
+                self.handle_connect(client_id, sender, resume_token);
+            }
+            ServerCommand::Resume {
+                client_id,
+                token,
+                reply,
+            } => {
+                self.handle_resume(client_id, token, reply);
             }
             ServerCommand::Disconnect { client_id } => {
-                self.handle_disconnect(client_id).await;
+                self.handle_disconnect(client_id);
             }
             ServerCommand::SetUsername { client_id, username } => {
-                self.handle_set_username(client_id, username).await;
+                self.handle_set_username(client_id, username);
+            }
+            ServerCommand::CreateRoom {
+                client_id,
+                capacity,
+                password,
+                invite_only,
+            } => {
+                self.handle_create_room(client_id, capacity, password, invite_only);
             }
-            ServerCommand::CreateRoom { client_id } => {
-                self.handle_create_room(client_id).await;
+            ServerCommand::JoinRoom {
+                client_id,
+                room_code,
+                password,
+                invite,
+            } => {
+                self.handle_join_room(client_id, room_code, password, invite);
             }
-            ServerCommand::JoinRoom { client_id, room_code } => {
-                self.handle_join_room(client_id, room_code).await;
+            ServerCommand::CreateInvite {
+                client_id,
+                single_use,
+                ttl_secs,
+            } => {
+                self.handle_create_invite(client_id, single_use, ttl_secs);
             }
-            ServerCommand::Chat { client_id, content } => {
-                self.handle_chat(client_id, content).await;
+            ServerCommand::Chat {
+                client_id,
+                content,
+                client_msg_id,
+                reply_to,
+            } => {
+                self.handle_chat(client_id, content, client_msg_id, reply_to);
+            }
+            ServerCommand::FetchHistory {
+                client_id,
+                before,
+                limit,
+            } => {
+                self.handle_fetch_history(client_id, before, limit);
+            }
+            ServerCommand::MarkRead {
+                client_id,
+                up_to_message_id,
+            } => {
+                self.handle_mark_read(client_id, up_to_message_id);
+            }
+            ServerCommand::EditMessage {
+                client_id,
+                message_id,
+                content,
+            } => {
+                self.handle_edit_message(client_id, message_id, Some(content));
+            }
+            ServerCommand::DeleteMessage {
+                client_id,
+                message_id,
+            } => {
+                self.handle_edit_message(client_id, message_id, None);
+            }
+            ServerCommand::React {
+                client_id,
+                message_id,
+                emoji,
+            } => {
+                self.handle_reaction(client_id, message_id, emoji, true);
+            }
+            ServerCommand::Unreact {
+                client_id,
+                message_id,
+                emoji,
+            } => {
+                self.handle_reaction(client_id, message_id, emoji, false);
             }
             ServerCommand::Typing { client_id } => {
-                self.handle_typing(client_id).await;
+                self.handle_typing(client_id);
             }
             ServerCommand::StopTyping { client_id } => {
-                self.handle_stop_typing(client_id).await;
+                self.handle_stop_typing(client_id);
             }
             ServerCommand::LeaveRoom { client_id } => {
-                self.handle_leave_room(client_id).await;
+                self.handle_leave_room(client_id);
             }
+            ServerCommand::FindPartner { client_id, tags } => {
+                self.handle_find_partner(client_id, tags);
+            }
+            ServerCommand::CancelFind { client_id } => {
+                self.handle_cancel_find(client_id);
+            }
+            ServerCommand::Stats { reply } => {
+                let _ = reply.send(self.stats());
+            }
+            ServerCommand::Shutdown { .. } => unreachable!("Shutdown is handled by run"),
+        }
+    }
+
+    /// Handle server shutdown
+    ///
+    /// Tells every client why the server is going away, then closes their
+    /// queues so each write task flushes what is left and closes the socket.
+    fn handle_shutdown(&mut self, reason: String, reconnect_after: Option<Duration>) {
+        info!(
+            "Shutting down ({}), notifying {} clients",
+            reason,
+            self.clients.len()
+        );
+
+        let msg = ServerMessage::ServerShutdown {
+            reason,
+            reconnect_after: reconnect_after.map(|d| d.as_millis() as u64),
+        };
+        for client in self.clients.values() {
+            let _ = client.send(msg.clone());
+            client.close();
         }
     }
 
     /// Handle new client connection
-    async fn handle_connect(&mut self, client_id: ClientId, sender: mpsc::Sender<ServerMessage>) {
+    fn handle_connect(
+        &mut self,
+        client_id: ClientId,
+        sender: OutboxSender,
+        resume_token: ResumeToken,
+    ) {
         info!("Client {} connected", client_id);
-        let client = Client::new(client_id, sender);
+        self.resume_tokens.insert(resume_token.clone(), client_id);
+        let client = Client::new(client_id, sender, resume_token);
         self.clients.insert(client_id, client);
         debug!(
             "Total clients: {}, Total rooms: {}",
@@ -144,16 +509,208 @@ impl ChatServer {
     }
 
     /// Handle client disconnection
-    async fn handle_disconnect(&mut self, client_id: ClientId) {
+    ///
+    /// Named clients are detached rather than removed: their seat is held
+    /// for the resume grace period and other members are told they are
+    /// reconnecting.
+    fn handle_disconnect(&mut self, client_id: ClientId) {
         info!("Client {} disconnected", client_id);
 
+        // A held session doesn't keep its place in the partner queue
+        self.leave_queue(client_id);
+
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+
+        let grace = self.config.rooms.resume_grace;
+        if grace.is_zero() || !client.has_username() || client.is_detached() {
+            self.remove_client(client_id);
+            return;
+        }
+
+        let was_typing = client.is_typing;
+        client.detach();
+        let username = client.display_name().to_string();
+        self.detached.insert(client_id, Instant::now() + grace);
+        info!("Holding session of client {} for {:?}", client_id, grace);
+
+        // Notify other members
+        if let Some(room_code) = self.client_rooms.get(&client_id).cloned() {
+            let others = self.room_others(client_id, &room_code);
+            if was_typing {
+                self.broadcast(&others, ServerMessage::PartnerStopTyping);
+            }
+            self.broadcast(&others, ServerMessage::PartnerReconnecting { username });
+        }
+    }
+
+    /// Handle session resume from a new connection
+    fn handle_resume(
+        &mut self,
+        client_id: ClientId,
+        token: String,
+        reply: oneshot::Sender<Option<ClientId>>,
+    ) {
+        let token = ResumeToken(token);
+
+        // The token must belong to a detached client, and the new
+        // connection must not have started a session of its own
+        let old_id = self.resume_tokens.get(&token).copied().filter(|&old_id| {
+            old_id != client_id
+                && self.clients.get(&old_id).is_some_and(Client::is_detached)
+                && self.clients.get(&client_id).is_some_and(|c| !c.has_username())
+                && !self.client_rooms.contains_key(&client_id)
+        });
+
+        let Some(old_id) = old_id else {
+            if let Some(client) = self.clients.get(&client_id) {
+                let _ = client.send(AppError::ResumeFailed.into());
+            }
+            let _ = reply.send(None);
+            return;
+        };
+
+        // Retire the new connection's own identity and the used token
+        let Some(Client {
+            sender,
+            resume_token,
+            ..
+        }) = self.clients.remove(&client_id)
+        else {
+            let _ = reply.send(None);
+            return;
+        };
+        self.resume_tokens.remove(&resume_token);
+        self.resume_tokens.remove(&token);
+        self.resume_tokens.insert(resume_token.clone(), old_id);
+        self.detached.remove(&old_id);
+
+        let room_code = self.client_rooms.get(&old_id).cloned();
+        let unread = room_code
+            .as_ref()
+            .and_then(|code| self.rooms.get(code))
+            .map(|room| room.unread_count(old_id));
+        let Some(client) = self.clients.get_mut(&old_id) else {
+            let _ = reply.send(None);
+            return;
+        };
+
+        let resumed = ServerMessage::Resumed {
+            client_id: old_id.to_string(),
+            resume_token: resume_token.to_string(),
+            username: client.username.clone(),
+            room_code: room_code.as_ref().map(|c| c.to_string()),
+            unread,
+        };
+        client.reattach(sender, resume_token, resumed);
+        let username = client.display_name().to_string();
+
+        info!("Client {} resumed as {}", client_id, old_id);
+
+        // Notify other members
+        if let Some(room_code) = room_code {
+            let others = self.room_others(old_id, &room_code);
+            self.broadcast(&others, ServerMessage::PartnerReconnected { username });
+        }
+
+        let _ = reply.send(Some(old_id));
+    }
+
+    /// Remove sessions whose resume grace period has run out
+    fn expire_sessions(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<ClientId> = self
+            .detached
+            .iter()
+            .filter(|(_, &deadline)| deadline <= now)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for client_id in expired {
+            info!("Session of client {} expired", client_id);
+            self.remove_client(client_id);
+        }
+    }
+
+    /// Close rooms that have outlived one of the room timeouts
+    fn reap_rooms(&mut self) {
+        let now = std::time::Instant::now();
+        let expired: Vec<(RoomCode, ExpiryReason)> = self
+            .rooms
+            .values()
+            .filter_map(|room| Some((room.code.clone(), room.expiry(&self.config.rooms, now)?)))
+            .collect();
+
+        for (room_code, reason) in expired {
+            self.close_room(&room_code, reason);
+        }
+    }
+
+    /// Helper: Remove a room and everyone in it, telling them why
+    ///
+    /// Members stay connected (or keep their held session) outside any room.
+    fn close_room(&mut self, room_code: &RoomCode, reason: ExpiryReason) {
+        let Some(room) = self.rooms.remove(room_code) else {
+            return;
+        };
+        info!("Room {} expired ({})", room_code, reason.as_str());
+
+        for member in &room.members {
+            self.client_rooms.remove(member);
+        }
+        self.broadcast(&room.members, ServerMessage::RoomExpired { reason });
+
+        let m = metrics();
+        m.room_lifetime.observe(room.created_at.elapsed().as_secs_f64());
+        m.rooms_expired.with_label_values(&[reason.as_str()]).inc();
+        if let Err(e) = self.store.remove_room(room_code) {
+            error!("Failed to clear history for room {}: {}", room_code, e);
+        }
+    }
+
+    /// Helper: Refresh the state gauges after a change
+    fn update_gauges(&self) {
+        let m = metrics();
+        let stats = self.stats();
+        m.connected_clients.set(stats.clients as i64);
+        m.named_clients.set(stats.named_clients as i64);
+        m.active_rooms.set(stats.rooms as i64);
+        m.full_rooms.set(stats.full_rooms as i64);
+        m.searching_clients.set(stats.searching_clients as i64);
+    }
+
+    /// Helper: Count clients and rooms
+    fn stats(&self) -> ServerStats {
+        ServerStats {
+            clients: self.clients.len(),
+            named_clients: self.clients.values().filter(|c| c.has_username()).count(),
+            detached_clients: self.detached.len(),
+            rooms: self.rooms.len(),
+            full_rooms: self.rooms.values().filter(|r| r.is_full()).count(),
+            searching_clients: self.match_queue.len(),
+        }
+    }
+
+    /// Helper: Remove a client entirely, leaving its room
+    fn remove_client(&mut self, client_id: ClientId) {
         // Remove from room if in one
         if let Some(room_code) = self.client_rooms.remove(&client_id) {
-            self.remove_client_from_room(client_id, &room_code).await;
+            self.remove_client_from_room(client_id, &room_code);
         }
 
         // Remove client
-        self.clients.remove(&client_id);
+        self.detached.remove(&client_id);
+        if let Some(client) = self.clients.remove(&client_id) {
+            self.resume_tokens.remove(&client.resume_token);
+            if let Some(username) = &client.username {
+                self.release_username(client_id, username);
+            }
+            let dropped = client.dropped_messages();
+            if dropped > 0 {
+                info!("Client {} had {} messages dropped", client_id, dropped);
+            }
+        }
 
         debug!(
             "Total clients: {}, Total rooms: {}",
@@ -162,181 +719,759 @@ impl ChatServer {
         );
     }
 
-    /// Handle username setting
-    async fn handle_set_username(&mut self, client_id: ClientId, username: String) {
-        let Some(client) = self.clients.get_mut(&client_id) else {
+    /// Handle username setting (or renaming)
+    fn handle_set_username(&mut self, client_id: ClientId, username: String) {
+        let Some(client) = self.clients.get(&client_id) else {
             return;
         };
 
+        // Check the name against the policy
+        let policy = &self.config.usernames;
+        let username = match policy.check(&username) {
+            Ok(username) => username,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+
+        // Check no one else holds the name or a look-alike
+        let key = UsernamePolicy::skeleton(&username);
+        if policy.unique && self.usernames.get(&key).is_some_and(|&owner| owner != client_id) {
+            let _ = client.send(AppError::UsernameTaken(username).into());
+            return;
+        }
+
+        let old_username = client.username.clone();
+        if let Some(old_username) = &old_username {
+            self.release_username(client_id, old_username);
+        }
+        self.usernames.insert(key, client_id);
+
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
         client.set_username(username.clone());
         info!("Client {} set username to '{}'", client_id, username);
 
-        let _ = client
-            .send(ServerMessage::UsernameSet {
-                username: username.clone(),
-            })
-            .await;
+        let _ = client.send(ServerMessage::UsernameSet {
+            username: username.clone(),
+        });
+
+        // Notify other members of a rename
+        let Some(old_username) = old_username.filter(|old| *old != username) else {
+            return;
+        };
+        let Some(room_code) = self.client_rooms.get(&client_id) else {
+            return;
+        };
+        let Some(room) = self.rooms.get(room_code) else {
+            return;
+        };
+        let others = room.others(client_id);
+        let notice = if room.is_group() {
+            ServerMessage::MemberRenamed {
+                old_username,
+                username,
+                members: self.member_names(room_code),
+            }
+        } else {
+            ServerMessage::PartnerRenamed {
+                old_username,
+                username,
+            }
+        };
+        self.broadcast(&others, notice);
+    }
+
+    /// Helper: Free a client's username for others to take
+    fn release_username(&mut self, client_id: ClientId, username: &str) {
+        let key = UsernamePolicy::skeleton(username);
+        if self.usernames.get(&key) == Some(&client_id) {
+            self.usernames.remove(&key);
+        }
     }
 
     /// Handle room creation
-    async fn handle_create_room(&mut self, client_id: ClientId) {
+    ///
+    /// A password is hashed off the actor first; the room opens once
+    /// that is done.
+    fn handle_create_room(
+        &mut self,
+        client_id: ClientId,
+        capacity: Option<usize>,
+        password: Option<String>,
+        invite_only: bool,
+    ) {
         let Some(client) = self.clients.get(&client_id) else {
             return;
         };
 
         // Check username
         if !client.has_username() {
-            let _ = client.send(AppError::UsernameRequired.into()).await;
+            let _ = client.send(AppError::UsernameRequired.into());
             return;
         }
 
         // Check if already in a room
         if self.client_rooms.contains_key(&client_id) {
-            let _ = client.send(AppError::AlreadyInRoom.into()).await;
+            let _ = client.send(AppError::AlreadyInRoom.into());
+            return;
+        }
+
+        // Check requested capacity
+        let capacity = capacity.unwrap_or(room::DEFAULT_CAPACITY);
+        let max = self.config.rooms.max_capacity;
+        if !Room::is_valid_capacity(capacity, max) {
+            let _ = client.send(AppError::InvalidCapacity { capacity, max }.into());
+            return;
+        }
+
+        if let Some(password) = password {
+            if let Err(e) = access::check_password(&password) {
+                let _ = client.send(e.into());
+                return;
+            }
+            self.spawn_password_task(client_id, move || PasswordTask::Hashed {
+                client_id,
+                capacity,
+                invite_only,
+                hash: access::hash_password(&password),
+            });
             return;
         }
 
+        self.open_room(client_id, capacity, RoomAccess::new(None, invite_only));
+    }
+
+    /// Helper: Create a room hosted by the client
+    fn open_room(&mut self, client_id: ClientId, capacity: usize, access: RoomAccess) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+
         // Generate unique room code
-        let room_code = loop {
-            let code = RoomCode::generate();
-            if !self.rooms.contains_key(&code) {
-                break code;
+        let room_code = match free_room_code(self.codes.as_mut(), &self.rooms) {
+            Ok(room_code) => room_code,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
             }
         };
 
+        // Discard any history left behind under a reused code
+        if let Err(e) = self.store.remove_room(&room_code) {
+            error!("Failed to clear history for room {}: {}", room_code, e);
+        }
+
         // Create room
-        let room = Room::new(room_code.clone(), client_id);
+        let password_protected = access.password_hash.is_some();
+        let invite_only = access.invite_only;
+        let mut room = Room::with_capacity(room_code.clone(), client_id, capacity);
+        room.access = access;
         self.rooms.insert(room_code.clone(), room);
         self.client_rooms.insert(client_id, room_code.clone());
 
-        info!("Client {} created room {}", client_id, room_code);
+        info!(
+            "Client {} created room {} (capacity {})",
+            client_id, room_code, capacity
+        );
 
-        let _ = client
-            .send(ServerMessage::RoomCreated {
-                room_code: room_code.to_string(),
-            })
-            .await;
+        let _ = client.send(ServerMessage::RoomCreated {
+            room_code: room_code.to_string(),
+            capacity,
+            password_protected,
+            invite_only,
+        });
+        self.leave_queue(client_id);
     }
 
     /// Handle room joining
-    async fn handle_join_room(&mut self, client_id: ClientId, room_code: String) {
-        let Some(client) = self.clients.get(&client_id) else {
+    ///
+    /// Protected rooms take an invite or the password; every attempt
+    /// counts against the client's `join_attempts` limit. Passwords are
+    /// checked off the actor and the join completes afterwards.
+    fn handle_join_room(
+        &mut self,
+        client_id: ClientId,
+        room_code: String,
+        password: Option<String>,
+        invite: Option<String>,
+    ) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
             return;
         };
 
         // Check username
         if !client.has_username() {
-            let _ = client.send(AppError::UsernameRequired.into()).await;
+            let _ = client.send(AppError::UsernameRequired.into());
             return;
         }
 
-        // Check if already in a room
-        if self.client_rooms.contains_key(&client_id) {
-            let _ = client.send(AppError::AlreadyInRoom.into()).await;
-            return;
+        let room_code = RoomCode::from_string(room_code);
+        let joinable = check_joinable(&self.client_rooms, &mut self.rooms, client_id, &room_code);
+        let room = match joinable {
+            Ok(room) => room,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+
+        if !room.access.is_open() {
+            let now = Instant::now();
+            let limit = &self.config.rooms.join_attempts;
+            let attempts = client
+                .join_attempts
+                .get_or_insert_with(|| TokenBucket::for_limit(limit, now));
+            let wait = attempts.wait_time(now);
+            if !attempts.try_take(now) {
+                let retry_after_ms = wait.as_millis() as u64;
+                let _ = client.send(AppError::RateLimited { retry_after_ms }.into());
+                return;
+            }
+
+            // A valid invite also stands in for the password
+            if let Some(invite) = invite {
+                if !room.access.redeem_invite(&invite) {
+                    let _ = client.send(AppError::InviteInvalid.into());
+                    return;
+                }
+            } else if room.access.invite_only {
+                let _ = client.send(AppError::InviteInvalid.into());
+                return;
+            } else if let Some(hash) = room.access.password_hash.clone() {
+                let Some(password) = password else {
+                    let _ = client.send(AppError::WrongPassword.into());
+                    return;
+                };
+                self.spawn_password_task(client_id, move || PasswordTask::Verified {
+                    client_id,
+                    room_code,
+                    ok: access::verify_password(&password, &hash),
+                });
+                return;
+            }
         }
 
-        let room_code = RoomCode::from_string(room_code);
+        self.admit(client_id, room_code);
+    }
 
-        // Check room exists
-        let Some(room) = self.rooms.get_mut(&room_code) else {
-            let _ = client
-                .send(AppError::RoomNotFound(room_code.to_string()).into())
-                .await;
+    /// Helper: Run password work on the blocking pool if a slot is free
+    ///
+    /// Each Argon2 run takes about 19 MB, so only `max_password_tasks` run
+    /// at once across all clients; anyone else is told to retry.
+    fn spawn_password_task<F>(&mut self, client_id: ClientId, task: F)
+    where
+        F: FnOnce() -> PasswordTask + Send + 'static,
+    {
+        let Ok(permit) = self.password_slots.clone().try_acquire_owned() else {
+            warn!("Password slots busy, turning away client {}", client_id);
+            if let Some(client) = self.clients.get(&client_id) {
+                let retry_after_ms = access::PASSWORD_BUSY_RETRY.as_millis() as u64;
+                let _ = client.send(AppError::RateLimited { retry_after_ms }.into());
+            }
             return;
         };
+        self.password_tasks.spawn_blocking(move || {
+            let _permit = permit;
+            task()
+        });
+    }
+
+    /// Finish a room creation or join that waited on a password
+    ///
+    /// The client may have left, joined elsewhere, or found the room
+    /// full in the meantime, so the usual checks run again.
+    fn finish_password_task(&mut self, task: PasswordTask) {
+        match task {
+            PasswordTask::Hashed {
+                client_id,
+                capacity,
+                invite_only,
+                hash,
+            } => {
+                let Some(client) = self.clients.get(&client_id) else {
+                    return;
+                };
+                if self.client_rooms.contains_key(&client_id) {
+                    let _ = client.send(AppError::AlreadyInRoom.into());
+                    return;
+                }
+                match hash {
+                    Ok(hash) => {
+                        let access = RoomAccess::new(Some(hash), invite_only);
+                        self.open_room(client_id, capacity, access);
+                    }
+                    Err(e) => {
+                        error!("Failed to hash room password: {}", e);
+                        let _ = client.send(e.into());
+                    }
+                }
+            }
+            PasswordTask::Verified {
+                client_id,
+                room_code,
+                ok,
+            } => {
+                if ok {
+                    self.admit(client_id, room_code);
+                } else if let Some(client) = self.clients.get(&client_id) {
+                    let _ = client.send(AppError::WrongPassword.into());
+                }
+            }
+        }
+    }
 
-        // Check room capacity
-        if room.is_full() {
-            let _ = client.send(AppError::RoomFull.into()).await;
+    /// Handle invite creation (room host only)
+    fn handle_create_invite(
+        &mut self,
+        client_id: ClientId,
+        single_use: bool,
+        ttl_secs: Option<u64>,
+    ) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        let Some(room) = self
+            .client_rooms
+            .get(&client_id)
+            .and_then(|room_code| self.rooms.get_mut(room_code))
+        else {
+            let _ = client.send(AppError::NotInRoom.into());
+            return;
+        };
+        if room.host() != client_id {
+            let _ = client.send(AppError::NotRoomHost.into());
             return;
         }
 
-        // Add guest to room
-        let host_id = room.host;
-        room.add_guest(client_id);
+        let rooms = &self.config.rooms;
+        let ttl = ttl_secs
+            .map(Duration::from_secs)
+            .unwrap_or(rooms.invite_ttl)
+            .max(Duration::from_secs(1))
+            .min(rooms.max_invite_ttl);
+        let invite = room.access.create_invite(ttl, single_use);
+        info!("Client {} created an invite to room {}", client_id, room.code);
+
+        let _ = client.send(ServerMessage::InviteCreated {
+            invite,
+            single_use,
+            expires_in_secs: ttl.as_secs(),
+        });
+    }
+
+    /// Helper: Seat the client in a room it is allowed into
+    fn admit(&mut self, client_id: ClientId, room_code: RoomCode) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        let joinable = check_joinable(&self.client_rooms, &mut self.rooms, client_id, &room_code);
+        let room = match joinable {
+            Ok(room) => room,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+
+        // Add member to room
+        let host_id = room.host();
+        let is_group = room.is_group();
+        room.add_member(client_id);
+        let history = self
+            .store
+            .recent(&room_code, self.config.history.replay_limit)
+            .unwrap_or_else(|e| {
+                error!("Failed to load history for room {}: {}", room_code, e);
+                Vec::new()
+            });
+        room.start_reading(client_id, history.len());
+        let others = room.others(client_id);
+        let unread = room.unread_count(client_id);
         self.client_rooms.insert(client_id, room_code.clone());
 
         info!("Client {} joined room {}", client_id, room_code);
 
-        // Get host name
+        // Get host name and, for group rooms, the full member list
         let host_name = self
             .clients
             .get(&host_id)
             .and_then(|c| c.username.clone());
+        let members = if is_group {
+            self.member_names(&room_code)
+        } else {
+            Vec::new()
+        };
 
         // Notify joiner
-        let _ = client
-            .send(ServerMessage::RoomJoined {
-                room_code: room_code.to_string(),
-                partner: host_name,
-            })
-            .await;
-
-        // Notify host
-        if let Some(host) = self.clients.get(&host_id) {
-            let guest_name = client.username.clone().unwrap_or_default();
-            let _ = host
-                .send(ServerMessage::PartnerJoined {
-                    username: guest_name,
-                })
-                .await;
-        }
+        let _ = client.send(ServerMessage::RoomJoined {
+            room_code: room_code.to_string(),
+            partner: host_name,
+            members: members.clone(),
+            history,
+            unread,
+        });
+
+        // Notify existing members
+        let username = client.display_name().to_string();
+        let notice = if is_group {
+            ServerMessage::MemberJoined { username, members }
+        } else {
+            ServerMessage::PartnerJoined { username }
+        };
+        self.broadcast(&others, notice);
+        self.leave_queue(client_id);
     }
 
     /// Handle chat message
-    async fn handle_chat(&mut self, client_id: ClientId, content: String) {
+    ///
+    /// The sender gets a `ChatAck` once the message is recorded and
+    /// relayed. A retry with an already acknowledged `client_msg_id` is
+    /// acknowledged again without being delivered twice. A reply carries
+    /// a quote of its (recent, undeleted) target.
+    fn handle_chat(
+        &mut self,
+        client_id: ClientId,
+        content: String,
+        client_msg_id: Option<String>,
+        reply_to: Option<MessageId>,
+    ) {
         let Some(client) = self.clients.get_mut(&client_id) else {
             return;
         };
 
         // Check if in a room
         let Some(room_code) = self.client_rooms.get(&client_id) else {
-            let _ = client.send(AppError::NotInRoom.into()).await;
+            let _ = client.send(AppError::NotInRoom.into());
             return;
         };
 
+        if let Some(ack) = client_msg_id.as_deref().and_then(|id| client.find_ack(id)) {
+            debug!("Client {} resent an acknowledged message", client_id);
+            let _ = client.send(ack);
+            return;
+        }
+
         let room_code = room_code.clone();
 
+        // Quote the message being replied to
+        let reply_to = match reply_to.map(|id| self.quote(client_id, id)).transpose() {
+            Ok(reply_to) => reply_to,
+            Err(e) => {
+                if let Some(client) = self.clients.get(&client_id) {
+                    let _ = client.send(e.into());
+                }
+                return;
+            }
+        };
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+
         // Get sender name and clear typing status
         let sender_name = client.display_name().to_string();
         let was_typing = client.is_typing;
         client.set_typing(false);
 
-        // Get room and partner
-        let Some(room) = self.rooms.get(&room_code) else {
+        // Assign ID and timestamp, then record in history
+        let message = StoredMessage {
+            message_id: self.next_message_id,
+            from: sender_name,
+            content,
+            server_ts: history::now_ms(),
+            edited_ts: None,
+            deleted: false,
+            reply_to,
+        };
+        self.next_message_id = self.next_message_id.next();
+        if let Some(room) = self.rooms.get_mut(&room_code) {
+            room.record_message(message.message_id, client_id);
+        }
+
+        if let Err(e) = self.store.append(&room_code, message.clone()) {
+            error!("Failed to record message in room {}: {}", room_code, e);
+        }
+
+        // Fan out to every other member
+        let others = self.room_others(client_id, &room_code);
+
+        // Send stop typing if was typing
+        if was_typing {
+            self.broadcast(&others, ServerMessage::PartnerStopTyping);
+        }
+
+        let relayed = message.content.len() * others.len();
+        metrics().chat_bytes_relayed.inc_by(relayed as u64);
+
+        self.broadcast(
+            &others,
+            ServerMessage::Chat {
+                message_id: message.message_id,
+                from: message.from,
+                content: message.content,
+                server_ts: message.server_ts,
+                reply_to: message.reply_to,
+            },
+        );
+
+        if let Some(client) = self.clients.get_mut(&client_id) {
+            let ack = client.record_ack(client_msg_id, message.message_id, message.server_ts);
+            let _ = client.send(ack);
+        }
+    }
+
+    /// Handle a read receipt
+    ///
+    /// Moves the client's read position forward and tells the other
+    /// members. Receipts that don't move it are dropped silently.
+    fn handle_mark_read(&mut self, client_id: ClientId, up_to_message_id: MessageId) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        let Some(room_code) = self.client_rooms.get(&client_id) else {
+            let _ = client.send(AppError::NotInRoom.into());
+            return;
+        };
+        let Some(room) = self.rooms.get_mut(room_code) else {
+            return;
+        };
+
+        let Some(up_to_message_id) = room.mark_read(client_id, up_to_message_id) else {
+            return;
+        };
+        let others = room.others(client_id);
+        let username = client.display_name().to_string();
+        self.broadcast(
+            &others,
+            ServerMessage::PartnerRead {
+                username,
+                up_to_message_id,
+            },
+        );
+    }
+
+    /// Handle an edit (`Some` content) or delete (`None`) of a message
+    ///
+    /// Only the author of a recent message in their current room may
+    /// change it. Every member, the author included, is told of the
+    /// change; history keeps the new content or a tombstone.
+    fn handle_edit_message(
+        &mut self,
+        client_id: ClientId,
+        message_id: MessageId,
+        content: Option<String>,
+    ) {
+        let Some(client) = self.clients.get(&client_id) else {
             return;
         };
+        let (room_code, author, stored) = match self.find_message(client_id, message_id) {
+            Ok(found) => found,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+        if author != client_id {
+            let _ = client.send(AppError::NotMessageAuthor.into());
+            return;
+        }
 
-        let Some(partner_id) = room.get_partner(client_id) else {
-            return; // No partner to send to
+        let Some(room) = self.rooms.get_mut(&room_code) else {
+            return;
+        };
+        if content.is_none() {
+            room.delete_message(message_id);
+        }
+        let mut members = room.others(client_id);
+        members.push(client_id);
+        let now = history::now_ms();
+        let event = match &content {
+            Some(content) => ServerMessage::MessageEdited {
+                message_id,
+                content: content.clone(),
+                edited_ts: now,
+            },
+            None => ServerMessage::MessageDeleted { message_id },
         };
 
-        // Send to partner
-        if let Some(partner) = self.clients.get(&partner_id) {
-            // Send stop typing if was typing
-            if was_typing {
-                let _ = partner.send(ServerMessage::PartnerStopTyping).await;
+        // Older messages may have left the store; the event still goes out
+        if let Some(mut message) = stored {
+            match content {
+                Some(content) => {
+                    message.content = content;
+                    message.edited_ts = Some(now);
+                }
+                None => {
+                    message.content.clear();
+                    message.deleted = true;
+                }
+            }
+            if let Err(e) = self.store.update(&room_code, message) {
+                error!("Failed to update message in room {}: {}", room_code, e);
             }
+        }
+
+        self.broadcast(&members, event);
+    }
+
+    /// Handle adding (`added`) or removing an emoji reaction
+    ///
+    /// Every member, the reactor included, gets the emoji's new count.
+    /// Repeating a reaction or removing a missing one changes nothing and
+    /// is not relayed.
+    fn handle_reaction(
+        &mut self,
+        client_id: ClientId,
+        message_id: MessageId,
+        emoji: String,
+        added: bool,
+    ) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        // Removing needs no checks; reactions of deleted messages are gone
+        let result = if added {
+            reaction::check_emoji(&emoji)
+                .and_then(|()| self.find_message(client_id, message_id))
+                .map(|(room_code, _, _)| room_code)
+        } else {
+            self.client_rooms
+                .get(&client_id)
+                .cloned()
+                .ok_or(AppError::NotInRoom)
+        };
+        let room_code = match result {
+            Ok(room_code) => room_code,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+        let username = client.display_name().to_string();
 
-            let _ = partner
-                .send(ServerMessage::Chat {
-                    from: sender_name,
-                    content,
-                })
-                .await;
+        let Some(room) = self.rooms.get_mut(&room_code) else {
+            return;
+        };
+        let count = if added {
+            room.react(message_id, client_id, &emoji)
+        } else {
+            Ok(room.unreact(message_id, client_id, &emoji))
+        };
+        let count = match count {
+            Ok(Some(count)) => count,
+            Ok(None) => return,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+        let mut members = room.others(client_id);
+        members.push(client_id);
+
+        self.broadcast(
+            &members,
+            ServerMessage::ReactionUpdated {
+                message_id,
+                emoji,
+                username,
+                added,
+                count,
+            },
+        );
+    }
+
+    /// Helper: Quote a message the client is replying to
+    ///
+    /// The message must be recent, undeleted and still in the history.
+    fn quote(&self, client_id: ClientId, message_id: MessageId) -> Result<ReplyTo, AppError> {
+        match self.find_message(client_id, message_id) {
+            Ok((_, _, Some(message))) => Ok(ReplyTo::quote(&message)),
+            Ok((_, _, None)) | Err(AppError::MessageNotFound(_)) => {
+                Err(AppError::ReplyNotFound(message_id))
+            }
+            Err(e) => Err(e),
         }
     }
 
+    /// Helper: Find a recent, undeleted message in the client's room
+    ///
+    /// Returns the room, the message's author and the stored message if
+    /// the history backend still has it.
+    fn find_message(
+        &self,
+        client_id: ClientId,
+        message_id: MessageId,
+    ) -> Result<(RoomCode, ClientId, Option<StoredMessage>), AppError> {
+        let room_code = self
+            .client_rooms
+            .get(&client_id)
+            .ok_or(AppError::NotInRoom)?;
+        let author = self
+            .rooms
+            .get(room_code)
+            .and_then(|room| room.author_of(message_id))
+            .ok_or(AppError::MessageNotFound(message_id))?;
+
+        let stored = match self.store.get(room_code, message_id) {
+            Ok(stored) => stored,
+            Err(e) => {
+                error!("Failed to load message from room {}: {}", room_code, e);
+                None
+            }
+        };
+        if stored.as_ref().is_some_and(|m| m.deleted) {
+            return Err(AppError::MessageNotFound(message_id));
+        }
+        Ok((room_code.clone(), author, stored))
+    }
+
+    /// Handle history paging request
+    fn handle_fetch_history(
+        &mut self,
+        client_id: ClientId,
+        before: Option<MessageId>,
+        limit: Option<usize>,
+    ) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+
+        // Check if in a room
+        let Some(room_code) = self.client_rooms.get(&client_id) else {
+            let _ = client.send(AppError::NotInRoom.into());
+            return;
+        };
+
+        // Fetch one extra message to learn whether older ones remain
+        let limit = limit
+            .unwrap_or(self.config.history.replay_limit)
+            .clamp(1, self.config.history.max_fetch_limit);
+        let mut messages = match self.store.before(room_code, before, limit + 1) {
+            Ok(messages) => messages,
+            Err(e) => {
+                error!("Failed to load history for room {}: {}", room_code, e);
+                Vec::new()
+            }
+        };
+        let has_more = messages.len() > limit;
+        if has_more {
+            messages.remove(0);
+        }
+
+        let _ = client.send(ServerMessage::History { messages, has_more });
+    }
+
     /// Handle typing indicator start
-    async fn handle_typing(&mut self, client_id: ClientId) {
+    fn handle_typing(&mut self, client_id: ClientId) {
         let Some(client) = self.clients.get_mut(&client_id) else {
             return;
         };
 
         // Check if in a room
         let Some(room_code) = self.client_rooms.get(&client_id) else {
-            let _ = client.send(AppError::NotInRoom.into()).await;
+            let _ = client.send(AppError::NotInRoom.into());
             return;
         };
 
@@ -349,16 +1484,13 @@ impl ChatServer {
 
         client.set_typing(true);
 
-        // Notify partner
-        if let Some(partner_id) = self.get_partner(client_id, &room_code) {
-            if let Some(partner) = self.clients.get(&partner_id) {
-                let _ = partner.send(ServerMessage::PartnerTyping).await;
-            }
-        }
+        // Notify other members
+        let others = self.room_others(client_id, &room_code);
+        self.broadcast(&others, ServerMessage::PartnerTyping);
     }
 
     /// Handle typing indicator stop
-    async fn handle_stop_typing(&mut self, client_id: ClientId) {
+    fn handle_stop_typing(&mut self, client_id: ClientId) {
         let Some(client) = self.clients.get_mut(&client_id) else {
             return;
         };
@@ -377,58 +1509,257 @@ impl ChatServer {
 
         client.set_typing(false);
 
-        // Notify partner
-        if let Some(partner_id) = self.get_partner(client_id, &room_code) {
-            if let Some(partner) = self.clients.get(&partner_id) {
-                let _ = partner.send(ServerMessage::PartnerStopTyping).await;
-            }
-        }
+        // Notify other members
+        let others = self.room_others(client_id, &room_code);
+        self.broadcast(&others, ServerMessage::PartnerStopTyping);
     }
 
     /// Handle voluntary room leaving
-    async fn handle_leave_room(&mut self, client_id: ClientId) {
+    fn handle_leave_room(&mut self, client_id: ClientId) {
         let Some(client) = self.clients.get(&client_id) else {
             return;
         };
 
         // Check if in a room
         let Some(room_code) = self.client_rooms.remove(&client_id) else {
-            let _ = client.send(AppError::NotInRoom.into()).await;
+            let _ = client.send(AppError::NotInRoom.into());
             return;
         };
 
         info!("Client {} left room {}", client_id, room_code);
 
-        self.remove_client_from_room(client_id, &room_code).await;
+        self.remove_client_from_room(client_id, &room_code);
+    }
+
+    /// Handle a request for a random partner
+    ///
+    /// Pairs the client with the longest-waiting compatible client in a
+    /// new 1:1 room, or queues them until one comes along.
+    fn handle_find_partner(&mut self, client_id: ClientId, tags: Vec<String>) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        if !client.has_username() {
+            let _ = client.send(AppError::UsernameRequired.into());
+            return;
+        }
+        if self.client_rooms.contains_key(&client_id) {
+            let _ = client.send(AppError::AlreadyInRoom.into());
+            return;
+        }
+        if self.match_queue.position(client_id).is_some() {
+            let _ = client.send(AppError::AlreadySearching.into());
+            return;
+        }
+        let tags = match matchmaking::normalize_tags(tags) {
+            Ok(tags) => tags,
+            Err(e) => {
+                let _ = client.send(e.into());
+                return;
+            }
+        };
+
+        match self.match_queue.find(client_id, tags) {
+            Search::Waiting { position } => {
+                info!("Client {} is waiting for a partner (#{})", client_id, position);
+                let _ = client.send(ServerMessage::QueuePosition { position });
+            }
+            Search::Matched { partner, vacated } => {
+                self.send_queue_positions(vacated);
+                self.open_match(partner, client_id);
+            }
+        }
+    }
+
+    /// Handle leaving the random-partner queue
+    fn handle_cancel_find(&mut self, client_id: ClientId) {
+        let Some(client) = self.clients.get(&client_id) else {
+            return;
+        };
+        if self.match_queue.position(client_id).is_none() {
+            let _ = client.send(AppError::NotSearching.into());
+            return;
+        }
+        let _ = client.send(ServerMessage::FindCancelled);
+        self.leave_queue(client_id);
+    }
+
+    /// Helper: Put two matched clients in a new 1:1 room
+    ///
+    /// `waiter` is the one who was queued, so they become the host.
+    fn open_match(&mut self, waiter: ClientId, newcomer: ClientId) {
+        let room_code = match free_room_code(self.codes.as_mut(), &self.rooms) {
+            Ok(room_code) => room_code,
+            Err(e) => {
+                let msg: ServerMessage = e.into();
+                self.broadcast(&[waiter, newcomer], msg);
+                return;
+            }
+        };
+        if let Err(e) = self.store.remove_room(&room_code) {
+            error!("Failed to clear history for room {}: {}", room_code, e);
+        }
+
+        let mut room = Room::new(room_code.clone(), waiter);
+        room.add_member(newcomer);
+        self.rooms.insert(room_code.clone(), room);
+        self.client_rooms.insert(waiter, room_code.clone());
+        self.client_rooms.insert(newcomer, room_code.clone());
+
+        info!(
+            "Matched clients {} and {} in room {}",
+            waiter, newcomer, room_code
+        );
+
+        for (client_id, partner_id) in [(waiter, newcomer), (newcomer, waiter)] {
+            let partner = self
+                .clients
+                .get(&partner_id)
+                .map(|c| c.display_name().to_string())
+                .unwrap_or_default();
+            if let Some(client) = self.clients.get(&client_id) {
+                let _ = client.send(ServerMessage::Matched {
+                    room_code: room_code.to_string(),
+                    partner,
+                });
+            }
+        }
+    }
+
+    /// Helper: Take a client out of the partner queue if they are in it
+    fn leave_queue(&mut self, client_id: ClientId) {
+        if let Some(position) = self.match_queue.remove(client_id) {
+            debug!("Client {} left the partner queue", client_id);
+            self.send_queue_positions(position);
+        }
+    }
+
+    /// Helper: Tell waiting clients from `position` on where they now stand
+    fn send_queue_positions(&self, position: usize) {
+        for (client_id, position) in self.match_queue.from_position(position) {
+            if let Some(client) = self.clients.get(&client_id) {
+                let _ = client.send(ServerMessage::QueuePosition { position });
+            }
+        }
     }
 
     /// Helper: Remove a client from their room and handle cleanup
-    async fn remove_client_from_room(&mut self, client_id: ClientId, room_code: &RoomCode) {
+    fn remove_client_from_room(&mut self, client_id: ClientId, room_code: &RoomCode) {
         let Some(room) = self.rooms.get_mut(room_code) else {
             return;
         };
 
-        // Get partner before removing
-        let partner_id = room.get_partner(client_id);
+        // Get remaining members before removing
+        let others = room.others(client_id);
+        let is_group = room.is_group();
 
         // Remove client from room
         let should_delete = room.remove_client(client_id);
 
         if should_delete {
-            self.rooms.remove(room_code);
+            if let Some(room) = self.rooms.remove(room_code) {
+                metrics()
+                    .room_lifetime
+                    .observe(room.created_at.elapsed().as_secs_f64());
+            }
+            if let Err(e) = self.store.remove_room(room_code) {
+                error!("Failed to clear history for room {}: {}", room_code, e);
+            }
             debug!("Room {} deleted (empty)", room_code);
+            return;
         }
 
-        // Notify partner
-        if let Some(partner_id) = partner_id {
-            if let Some(partner) = self.clients.get(&partner_id) {
-                let _ = partner.send(ServerMessage::PartnerLeft).await;
+        // Notify remaining members
+        let notice = if is_group {
+            let username = self
+                .clients
+                .get(&client_id)
+                .map(|c| c.display_name().to_string())
+                .unwrap_or_default();
+            ServerMessage::MemberLeft {
+                username,
+                members: self.member_names(room_code),
+            }
+        } else {
+            ServerMessage::PartnerLeft
+        };
+        self.broadcast(&others, notice);
+    }
+
+    /// Helper: Get every other member of a client's room
+    fn room_others(&self, client_id: ClientId, room_code: &RoomCode) -> Vec<ClientId> {
+        self.rooms
+            .get(room_code)
+            .map(|r| r.others(client_id))
+            .unwrap_or_default()
+    }
+
+    /// Helper: Get display names of a room's members in join order
+    fn member_names(&self, room_code: &RoomCode) -> Vec<String> {
+        let Some(room) = self.rooms.get(room_code) else {
+            return Vec::new();
+        };
+        room.members
+            .iter()
+            .filter_map(|id| self.clients.get(id))
+            .map(|c| c.display_name().to_string())
+            .collect()
+    }
+
+    /// Helper: Send a message to each of the given clients
+    fn broadcast(&self, client_ids: &[ClientId], msg: ServerMessage) {
+        for id in client_ids {
+            if let Some(client) = self.clients.get(id) {
+                let _ = client.send(msg.clone());
             }
         }
     }
+}
+
+/// Helper: Find a room the client may take a seat in
+///
+/// Fails if the client is already in a room, or the room is missing or full.
+fn check_joinable<'a>(
+    client_rooms: &HashMap<ClientId, RoomCode>,
+    rooms: &'a mut HashMap<RoomCode, Room>,
+    client_id: ClientId,
+    room_code: &RoomCode,
+) -> Result<&'a mut Room, AppError> {
+    if client_rooms.contains_key(&client_id) {
+        return Err(AppError::AlreadyInRoom);
+    }
+    let room = rooms
+        .get_mut(room_code)
+        .ok_or_else(|| AppError::RoomNotFound(room_code.to_string()))?;
+    if room.is_full() {
+        return Err(AppError::RoomFull);
+    }
+    Ok(room)
+}
+
+/// Helper: Pick a room code no open room uses
+///
+/// Gives up after `MAX_CODE_ATTEMPTS` taken codes, as the configured code
+/// space is then close to full.
+fn free_room_code(
+    codes: &mut dyn RoomCodeGenerator,
+    rooms: &HashMap<RoomCode, Room>,
+) -> Result<RoomCode, AppError> {
+    let code = (0..MAX_CODE_ATTEMPTS)
+        .map(|_| codes.generate())
+        .find(|code| !rooms.contains_key(code));
+    code.ok_or_else(|| {
+        error!("No free room code after {} attempts", MAX_CODE_ATTEMPTS);
+        AppError::RoomCodeUnavailable {
+            attempts: MAX_CODE_ATTEMPTS,
+        }
+    })
+}
 
-    /// Helper: Get partner ID for a client in a room
-    fn get_partner(&self, client_id: ClientId, room_code: &RoomCode) -> Option<ClientId> {
-        self.rooms.get(room_code).and_then(|r| r.get_partner(client_id))
+/// Helper: Sleep until the deadline, or forever if there is none
+pub(crate) async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
     }
 }
@@ -0,0 +1,256 @@
+//! TLS termination (wss://)
+//!
+//! Builds a rustls acceptor from PEM files named in the configuration,
+//! with optional client certificate authentication. The certificate and
+//! key are watched for changes and swapped in without a restart; new
+//! handshakes use the new certificate while open connections are untouched.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio::time::{self, MissedTickBehavior};
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{self, RootCertStore};
+use tokio_rustls::server::TlsStream;
+use tracing::{info, warn};
+
+use crate::error::AppError;
+
+/// Default time between checks for changed certificate files
+pub const DEFAULT_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time a client has to complete the TLS handshake
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether clients must present a certificate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuth {
+    /// Client certificates are not requested
+    #[default]
+    Off,
+    /// Clients may present a certificate; if they do, it must verify
+    Optional,
+    /// Clients must present a certificate signed by `client_ca`
+    Required,
+}
+
+/// TLS settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Serve wss:// instead of plain ws://
+    pub enabled: bool,
+    /// PEM certificate chain, leaf first
+    pub cert: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key: PathBuf,
+    /// Client certificate authentication
+    pub client_auth: ClientAuth,
+    /// PEM bundle of CAs trusted to sign client certificates
+    pub client_ca: PathBuf,
+    /// Time between checks for changed certificate files (zero = never)
+    #[serde(with = "humantime_serde")]
+    pub reload_interval: Duration,
+    /// Time a client has to complete the handshake
+    #[serde(with = "humantime_serde")]
+    pub handshake_timeout: Duration,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert: PathBuf::from("cert.pem"),
+            key: PathBuf::from("key.pem"),
+            client_auth: ClientAuth::default(),
+            client_ca: PathBuf::from("ca.pem"),
+            reload_interval: DEFAULT_RELOAD_INTERVAL,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+}
+
+/// Accepts TLS connections with a reloadable certificate
+///
+/// Cheap to clone; clones share the certificate.
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    inner: tokio_rustls::TlsAcceptor,
+    certs: Arc<ReloadingCert>,
+    handshake_timeout: Duration,
+}
+
+impl TlsAcceptor {
+    /// Load the certificate, key and (if used) client CAs
+    pub fn new(config: &TlsConfig) -> Result<Self, AppError> {
+        let provider = Arc::new(ring::default_provider());
+        let certs = Arc::new(ReloadingCert::load(&config.cert, &config.key, &provider)?);
+
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| AppError::Tls(e.to_string()))?;
+        let builder = match config.client_auth {
+            ClientAuth::Off => builder.with_no_client_auth(),
+            auth => {
+                let roots = Arc::new(load_roots(&config.client_ca)?);
+                let verifier = WebPkiClientVerifier::builder_with_provider(roots, provider);
+                let verifier = match auth {
+                    ClientAuth::Optional => verifier.allow_unauthenticated(),
+                    _ => verifier,
+                };
+                let verifier = verifier
+                    .build()
+                    .map_err(|e| AppError::Tls(format!("{}: {}", config.client_ca.display(), e)))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let server_config = builder.with_cert_resolver(certs.clone());
+
+        Ok(Self {
+            inner: tokio_rustls::TlsAcceptor::from(Arc::new(server_config)),
+            certs,
+            handshake_timeout: config.handshake_timeout,
+        })
+    }
+
+    /// Perform the TLS handshake on an accepted connection
+    pub async fn accept(&self, stream: TcpStream) -> io::Result<TlsStream<TcpStream>> {
+        time::timeout(self.handshake_timeout, self.inner.accept(stream))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"))?
+    }
+
+    /// Reload the certificate and key if either file changed
+    ///
+    /// Returns whether a new certificate was installed. On error the
+    /// current certificate stays in use.
+    pub fn reload(&self) -> Result<bool, AppError> {
+        self.certs.reload()
+    }
+
+    /// Check for changed certificate files every `interval`, forever
+    pub async fn watch(self, interval: Duration) {
+        let mut timer = time::interval(interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        timer.tick().await;
+        loop {
+            timer.tick().await;
+            match self.reload() {
+                Ok(true) => info!("Reloaded TLS certificate"),
+                Ok(false) => {}
+                Err(e) => warn!("Keeping the current TLS certificate: {}", e),
+            }
+        }
+    }
+}
+
+/// Certificate resolver whose certificate can be swapped at runtime
+#[derive(Debug)]
+struct ReloadingCert {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    provider: Arc<CryptoProvider>,
+    current: RwLock<Arc<CertifiedKey>>,
+    /// Modification times of the loaded files
+    loaded: Mutex<(SystemTime, SystemTime)>,
+}
+
+impl ReloadingCert {
+    fn load(
+        cert_path: &Path,
+        key_path: &Path,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<Self, AppError> {
+        let loaded = (modified(cert_path)?, modified(key_path)?);
+        let key = load_certified_key(cert_path, key_path, provider)?;
+        Ok(Self {
+            cert_path: cert_path.to_path_buf(),
+            key_path: key_path.to_path_buf(),
+            provider: provider.clone(),
+            current: RwLock::new(Arc::new(key)),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    fn reload(&self) -> Result<bool, AppError> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let times = (modified(&self.cert_path)?, modified(&self.key_path)?);
+        if times == *loaded {
+            return Ok(false);
+        }
+
+        let key = load_certified_key(&self.cert_path, &self.key_path, &self.provider)?;
+        *self.current.write().unwrap() = Arc::new(key);
+        *loaded = times;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for ReloadingCert {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+/// Helper: Read a certificate chain and its private key
+fn load_certified_key(
+    cert_path: &Path,
+    key_path: &Path,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, AppError> {
+    let certs = load_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| AppError::Tls(format!("{}: {}", key_path.display(), e)))?;
+    let key = provider
+        .key_provider
+        .load_private_key(key)
+        .map_err(|e| AppError::Tls(format!("{}: {}", key_path.display(), e)))?;
+
+    let certified = CertifiedKey::new(certs, key);
+    certified
+        .keys_match()
+        .map_err(|e| AppError::Tls(format!("{}: {}", key_path.display(), e)))?;
+    Ok(certified)
+}
+
+/// Helper: Read every certificate in a PEM file
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, AppError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| AppError::Tls(format!("{}: {}", path.display(), e)))?;
+    if certs.is_empty() {
+        return Err(AppError::Tls(format!(
+            "{}: no certificates found",
+            path.display()
+        )));
+    }
+    Ok(certs)
+}
+
+/// Helper: Read trusted CA certificates
+fn load_roots(path: &Path) -> Result<RootCertStore, AppError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots
+            .add(cert)
+            .map_err(|e| AppError::Tls(format!("{}: {}", path.display(), e)))?;
+    }
+    Ok(roots)
+}
+
+/// Helper: Get a file's modification time
+fn modified(path: &Path) -> Result<SystemTime, AppError> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| AppError::Tls(format!("{}: {}", path.display(), e)))
+}
@@ -2,10 +2,24 @@
 //!
 //! Provides newtype wrappers for type safety:
 //! - `ClientId`: UUID-based unique client identifier
-//! - `RoomCode`: 6-character alphanumeric room code
+//! - `RoomCode`: short human-friendly room code (6 characters by default)
+//! - `MessageId`: server-assigned chat message identifier
+//! - `ResumeToken`: opaque secret for reclaiming a session after a dropped connection
 
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::codes::{self, CrockfordCodes, RoomCodeGenerator};
+
+/// Default number of characters in a generated room code
+pub const DEFAULT_CODE_LENGTH: usize = 6;
+
+/// Shortest configurable room code
+pub const MIN_CODE_LENGTH: usize = 4;
+
+/// Longest configurable room code
+pub const MAX_CODE_LENGTH: usize = 16;
+
 /// Unique client identifier (newtype pattern)
 ///
 /// Wraps a UUID v4 for type-safe client identification.
@@ -32,29 +46,46 @@ impl std::fmt::Display for ClientId {
     }
 }
 
-/// Room code (6-character uppercase alphanumeric)
+/// Room code (uppercase, 6 Crockford base32 characters by default)
 ///
 /// Used to identify and join chat rooms.
-/// Generated randomly or parsed from user input.
+/// Generated by a `RoomCodeGenerator` or parsed from user input.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RoomCode(pub String);
 
 impl RoomCode {
     /// Generate a new random 6-character room code
     pub fn generate() -> Self {
-        use rand::Rng;
-        let code: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(6)
-            .map(char::from)
-            .collect::<String>()
-            .to_uppercase();
-        Self(code)
+        Self::generate_with_length(DEFAULT_CODE_LENGTH)
+    }
+
+    /// Generate a new random room code of the given length
+    pub fn generate_with_length(length: usize) -> Self {
+        CrockfordCodes::new(length).generate()
     }
 
-    /// Create a RoomCode from a string (converts to uppercase)
+    /// Create a RoomCode from user input
+    ///
+    /// Converts to uppercase and reads the look-alikes O as 0 and I or L
+    /// as 1. Whitespace, dashes and underscores grouping the characters
+    /// ("7KQ 2XM") are dropped, except in word-list codes, where they
+    /// become single dashes ("amber crab 42" is "AMBER-CRAB-42").
     pub fn from_string(code: String) -> Self {
-        Self(code.to_uppercase())
+        let code: String = code
+            .to_uppercase()
+            .chars()
+            .map(|c| match c {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            })
+            .collect();
+        let groups: Vec<&str> = code
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter(|group| !group.is_empty())
+            .collect();
+        let separator = if codes::is_word_code(&groups) { "-" } else { "" };
+        Self(groups.join(separator))
     }
 }
 
@@ -64,6 +95,49 @@ impl std::fmt::Display for RoomCode {
     }
 }
 
+/// Opaque session resume token
+///
+/// Issued on connect and presented by a new connection to reclaim
+/// the previous client's ID, username and room seat.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResumeToken(pub String);
+
+impl ResumeToken {
+    /// Generate a new random token
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().simple().to_string())
+    }
+}
+
+impl std::fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Server-assigned chat message identifier
+///
+/// Monotonically increasing across the whole server, so IDs also
+/// order messages within a room. Serialized as a plain number.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct MessageId(pub u64);
+
+impl MessageId {
+    /// Get the ID following this one
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+impl std::fmt::Display for MessageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,10 +149,21 @@ mod tests {
         assert_ne!(id1, id2);
     }
 
+    #[test]
+    fn test_resume_token_unique() {
+        let token1 = ResumeToken::generate();
+        let token2 = ResumeToken::generate();
+        assert_ne!(token1, token2);
+        assert_eq!(token1.0.len(), 32);
+    }
+
     #[test]
     fn test_room_code_length() {
         let code = RoomCode::generate();
         assert_eq!(code.0.len(), 6);
+
+        let code = RoomCode::generate_with_length(10);
+        assert_eq!(code.0.len(), 10);
     }
 
     #[test]
@@ -86,4 +171,31 @@ mod tests {
         let code = RoomCode::from_string("abc123".to_string());
         assert_eq!(code.0, "ABC123");
     }
+
+    #[test]
+    fn test_room_code_normalization() {
+        let code = RoomCode::from_string(" -7kq2xm- \n".to_string());
+        assert_eq!(code.0, "7KQ2XM");
+
+        let code = RoomCode::from_string("oil0".to_string());
+        assert_eq!(code.0, "0110");
+
+        // Grouped codes are read as one
+        let code = RoomCode::from_string("7kq-2xm".to_string());
+        assert_eq!(code.0, "7KQ2XM");
+        let code = RoomCode::from_string("482 915".to_string());
+        assert_eq!(code.0, "482915");
+
+        // Word codes keep one dash between words, whatever was typed
+        let code = RoomCode::from_string("amber-crab-42".to_string());
+        assert_eq!(code.0, "AMBER-CRAB-42");
+        let code = RoomCode::from_string(" amber  crab_-4O ".to_string());
+        assert_eq!(code.0, "AMBER-CRAB-40");
+
+        // Only list words followed by a two-digit number make a word code
+        let code = RoomCode::from_string("amber-crab".to_string());
+        assert_eq!(code.0, "AMBERCRAB");
+        let code = RoomCode::from_string("xyz-42".to_string());
+        assert_eq!(code.0, "XYZ42");
+    }
 }
@@ -0,0 +1,268 @@
+//! Username policy
+//!
+//! Requested usernames are NFC-normalized and trimmed, then checked against
+//! length bounds, allowed character classes, reserved names and mixed-script
+//! look-alikes. Names that look alike share a `skeleton`, which is what the
+//! server compares when usernames must be unique.
+
+use serde::{Deserialize, Serialize};
+use unicode_general_category::{get_general_category, GeneralCategory};
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::{skeleton, MixedScript};
+
+use crate::error::AppError;
+
+/// Default minimum username length (characters)
+pub const DEFAULT_MIN_LENGTH: usize = 1;
+
+/// Default maximum username length (characters)
+pub const DEFAULT_MAX_LENGTH: usize = 32;
+
+/// Names nobody may take by default (compared by skeleton)
+pub const DEFAULT_RESERVED: [&str; 6] = [
+    "admin",
+    "administrator",
+    "moderator",
+    "server",
+    "system",
+    "unknown",
+];
+
+/// Unicode character classes a username may be built from
+///
+/// Control, format, private-use and unassigned characters never qualify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CharClass {
+    /// Letters of any script (L*)
+    Letter,
+    /// Combining marks (M*)
+    Mark,
+    /// Digits and other numbers (N*)
+    Number,
+    /// Punctuation (P*)
+    Punctuation,
+    /// Symbols, including emoji (S*)
+    Symbol,
+    /// The space separator (Zs)
+    Space,
+}
+
+impl CharClass {
+    /// Get the class of a character, if it is usable at all
+    pub fn of(c: char) -> Option<Self> {
+        use GeneralCategory::*;
+        match get_general_category(c) {
+            UppercaseLetter | LowercaseLetter | TitlecaseLetter | ModifierLetter | OtherLetter => {
+                Some(CharClass::Letter)
+            }
+            NonspacingMark | SpacingMark | EnclosingMark => Some(CharClass::Mark),
+            DecimalNumber | LetterNumber | OtherNumber => Some(CharClass::Number),
+            ConnectorPunctuation | DashPunctuation | OpenPunctuation | ClosePunctuation
+            | InitialPunctuation | FinalPunctuation | OtherPunctuation => {
+                Some(CharClass::Punctuation)
+            }
+            MathSymbol | CurrencySymbol | ModifierSymbol | OtherSymbol => Some(CharClass::Symbol),
+            SpaceSeparator => Some(CharClass::Space),
+            _ => None,
+        }
+    }
+}
+
+/// Rules for usernames
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsernamePolicy {
+    /// Shortest allowed name, in characters
+    pub min_length: usize,
+    /// Longest allowed name, in characters
+    pub max_length: usize,
+    /// Character classes a name may use
+    pub allowed: Vec<CharClass>,
+    /// Apply NFC normalization before checking
+    pub normalize: bool,
+    /// Reject names mixing scripts (e.g. Latin "a" with Cyrillic "а")
+    pub reject_mixed_scripts: bool,
+    /// Names (and their look-alikes) nobody may take
+    pub reserved: Vec<String>,
+    /// Only one client at a time may use a name (or a look-alike)
+    pub unique: bool,
+}
+
+impl Default for UsernamePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: DEFAULT_MIN_LENGTH,
+            max_length: DEFAULT_MAX_LENGTH,
+            allowed: vec![
+                CharClass::Letter,
+                CharClass::Mark,
+                CharClass::Number,
+                CharClass::Punctuation,
+                CharClass::Space,
+            ],
+            normalize: true,
+            reject_mixed_scripts: true,
+            reserved: DEFAULT_RESERVED.iter().map(|s| s.to_string()).collect(),
+            unique: false,
+        }
+    }
+}
+
+impl UsernamePolicy {
+    /// Normalize a requested username and check it against the policy
+    ///
+    /// Returns the name as it should be stored and shown.
+    pub fn check(&self, username: &str) -> Result<String, AppError> {
+        let username: String = if self.normalize {
+            username.nfc().collect()
+        } else {
+            username.to_string()
+        };
+        let username = username.trim();
+
+        let length = username.chars().count();
+        if length < self.min_length || length > self.max_length {
+            return Err(AppError::InvalidUsername(format!(
+                "must be {}-{} characters long",
+                self.min_length, self.max_length
+            )));
+        }
+
+        if let Some(c) = username
+            .chars()
+            .find(|&c| !CharClass::of(c).is_some_and(|class| self.allowed.contains(&class)))
+        {
+            return Err(AppError::InvalidUsername(format!(
+                "character U+{:04X} is not allowed",
+                c as u32
+            )));
+        }
+
+        if self.reject_mixed_scripts && !username.is_single_script() {
+            return Err(AppError::InvalidUsername(
+                "mixes characters from different scripts".to_string(),
+            ));
+        }
+
+        let key = Self::skeleton(username);
+        if self.reserved.iter().any(|name| Self::skeleton(name) == key) {
+            return Err(AppError::InvalidUsername(format!(
+                "'{}' is reserved",
+                username
+            )));
+        }
+
+        Ok(username.to_string())
+    }
+
+    /// Key shared by names that look alike, ignoring case
+    pub fn skeleton(username: &str) -> String {
+        skeleton(&username.to_lowercase()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(result: Result<String, AppError>) -> String {
+        match result {
+            Err(AppError::InvalidUsername(reason)) => reason,
+            other => panic!("Expected InvalidUsername, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valid_names_are_trimmed() {
+        let policy = UsernamePolicy::default();
+        assert_eq!(policy.check("  Alice ").unwrap(), "Alice");
+        assert_eq!(policy.check("Zoë-42").unwrap(), "Zoë-42");
+        assert_eq!(policy.check("김철수").unwrap(), "김철수");
+    }
+
+    #[test]
+    fn test_names_are_nfc_normalized() {
+        let policy = UsernamePolicy::default();
+        // "e" followed by a combining acute accent
+        assert_eq!(policy.check("Rene\u{301}").unwrap(), "Ren\u{e9}");
+    }
+
+    #[test]
+    fn test_length_bounds() {
+        let policy = UsernamePolicy {
+            min_length: 2,
+            max_length: 4,
+            ..UsernamePolicy::default()
+        };
+        assert!(reason(policy.check("A")).contains("2-4"));
+        assert!(reason(policy.check("   ")).contains("2-4"));
+        assert!(policy.check("Ålæ").is_ok());
+        assert!(reason(policy.check("Alice")).contains("2-4"));
+    }
+
+    #[test]
+    fn test_disallowed_characters() {
+        let policy = UsernamePolicy::default();
+        assert!(reason(policy.check("Al\u{7}ice")).contains("U+0007"));
+        assert!(reason(policy.check("Al\u{200B}ice")).contains("U+200B"));
+        assert!(reason(policy.check("Alice 😀")).contains("U+1F600"));
+
+        let policy = UsernamePolicy {
+            allowed: vec![CharClass::Letter, CharClass::Symbol],
+            ..UsernamePolicy::default()
+        };
+        assert!(policy.check("Alice😀").is_ok());
+    }
+
+    #[test]
+    fn test_mixed_script_names_are_rejected() {
+        let policy = UsernamePolicy::default();
+        // Cyrillic "а" in an otherwise Latin name
+        assert!(reason(policy.check("\u{430}lice")).contains("scripts"));
+
+        let policy = UsernamePolicy {
+            reject_mixed_scripts: false,
+            ..UsernamePolicy::default()
+        };
+        assert!(policy.check("\u{430}lice").is_ok());
+    }
+
+    #[test]
+    fn test_reserved_names_and_look_alikes() {
+        let policy = UsernamePolicy::default();
+        assert!(reason(policy.check("Admin")).contains("reserved"));
+        assert!(reason(policy.check("SYSTEM")).contains("reserved"));
+
+        let policy = UsernamePolicy {
+            reject_mixed_scripts: false,
+            ..UsernamePolicy::default()
+        };
+        // Cyrillic "ѕ" for "s"
+        assert!(reason(policy.check("\u{455}ystem")).contains("reserved"));
+    }
+
+    #[test]
+    fn test_skeleton_matches_look_alikes() {
+        assert_eq!(
+            UsernamePolicy::skeleton("Alice"),
+            UsernamePolicy::skeleton("alice")
+        );
+        assert_eq!(
+            UsernamePolicy::skeleton("ALICE"),
+            UsernamePolicy::skeleton("alice")
+        );
+        assert_eq!(
+            UsernamePolicy::skeleton("\u{410}lice"),
+            UsernamePolicy::skeleton("alice")
+        );
+        assert_eq!(
+            UsernamePolicy::skeleton("rn"),
+            UsernamePolicy::skeleton("m")
+        );
+        assert_ne!(
+            UsernamePolicy::skeleton("Alice"),
+            UsernamePolicy::skeleton("Alina")
+        );
+    }
+}
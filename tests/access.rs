@@ -0,0 +1,165 @@
+//! Integration tests for password-protected and invite-only rooms
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::ServerConfig;
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Send a create_room request and return the new room's code
+async fn create(client: &mut TestClient, request: serde_json::Value) -> String {
+    client.send(request).await;
+    let created = client.expect("room_created").await;
+    created["room_code"].as_str().unwrap().to_string()
+}
+
+/// Ask the host for an invite and return its token
+async fn invite(host: &mut TestClient, single_use: bool) -> String {
+    host.send(json!({ "type": "create_invite", "single_use": single_use }))
+        .await;
+    let created = host.expect("invite_created").await;
+    assert_eq!(created["single_use"], single_use);
+    created["invite"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_password_room() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice
+        .send(json!({ "type": "create_room", "password": "hunter2" }))
+        .await;
+    let created = alice.expect("room_created").await;
+    assert_eq!(created["password_protected"], true);
+    assert!(created.get("invite_only").is_none());
+    let room_code = created["room_code"].as_str().unwrap();
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.send(json!({ "type": "join_room", "room_code": room_code }))
+        .await;
+    let error = bob.expect("error").await;
+    assert_eq!(error["code"], "wrong_password");
+    assert_eq!(error["field"], "password");
+
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "password": "hunter3" }))
+        .await;
+    assert_eq!(bob.expect("error").await["code"], "wrong_password");
+
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "password": "hunter2" }))
+        .await;
+    bob.expect("room_joined").await;
+    assert_eq!(alice.expect("partner_joined").await["username"], "Bob");
+}
+
+#[tokio::test]
+async fn test_invite_only_room() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = create(
+        &mut alice,
+        json!({ "type": "create_room", "invite_only": true }),
+    )
+    .await;
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.send(json!({ "type": "join_room", "room_code": room_code }))
+        .await;
+    let error = bob.expect("error").await;
+    assert_eq!(error["code"], "invite_invalid");
+    assert_eq!(error["field"], "invite");
+
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "invite": "made-up" }))
+        .await;
+    assert_eq!(bob.expect("error").await["code"], "invite_invalid");
+
+    let token = invite(&mut alice, false).await;
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "invite": token }))
+        .await;
+    bob.expect("room_joined").await;
+}
+
+#[tokio::test]
+async fn test_invite_skips_password() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = create(
+        &mut alice,
+        json!({ "type": "create_room", "password": "hunter2", "capacity": 3 }),
+    )
+    .await;
+    let token = invite(&mut alice, true).await;
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "invite": token }))
+        .await;
+    bob.expect("room_joined").await;
+
+    // Single-use invites are spent by the first join
+    let mut carol = TestClient::named(addr, "Carol").await;
+    carol
+        .send(json!({ "type": "join_room", "room_code": room_code, "invite": token }))
+        .await;
+    assert_eq!(carol.expect("error").await["code"], "invite_invalid");
+}
+
+#[tokio::test]
+async fn test_only_host_creates_invites() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+
+    bob.send(json!({ "type": "create_invite" })).await;
+    assert_eq!(bob.expect("error").await["code"], "not_room_host");
+
+    let mut carol = TestClient::named(addr, "Carol").await;
+    carol.send(json!({ "type": "create_invite" })).await;
+    assert_eq!(carol.expect("error").await["code"], "not_in_room");
+}
+
+#[tokio::test]
+async fn test_join_attempts_are_throttled() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = create(
+        &mut alice,
+        json!({ "type": "create_room", "password": "hunter2" }),
+    )
+    .await;
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    for _ in 0..5 {
+        bob.send(json!({ "type": "join_room", "room_code": room_code, "password": "guess" }))
+            .await;
+        assert_eq!(bob.expect("error").await["code"], "wrong_password");
+    }
+
+    // Even the right password is refused until attempts refill
+    bob.send(json!({ "type": "join_room", "room_code": room_code, "password": "hunter2" }))
+        .await;
+    let error = bob.expect("error").await;
+    assert_eq!(error["code"], "rate_limited");
+    assert!(error["retry_after_ms"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_password_work_is_capped() {
+    let mut config = ServerConfig::default();
+    config.rooms.max_password_tasks = 1;
+    let addr = spawn_server_with(config).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+
+    let request = json!({ "type": "create_room", "password": "hunter2" });
+    alice.send(request.clone()).await;
+    bob.send(request.clone()).await;
+    let error = bob.expect("error").await;
+    assert_eq!(error["code"], "rate_limited");
+    assert!(error["retry_after_ms"].as_u64().unwrap() > 0);
+
+    // The slot is free again once Alice's password is hashed
+    alice.expect("room_created").await;
+    create(&mut bob, request).await;
+}
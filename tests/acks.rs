@@ -0,0 +1,107 @@
+//! Integration tests for chat acknowledgements and retry dedupe
+
+mod common;
+
+use serde_json::json;
+
+use common::{spawn_server, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob)
+}
+
+#[tokio::test]
+async fn test_sender_gets_ack_matching_delivered_message() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    alice
+        .send(json!({ "type": "chat", "content": "hi", "client_msg_id": "m-1" }))
+        .await;
+    let ack = alice.expect("chat_ack").await;
+    let chat = bob.expect("chat").await;
+    assert_eq!(ack["client_msg_id"], "m-1");
+    assert_eq!(ack["message_id"], chat["message_id"]);
+    assert_eq!(ack["server_ts"], chat["server_ts"]);
+    assert!(chat.get("client_msg_id").is_none());
+
+    // Without a client_msg_id the ack still carries the id and time
+    alice
+        .send(json!({ "type": "chat", "content": "again" }))
+        .await;
+    let ack = alice.expect("chat_ack").await;
+    assert!(ack.get("client_msg_id").is_none());
+    assert!(ack["message_id"].as_u64() > chat["message_id"].as_u64());
+}
+
+#[tokio::test]
+async fn test_retried_message_is_not_delivered_twice() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let msg = json!({ "type": "chat", "content": "hi", "client_msg_id": "m-1" });
+    alice.send(msg.clone()).await;
+    let first = alice.expect("chat_ack").await;
+    alice.send(msg).await;
+    let second = alice.expect("chat_ack").await;
+    assert_eq!(first, second);
+
+    // Bob sees "hi" once, then the next message
+    alice
+        .send(json!({ "type": "chat", "content": "next", "client_msg_id": "m-2" }))
+        .await;
+    assert_eq!(bob.expect("chat").await["content"], "hi");
+    assert_eq!(bob.expect("chat").await["content"], "next");
+}
+
+#[tokio::test]
+async fn test_retry_after_resume_is_deduplicated() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let msg = json!({ "type": "chat", "content": "hi", "client_msg_id": "m-1" });
+    alice.send(msg.clone()).await;
+    let first = alice.expect("chat_ack").await;
+    assert_eq!(bob.expect("chat").await["content"], "hi");
+
+    // The connection drops before Alice sees the ack; she resumes and retries
+    let token = alice.resume_token.clone();
+    drop(alice);
+    bob.expect("partner_reconnecting").await;
+    let mut alice = TestClient::connect(addr).await;
+    alice
+        .send(json!({ "type": "resume", "token": token }))
+        .await;
+    alice.expect("resumed").await;
+
+    alice.send(msg).await;
+    assert_eq!(
+        alice.expect("chat_ack").await["message_id"],
+        first["message_id"]
+    );
+
+    alice
+        .send(json!({ "type": "chat", "content": "next" }))
+        .await;
+    assert_eq!(bob.expect("chat").await["content"], "next");
+}
+
+#[tokio::test]
+async fn test_oversized_client_msg_id_is_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _bob) = pair(addr).await;
+
+    let id = "x".repeat(65);
+    alice
+        .send(json!({ "type": "chat", "content": "hi", "client_msg_id": id }))
+        .await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "invalid_message");
+    assert_eq!(error["field"], "client_msg_id");
+}
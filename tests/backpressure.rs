@@ -0,0 +1,142 @@
+//! Regression test: a client that stops reading must not stall the actor
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use chat_server_v1::outbox::{self, OutboxReceiver, OutboxSender};
+use chat_server_v1::{
+    ChatServer, ClientId, DeliveryPolicy, ResumeToken, ServerCommand, ServerConfig, ServerMessage,
+};
+
+const WAIT: Duration = Duration::from_secs(2);
+
+async fn connect(
+    cmd_tx: &mpsc::Sender<ServerCommand>,
+    name: &str,
+    capacity: usize,
+) -> (ClientId, OutboxSender, OutboxReceiver) {
+    let client_id = ClientId::new();
+    let (tx, rx) = outbox::channel(capacity, DeliveryPolicy::DropOldest);
+    cmd_tx
+        .send(ServerCommand::Connect {
+            client_id,
+            sender: tx.clone(),
+            resume_token: ResumeToken::generate(),
+        })
+        .await
+        .unwrap();
+    cmd_tx
+        .send(ServerCommand::SetUsername {
+            client_id,
+            username: name.to_string(),
+        })
+        .await
+        .unwrap();
+    (client_id, tx, rx)
+}
+
+async fn next(rx: &mut OutboxReceiver) -> ServerMessage {
+    timeout(WAIT, rx.recv())
+        .await
+        .expect("timed out waiting for message")
+        .expect("outbox closed")
+}
+
+async fn create_room(
+    cmd_tx: &mpsc::Sender<ServerCommand>,
+    client_id: ClientId,
+    rx: &mut OutboxReceiver,
+) -> String {
+    cmd_tx
+        .send(ServerCommand::CreateRoom {
+            client_id,
+            capacity: None,
+            password: None,
+            invite_only: false,
+        })
+        .await
+        .unwrap();
+    loop {
+        if let ServerMessage::RoomCreated { room_code, .. } = next(rx).await {
+            return room_code;
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_stalled_client_does_not_delay_other_rooms() {
+    let (cmd_tx, cmd_rx) = mpsc::channel(256);
+    tokio::spawn(ChatServer::new(cmd_rx, Arc::new(ServerConfig::default())).run());
+
+    // Room 1: Alice stops reading, Bob floods her
+    let (alice, alice_tx, mut alice_rx) = connect(&cmd_tx, "Alice", 4).await;
+    let (bob, _bob_tx, mut bob_rx) = connect(&cmd_tx, "Bob", 32).await;
+    let room_code = create_room(&cmd_tx, alice, &mut alice_rx).await;
+    cmd_tx
+        .send(ServerCommand::JoinRoom {
+            client_id: bob,
+            room_code,
+            password: None,
+            invite: None,
+        })
+        .await
+        .unwrap();
+    assert!(matches!(
+        next(&mut bob_rx).await,
+        ServerMessage::UsernameSet { .. }
+    ));
+    assert!(matches!(
+        next(&mut bob_rx).await,
+        ServerMessage::RoomJoined { .. }
+    ));
+
+    for i in 0..200 {
+        cmd_tx
+            .send(ServerCommand::Chat {
+                client_id: bob,
+                content: format!("flood {}", i),
+                client_msg_id: None,
+                reply_to: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    // Room 2: Carol and Dave keep chatting normally
+    let (carol, _carol_tx, mut carol_rx) = connect(&cmd_tx, "Carol", 32).await;
+    let (dave, _dave_tx, mut dave_rx) = connect(&cmd_tx, "Dave", 32).await;
+    let room_code = create_room(&cmd_tx, carol, &mut carol_rx).await;
+    cmd_tx
+        .send(ServerCommand::JoinRoom {
+            client_id: dave,
+            room_code,
+            password: None,
+            invite: None,
+        })
+        .await
+        .unwrap();
+    cmd_tx
+        .send(ServerCommand::Chat {
+            client_id: carol,
+            content: "still here".to_string(),
+            client_msg_id: None,
+            reply_to: None,
+        })
+        .await
+        .unwrap();
+
+    loop {
+        if let ServerMessage::Chat { from, content, .. } = next(&mut dave_rx).await {
+            assert_eq!(from, "Carol");
+            assert_eq!(content, "still here");
+            break;
+        }
+    }
+
+    // Alice's queue stayed bounded and overflow was counted
+    assert!(alice_tx.len() <= 4);
+    assert!(alice_tx.dropped_count() > 0);
+}
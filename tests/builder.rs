@@ -0,0 +1,129 @@
+//! Integration tests for the embedding API
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+
+use chat_server_v1::{AppError, ChatServerBuilder, Peer, ServerConfig, ServerHandle, ServerStats};
+use common::{TestClient, WAIT};
+
+/// Build a server and serve it on an ephemeral port
+async fn spawn(config: ServerConfig) -> (ServerHandle, std::net::SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = ChatServerBuilder::new(config).build().unwrap();
+    let server = handle.clone();
+    tokio::spawn(async move { server.serve(listener.into()).await });
+    (handle, addr)
+}
+
+#[tokio::test]
+async fn test_serve_and_stats() {
+    let (handle, addr) = spawn(ServerConfig::default()).await;
+    assert_eq!(handle.stats().await.unwrap(), ServerStats::default());
+
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice.create_room().await;
+    let _bob = TestClient::connect(addr).await;
+
+    let stats = handle.stats().await.unwrap();
+    assert_eq!(stats.clients, 2);
+    assert_eq!(stats.named_clients, 1);
+    assert_eq!(stats.rooms, 1);
+    assert_eq!(stats.full_rooms, 0);
+}
+
+#[tokio::test]
+async fn test_one_server_behind_two_listeners() {
+    let (handle, addr) = spawn(ServerConfig::default()).await;
+    let second = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second_addr = second.local_addr().unwrap();
+    let server = handle.clone();
+    tokio::spawn(async move { server.serve(second.into()).await });
+
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let code = alice.create_room().await;
+    let mut bob = TestClient::named(second_addr, "Bob").await;
+    bob.send(json!({ "type": "join_room", "room_code": code }))
+        .await;
+    bob.expect("room_joined").await;
+
+    alice.send(json!({ "type": "chat", "content": "hi" })).await;
+    assert_eq!(bob.expect("chat").await["content"], "hi");
+}
+
+#[tokio::test]
+async fn test_serve_stream_in_process() {
+    let handle = ChatServerBuilder::new(ServerConfig::default())
+        .build()
+        .unwrap();
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let server = handle.clone();
+    tokio::spawn(async move {
+        server
+            .serve_stream(server_io, Peer::Other("embedded".to_string()))
+            .await
+    });
+
+    let (mut ws, _) = tokio_tungstenite::client_async("ws://localhost/", client_io)
+        .await
+        .unwrap();
+    let frame = timeout(WAIT, futures_util::StreamExt::next(&mut ws))
+        .await
+        .unwrap();
+    assert!(frame
+        .unwrap()
+        .unwrap()
+        .to_text()
+        .unwrap()
+        .contains("connected"));
+    assert_eq!(handle.stats().await.unwrap().clients, 1);
+}
+
+#[tokio::test]
+async fn test_shutdown_notifies_clients_and_stops_serving() {
+    let mut config = ServerConfig::default();
+    config.shutdown.drain_timeout = Duration::from_secs(1);
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = ChatServerBuilder::new(config).build().unwrap();
+    let server = handle.clone();
+    let serving = tokio::spawn(async move { server.serve(listener.into()).await });
+
+    let mut alice = TestClient::named(addr, "Alice").await;
+    timeout(WAIT, handle.shutdown()).await.unwrap();
+    alice.expect("server_shutdown").await;
+
+    // The accept loop returns once its connections have drained
+    timeout(WAIT, serving).await.unwrap().unwrap();
+    assert!(handle.stats().await.is_err());
+
+    // Shutting down again is harmless
+    timeout(WAIT, handle.shutdown()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_cloned_sender_reaches_the_actor() {
+    let handle = ChatServerBuilder::new(ServerConfig::default())
+        .build()
+        .unwrap();
+    let sender = handle.sender();
+    let (reply, rx) = tokio::sync::oneshot::channel();
+    sender
+        .send(chat_server_v1::ServerCommand::Stats { reply })
+        .await
+        .unwrap();
+    assert_eq!(rx.await.unwrap(), ServerStats::default());
+}
+
+#[tokio::test]
+async fn test_build_rejects_invalid_config() {
+    let mut config = ServerConfig::default();
+    config.rooms.reap_interval = Duration::ZERO;
+    let result = ChatServerBuilder::new(config).build();
+    assert!(matches!(result, Err(AppError::Config(_))));
+}
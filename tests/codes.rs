@@ -0,0 +1,78 @@
+//! Integration tests for room code styles and code input normalization
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::{ChatServer, CodeStyle, RoomCode, RoomCodeGenerator, ServerConfig};
+use common::{spawn_custom_server, spawn_server_with, TestClient};
+
+/// Hands out the same code every time
+struct FixedCode;
+
+impl RoomCodeGenerator for FixedCode {
+    fn generate(&mut self) -> RoomCode {
+        RoomCode("SAME01".to_string())
+    }
+}
+
+/// Server settings generating codes of the given style
+fn with_style(code_style: CodeStyle) -> ServerConfig {
+    let mut config = ServerConfig::default();
+    config.rooms.code_style = code_style;
+    config
+}
+
+#[tokio::test]
+async fn test_word_codes_can_be_typed_loosely() {
+    let addr = spawn_server_with(with_style(CodeStyle::Words)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    assert_eq!(room_code.split('-').count(), 3);
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    let typed = format!("  {}-\n", room_code.to_lowercase().replacen('-', " ", 1));
+    let joined = bob.join_room(&typed).await;
+    assert_eq!(joined["room_code"], room_code);
+}
+
+#[tokio::test]
+async fn test_look_alikes_are_mapped() {
+    let addr = spawn_server_with(with_style(CodeStyle::Digits)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    assert!(room_code.chars().all(|c| c.is_ascii_digit()));
+
+    // O for 0 and l for 1, as someone copying the code by hand might type
+    let typed = room_code.replace('0', "O").replace('1', "l");
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&typed).await;
+}
+
+#[tokio::test]
+async fn test_grouped_codes_are_joined() {
+    let addr = spawn_server_with(ServerConfig::default()).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+
+    // Read aloud or copied in halves, e.g. "7kq 2xm"
+    let (first, second) = room_code.split_at(3);
+    let typed = format!("{} {}", first, second).to_lowercase();
+    let mut bob = TestClient::named(addr, "Bob").await;
+    let joined = bob.join_room(&typed).await;
+    assert_eq!(joined["room_code"], room_code);
+}
+
+#[tokio::test]
+async fn test_creation_fails_when_codes_run_out() {
+    let addr = spawn_custom_server(ServerConfig::default(), |rx, config| {
+        ChatServer::new(rx, config).with_code_generator(Box::new(FixedCode))
+    })
+    .await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    assert_eq!(alice.create_room().await, "SAME01");
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.send(json!({ "type": "create_room" })).await;
+    assert_eq!(bob.expect("error").await["code"], "room_code_unavailable");
+}
@@ -0,0 +1,171 @@
+//! Shared helpers for integration tests
+//!
+//! Spins up a real server on an ephemeral port and provides a small
+//! JSON-speaking WebSocket client.
+
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use chat_server_v1::{
+    handle_connection, ChatServer, Peer, RateLimiter, ServerCommand, ServerConfig,
+};
+
+/// How long to wait for any single frame
+pub const WAIT: Duration = Duration::from_secs(2);
+
+/// Start a server with default settings, returning its address
+pub async fn spawn_server() -> SocketAddr {
+    spawn_server_with(ServerConfig::default()).await
+}
+
+/// Start a server with the given settings, returning its address
+pub async fn spawn_server_with(config: ServerConfig) -> SocketAddr {
+    spawn_custom_server(config, ChatServer::new).await
+}
+
+/// Start a server whose ChatServer actor is built by `build`
+pub async fn spawn_custom_server(
+    config: ServerConfig,
+    build: impl FnOnce(mpsc::Receiver<ServerCommand>, Arc<ServerConfig>) -> ChatServer,
+) -> SocketAddr {
+    spawn_controlled_server(config, build).await.addr
+}
+
+/// A running test server that tests can send commands to directly
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub cmd_tx: mpsc::Sender<ServerCommand>,
+    /// The ChatServer actor's task
+    pub actor: JoinHandle<()>,
+}
+
+/// Start a server, keeping a handle on its command channel and actor
+pub async fn spawn_controlled_server(
+    config: ServerConfig,
+    build: impl FnOnce(mpsc::Receiver<ServerCommand>, Arc<ServerConfig>) -> ChatServer,
+) -> TestServer {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (cmd_tx, cmd_rx) = mpsc::channel(config.command_buffer);
+    let config = Arc::new(config);
+
+    let actor = tokio::spawn(build(cmd_rx, config.clone()).run());
+    let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    let accept_tx = cmd_tx.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, peer_addr)) = listener.accept().await {
+            tokio::spawn(handle_connection(
+                stream,
+                Peer::Tcp(peer_addr),
+                accept_tx.clone(),
+                config.clone(),
+                limiter.clone(),
+            ));
+        }
+    });
+
+    TestServer {
+        addr,
+        cmd_tx,
+        actor,
+    }
+}
+
+/// A WebSocket test client speaking the JSON protocol
+pub struct TestClient {
+    pub ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    pub client_id: String,
+    pub resume_token: String,
+}
+
+impl TestClient {
+    /// Connect and consume the `connected` greeting
+    pub async fn connect(addr: SocketAddr) -> Self {
+        let (ws, _) = connect_async(format!("ws://{}", addr)).await.unwrap();
+        let mut client = Self {
+            ws,
+            client_id: String::new(),
+            resume_token: String::new(),
+        };
+        let connected = client.expect("connected").await;
+        client.client_id = connected["client_id"].as_str().unwrap().to_string();
+        client.resume_token = connected["resume_token"].as_str().unwrap().to_string();
+        client
+    }
+
+    /// Connect and set a username
+    pub async fn named(addr: SocketAddr, username: &str) -> Self {
+        let mut client = Self::connect(addr).await;
+        client
+            .send(serde_json::json!({ "type": "set_username", "username": username }))
+            .await;
+        client.expect("username_set").await;
+        client
+    }
+
+    /// Send a JSON value as a text frame
+    pub async fn send(&mut self, value: Value) {
+        self.send_raw(Message::Text(value.to_string())).await;
+    }
+
+    /// Send an arbitrary frame
+    pub async fn send_raw(&mut self, msg: Message) {
+        self.ws.send(msg).await.unwrap();
+    }
+
+    /// Receive the next frame
+    pub async fn next_frame(&mut self) -> Option<Message> {
+        timeout(WAIT, self.ws.next())
+            .await
+            .expect("timed out waiting for frame")
+            .map(|r| r.unwrap())
+    }
+
+    /// Receive the next JSON message
+    pub async fn recv(&mut self) -> Value {
+        loop {
+            match self.next_frame().await {
+                Some(Message::Text(text)) => return serde_json::from_str(&text).unwrap(),
+                Some(Message::Ping(_)) | Some(Message::Pong(_)) => continue,
+                other => panic!("Expected text frame, got {:?}", other),
+            }
+        }
+    }
+
+    /// Receive messages until one of the given type arrives
+    pub async fn expect(&mut self, msg_type: &str) -> Value {
+        loop {
+            let msg = self.recv().await;
+            if msg["type"] == msg_type {
+                return msg;
+            }
+        }
+    }
+
+    /// Create a room and return its code
+    pub async fn create_room(&mut self) -> String {
+        self.send(serde_json::json!({ "type": "create_room" }))
+            .await;
+        let created = self.expect("room_created").await;
+        created["room_code"].as_str().unwrap().to_string()
+    }
+
+    /// Join a room by code
+    pub async fn join_room(&mut self, room_code: &str) -> Value {
+        self.send(serde_json::json!({ "type": "join_room", "room_code": room_code }))
+            .await;
+        self.expect("room_joined").await
+    }
+}
@@ -0,0 +1,153 @@
+//! Integration tests for editing and deleting chat messages
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::ServerConfig;
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob)
+}
+
+/// Send a chat and return its server-assigned ID
+async fn chat(client: &mut TestClient, content: &str) -> u64 {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+    client.expect("chat_ack").await["message_id"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_edit_is_relayed_and_stored() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let id = chat(&mut alice, "helo").await;
+    bob.expect("chat").await;
+
+    alice
+        .send(json!({ "type": "edit_message", "message_id": id, "content": "hello" }))
+        .await;
+    let edited = bob.expect("message_edited").await;
+    assert_eq!(edited["message_id"], id);
+    assert_eq!(edited["content"], "hello");
+    assert!(edited["edited_ts"].is_u64());
+    assert_eq!(alice.expect("message_edited").await["message_id"], id);
+
+    bob.send(json!({ "type": "fetch_history" })).await;
+    let history = bob.expect("history").await;
+    let stored = &history["messages"][0];
+    assert_eq!(stored["content"], "hello");
+    assert_eq!(stored["edited_ts"], edited["edited_ts"]);
+}
+
+#[tokio::test]
+async fn test_delete_leaves_tombstone() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let id = chat(&mut alice, "oops").await;
+    bob.send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    assert_eq!(bob.expect("error").await["code"], "not_message_author");
+
+    alice
+        .send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    assert_eq!(bob.expect("message_deleted").await["message_id"], id);
+
+    bob.send(json!({ "type": "fetch_history" })).await;
+    let history = bob.expect("history").await;
+    let stored = &history["messages"][0];
+    assert_eq!(stored["message_id"], id);
+    assert_eq!(stored["deleted"], true);
+    assert_eq!(stored["content"], "");
+
+    // A deleted message can be neither edited nor deleted again
+    alice
+        .send(json!({ "type": "edit_message", "message_id": id, "content": "back" }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+}
+
+#[tokio::test]
+async fn test_delete_is_final_without_history() {
+    let mut config = ServerConfig::default();
+    config.history.capacity = 0;
+    let addr = spawn_server_with(config).await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let id = chat(&mut alice, "oops").await;
+    alice
+        .send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    bob.expect("message_deleted").await;
+
+    // The room remembers the tombstone even though history keeps nothing
+    alice
+        .send(json!({ "type": "edit_message", "message_id": id, "content": "back" }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+    alice
+        .send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+
+    // Bob saw nothing after the delete
+    alice.send(json!({ "type": "typing" })).await;
+    assert_eq!(bob.recv().await["type"], "partner_typing");
+}
+
+#[tokio::test]
+async fn test_only_author_may_edit() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+
+    let id = chat(&mut alice, "mine").await;
+    bob.send(json!({ "type": "edit_message", "message_id": id, "content": "ours" }))
+        .await;
+    let error = bob.expect("error").await;
+    assert_eq!(error["code"], "not_message_author");
+    assert_eq!(error["field"], "message_id");
+}
+
+#[tokio::test]
+async fn test_unknown_message_is_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _bob) = pair(addr).await;
+
+    alice
+        .send(json!({ "type": "edit_message", "message_id": 9999, "content": "hi" }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+
+    let mut carol = TestClient::named(addr, "Carol").await;
+    carol
+        .send(json!({ "type": "delete_message", "message_id": 1 }))
+        .await;
+    assert_eq!(carol.expect("error").await["code"], "not_in_room");
+}
+
+#[tokio::test]
+async fn test_blank_edit_is_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _bob) = pair(addr).await;
+
+    let id = chat(&mut alice, "text").await;
+    alice
+        .send(json!({ "type": "edit_message", "message_id": id, "content": "  " }))
+        .await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "invalid_message");
+    assert_eq!(error["field"], "content");
+}
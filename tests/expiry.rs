@@ -0,0 +1,74 @@
+//! Integration tests for closing expired and idle rooms
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+
+use chat_server_v1::{RoomConfig, ServerConfig};
+use common::{spawn_server_with, TestClient};
+
+/// Server settings with fast room reaping and the given timeouts
+fn with_timeouts(max_lifetime: u64, idle_timeout: u64, guest_timeout: u64) -> ServerConfig {
+    ServerConfig {
+        rooms: RoomConfig {
+            max_lifetime: Duration::from_millis(max_lifetime),
+            idle_timeout: Duration::from_millis(idle_timeout),
+            guest_timeout: Duration::from_millis(guest_timeout),
+            reap_interval: Duration::from_millis(50),
+            ..RoomConfig::default()
+        },
+        ..ServerConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn test_lone_host_room_expires() {
+    let addr = spawn_server_with(with_timeouts(0, 0, 200)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+
+    assert_eq!(alice.expect("room_expired").await["reason"], "no_guest");
+
+    // The code is gone and Alice is free to start over
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.send(json!({ "type": "join_room", "room_code": room_code }))
+        .await;
+    assert_eq!(bob.expect("error").await["code"], "room_not_found");
+    alice.create_room().await;
+}
+
+#[tokio::test]
+async fn test_idle_room_expires_for_everyone() {
+    let addr = spawn_server_with(with_timeouts(0, 300, 0)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+
+    assert_eq!(alice.expect("room_expired").await["reason"], "idle");
+    assert_eq!(bob.expect("room_expired").await["reason"], "idle");
+
+    bob.send(json!({ "type": "chat", "content": "anyone?" }))
+        .await;
+    assert_eq!(bob.expect("error").await["code"], "not_in_room");
+}
+
+#[tokio::test]
+async fn test_busy_room_still_reaches_max_lifetime() {
+    let addr = spawn_server_with(with_timeouts(500, 300, 0)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+
+    // Chatting keeps the room from going idle, but not past its lifetime
+    for _ in 0..5 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        alice
+            .send(json!({ "type": "chat", "content": "still here" }))
+            .await;
+    }
+    assert_eq!(bob.expect("room_expired").await["reason"], "max_lifetime");
+}
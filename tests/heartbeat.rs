@@ -0,0 +1,103 @@
+//! Integration tests for server heartbeats and idle timeouts
+
+mod common;
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use serde_json::json;
+use tokio::time::{sleep, timeout, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+use chat_server_v1::handler::{CLOSE_HEARTBEAT_TIMEOUT, CLOSE_IDLE_TIMEOUT};
+use chat_server_v1::{ConnectionConfig, ServerConfig};
+use common::{spawn_server_with, TestClient};
+
+/// Server settings with the given connection settings
+fn with_connection(connection: ConnectionConfig) -> ServerConfig {
+    ServerConfig {
+        connection,
+        ..ServerConfig::default()
+    }
+}
+
+/// Read frames until the server closes the connection, returning the close code
+async fn close_code(client: &mut TestClient) -> u16 {
+    loop {
+        match client.next_frame().await {
+            Some(Message::Close(Some(frame))) => return frame.code.into(),
+            Some(Message::Close(None)) | None => panic!("Closed without a close code"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_unanswered_pings_close_connection() {
+    let addr = spawn_server_with(with_connection(ConnectionConfig {
+        ping_interval: Duration::from_millis(100),
+        pong_timeout: Duration::from_millis(200),
+        idle_timeout: Duration::ZERO,
+        ..ConnectionConfig::default()
+    }))
+    .await;
+    let mut client = TestClient::connect(addr).await;
+
+    // Not reading means the client never answers the server's pings
+    sleep(Duration::from_millis(600)).await;
+
+    assert_eq!(close_code(&mut client).await, CLOSE_HEARTBEAT_TIMEOUT);
+}
+
+#[tokio::test]
+async fn test_answered_pings_keep_connection_open() {
+    let addr = spawn_server_with(with_connection(ConnectionConfig {
+        ping_interval: Duration::from_millis(50),
+        pong_timeout: Duration::from_millis(100),
+        idle_timeout: Duration::ZERO,
+        ..ConnectionConfig::default()
+    }))
+    .await;
+    let mut client = TestClient::connect(addr).await;
+
+    // Reading lets tungstenite answer each ping with a pong
+    let deadline = Instant::now() + Duration::from_millis(500);
+    let mut pings = 0;
+    while let Ok(frame) = timeout(deadline - Instant::now(), client.ws.next()).await {
+        match frame {
+            Some(Ok(Message::Ping(_))) => pings += 1,
+            other => panic!("Unexpected frame: {:?}", other),
+        }
+    }
+    assert!(pings >= 3, "only {} pings", pings);
+
+    client
+        .send(json!({ "type": "set_username", "username": "Alice" }))
+        .await;
+    client.expect("username_set").await;
+}
+
+#[tokio::test]
+async fn test_idle_client_is_disconnected() {
+    let addr = spawn_server_with(with_connection(ConnectionConfig {
+        idle_timeout: Duration::from_millis(400),
+        ..ConnectionConfig::default()
+    }))
+    .await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+
+    // Receiving messages doesn't count as activity, only sending does
+    sleep(Duration::from_millis(200)).await;
+    bob.send(json!({ "type": "chat", "content": "still there?" }))
+        .await;
+    alice.expect("chat").await;
+
+    assert_eq!(close_code(&mut alice).await, CLOSE_IDLE_TIMEOUT);
+
+    // The timeout goes through the normal disconnect path
+    let notice = bob.expect("partner_reconnecting").await;
+    assert_eq!(notice["username"], "Alice");
+}
@@ -0,0 +1,95 @@
+//! Integration tests for message history replay and paging
+
+mod common;
+
+use serde_json::json;
+
+use common::{spawn_server, TestClient};
+
+#[tokio::test]
+async fn test_late_joiner_receives_history() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+
+    for content in ["first", "second"] {
+        alice
+            .send(json!({ "type": "chat", "content": content }))
+            .await;
+    }
+    // Round-trip so both chats are processed before Bob joins
+    alice.send(json!({ "type": "fetch_history" })).await;
+    alice.expect("history").await;
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    let joined = bob.join_room(&room_code).await;
+
+    let history = joined["history"].as_array().unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0]["from"], "Alice");
+    assert_eq!(history[0]["content"], "first");
+    assert_eq!(history[1]["content"], "second");
+    assert!(history[0]["message_id"].as_u64() < history[1]["message_id"].as_u64());
+    assert!(history[0]["server_ts"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_chat_carries_id_and_timestamp() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+
+    alice.send(json!({ "type": "chat", "content": "hi" })).await;
+    let chat = bob.expect("chat").await;
+    assert!(chat["message_id"].as_u64().unwrap() > 0);
+    assert!(chat["server_ts"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_fetch_history_pages_backwards() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice.create_room().await;
+
+    for i in 1..=5 {
+        alice
+            .send(json!({ "type": "chat", "content": format!("m{}", i) }))
+            .await;
+    }
+
+    alice
+        .send(json!({ "type": "fetch_history", "limit": 2 }))
+        .await;
+    let page = alice.expect("history").await;
+    let messages = page["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0]["content"], "m4");
+    assert_eq!(messages[1]["content"], "m5");
+    assert_eq!(page["has_more"], true);
+
+    let before = messages[0]["message_id"].clone();
+    alice
+        .send(json!({ "type": "fetch_history", "before": before, "limit": 10 }))
+        .await;
+    let page = alice.expect("history").await;
+    let contents: Vec<&str> = page["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["content"].as_str().unwrap())
+        .collect();
+    assert_eq!(contents, vec!["m1", "m2", "m3"]);
+    assert_eq!(page["has_more"], false);
+}
+
+#[tokio::test]
+async fn test_fetch_history_requires_room() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+
+    alice.send(json!({ "type": "fetch_history" })).await;
+    let err = alice.expect("error").await;
+    assert_eq!(err["code"], "not_in_room");
+}
@@ -0,0 +1,120 @@
+//! Integration tests for frame size and chat content limits
+
+mod common;
+
+use serde_json::json;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use chat_server_v1::{ConnectionConfig, ServerConfig};
+use common::{spawn_server_with, TestClient};
+
+/// Server settings with small content and frame limits
+fn small_limits() -> ServerConfig {
+    ServerConfig {
+        connection: ConnectionConfig {
+            max_frame_size: 1024,
+            max_content_chars: 10,
+            max_content_bytes: 20,
+            ..ConnectionConfig::default()
+        },
+        ..ServerConfig::default()
+    }
+}
+
+/// Two named clients sharing a room
+async fn pair(config: ServerConfig) -> (TestClient, TestClient) {
+    let addr = spawn_server_with(config).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob)
+}
+
+async fn chat(client: &mut TestClient, content: &str) {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+}
+
+#[tokio::test]
+async fn test_long_content_is_rejected() {
+    let (mut alice, mut bob) = pair(small_limits()).await;
+
+    // Too many characters, then too many bytes in few characters
+    for content in ["hello world!", "한국어한국어한국어"] {
+        chat(&mut alice, content).await;
+        let error = alice.expect("error").await;
+        assert_eq!(error["code"], "message_too_large", "{:?}", content);
+        assert_eq!(error["max_chars"], 10);
+        assert_eq!(error["max_bytes"], 20);
+        assert_eq!(error["field"], "content");
+    }
+
+    // Content at the limit still goes through
+    chat(&mut alice, "0123456789").await;
+    assert_eq!(bob.expect("chat").await["content"], "0123456789");
+}
+
+#[tokio::test]
+async fn test_escaped_content_fits_smallest_frame() {
+    let mut connection = ConnectionConfig {
+        max_content_bytes: 100,
+        ..ConnectionConfig::default()
+    };
+    connection.max_frame_size = connection.min_frame_size();
+    let config = ServerConfig {
+        connection,
+        ..ServerConfig::default()
+    };
+    config.validate().unwrap();
+    let (mut alice, mut bob) = pair(config).await;
+
+    // Every byte goes over the wire as a six-byte \u0001 escape
+    let client_msg_id = "\u{1}".repeat(64);
+    let too_long = "\u{1}".repeat(101);
+    alice
+        .send(json!({ "type": "chat", "content": too_long, "client_msg_id": client_msg_id }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_too_large");
+
+    let at_limit = "\u{1}".repeat(100);
+    alice
+        .send(json!({ "type": "chat", "content": at_limit, "client_msg_id": client_msg_id }))
+        .await;
+    assert_eq!(bob.expect("chat").await["content"], at_limit);
+}
+
+#[tokio::test]
+async fn test_empty_content_is_rejected() {
+    let (mut alice, mut bob) = pair(small_limits()).await;
+
+    for content in ["", " \n\t "] {
+        chat(&mut alice, content).await;
+        let error = alice.expect("error").await;
+        assert_eq!(error["code"], "invalid_message");
+        assert_eq!(error["field"], "content");
+    }
+
+    chat(&mut alice, "hi").await;
+    assert_eq!(bob.expect("chat").await["content"], "hi");
+}
+
+#[tokio::test]
+async fn test_oversized_frame_closes_connection() {
+    let (mut alice, _bob) = pair(small_limits()).await;
+
+    let content = "x".repeat(2000);
+    chat(&mut alice, &content).await;
+
+    let code = loop {
+        match alice.next_frame().await {
+            Some(Message::Close(Some(frame))) => break frame.code,
+            Some(Message::Close(None)) | None => panic!("Closed without a close code"),
+            _ => continue,
+        }
+    };
+    assert_eq!(code, CloseCode::Size);
+}
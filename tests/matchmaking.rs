@@ -0,0 +1,115 @@
+//! Integration tests for random-partner matchmaking
+
+mod common;
+
+use serde_json::json;
+
+use common::{spawn_server, TestClient};
+
+/// Ask for a partner with the given tags
+async fn find(client: &mut TestClient, tags: &[&str]) {
+    client
+        .send(json!({ "type": "find_partner", "tags": tags }))
+        .await;
+}
+
+/// Wait for the client's next queue position
+async fn position(client: &mut TestClient) -> u64 {
+    client.expect("queue_position").await["position"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_strangers_are_matched() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    find(&mut alice, &[]).await;
+    assert_eq!(position(&mut alice).await, 1);
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    find(&mut bob, &[]).await;
+    let matched = bob.expect("matched").await;
+    assert_eq!(matched["partner"], "Alice");
+    let room_code = matched["room_code"].clone();
+
+    let matched = alice.expect("matched").await;
+    assert_eq!(matched["partner"], "Bob");
+    assert_eq!(matched["room_code"], room_code);
+
+    bob.send(json!({ "type": "chat", "content": "Hi stranger" }))
+        .await;
+    assert_eq!(alice.expect("chat").await["content"], "Hi stranger");
+}
+
+#[tokio::test]
+async fn test_tags_must_overlap() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    find(&mut alice, &["music"]).await;
+    assert_eq!(position(&mut alice).await, 1);
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    find(&mut bob, &["games"]).await;
+    assert_eq!(position(&mut bob).await, 2);
+
+    // Tags are compared case-insensitively
+    let mut carol = TestClient::named(addr, "Carol").await;
+    find(&mut carol, &["films", " GAMES "]).await;
+    assert_eq!(carol.expect("matched").await["partner"], "Bob");
+    assert_eq!(bob.expect("matched").await["partner"], "Carol");
+}
+
+#[tokio::test]
+async fn test_cancel_find() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    find(&mut alice, &[]).await;
+    position(&mut alice).await;
+
+    find(&mut alice, &[]).await;
+    assert_eq!(alice.expect("error").await["code"], "already_searching");
+
+    alice.send(json!({ "type": "cancel_find" })).await;
+    alice.expect("find_cancelled").await;
+    alice.send(json!({ "type": "cancel_find" })).await;
+    assert_eq!(alice.expect("error").await["code"], "not_searching");
+
+    // Nobody is waiting any more, so Bob is queued
+    let mut bob = TestClient::named(addr, "Bob").await;
+    find(&mut bob, &[]).await;
+    assert_eq!(position(&mut bob).await, 1);
+}
+
+#[tokio::test]
+async fn test_disconnect_leaves_queue() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    find(&mut alice, &["music"]).await;
+    position(&mut alice).await;
+
+    let mut bob = TestClient::named(addr, "Bob").await;
+    find(&mut bob, &["games"]).await;
+    assert_eq!(position(&mut bob).await, 2);
+
+    drop(alice);
+    assert_eq!(position(&mut bob).await, 1);
+
+    // Alice's old place can't be matched any more
+    let mut carol = TestClient::named(addr, "Carol").await;
+    find(&mut carol, &["music"]).await;
+    assert_eq!(position(&mut carol).await, 2);
+}
+
+#[tokio::test]
+async fn test_room_members_cannot_search() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice.create_room().await;
+    find(&mut alice, &[]).await;
+    assert_eq!(alice.expect("error").await["code"], "already_in_room");
+
+    let mut bob = TestClient::connect(addr).await;
+    find(&mut bob, &[]).await;
+    assert_eq!(bob.expect("error").await["code"], "username_required");
+}
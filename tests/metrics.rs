@@ -0,0 +1,94 @@
+//! Integration test for the Prometheus metrics endpoint
+
+mod common;
+
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use chat_server_v1::metrics;
+use common::{spawn_server, TestClient};
+
+/// Fetch a path from the metrics endpoint, returning (status line, body)
+async fn get(addr: std::net::SocketAddr, path: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.lines().next().unwrap().to_string(), body.to_string())
+}
+
+/// Value of the sample with exactly this name and label set
+fn sample(text: &str, series: &str) -> f64 {
+    text.lines()
+        .find_map(|line| line.strip_prefix(series)?.strip_prefix(' '))
+        .unwrap_or_else(|| panic!("no sample for {}", series))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_reports_state_and_traffic() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let metrics_addr = listener.local_addr().unwrap();
+    tokio::spawn(metrics::serve(listener));
+
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    let mut carol = TestClient::connect(addr).await;
+
+    alice
+        .send(json!({ "type": "chat", "content": "hello" }))
+        .await;
+    bob.expect("chat").await;
+    carol
+        .send(json!({ "type": "join_room", "room_code": "NOPE00" }))
+        .await;
+    carol.expect("error").await;
+
+    let (status, text) = get(metrics_addr, "/metrics").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+
+    assert_eq!(sample(&text, "chat_connected_clients"), 3.0);
+    assert_eq!(sample(&text, "chat_named_clients"), 2.0);
+    assert_eq!(sample(&text, "chat_active_rooms"), 1.0);
+    assert_eq!(sample(&text, "chat_full_rooms"), 1.0);
+    assert_eq!(sample(&text, r#"chat_commands_total{command="chat"}"#), 1.0);
+    assert_eq!(
+        sample(&text, r#"chat_commands_total{command="join_room"}"#),
+        2.0
+    );
+    assert_eq!(
+        sample(&text, r#"chat_errors_total{code="username_required"}"#),
+        1.0
+    );
+    assert_eq!(sample(&text, "chat_relayed_bytes_total"), 5.0);
+    assert!(
+        sample(
+            &text,
+            r#"chat_command_duration_seconds_count{command="connect"}"#
+        ) >= 3.0
+    );
+
+    // The room's lifetime is recorded when it goes away
+    alice.send(json!({ "type": "leave_room" })).await;
+    bob.expect("partner_left").await;
+    bob.send(json!({ "type": "leave_room" })).await;
+    bob.send(json!({ "type": "fetch_history" })).await;
+    bob.expect("error").await;
+
+    let (_, text) = get(metrics_addr, "/metrics").await;
+    assert_eq!(sample(&text, "chat_active_rooms"), 0.0);
+    assert_eq!(sample(&text, "chat_room_lifetime_seconds_count"), 1.0);
+
+    let (status, _) = get(metrics_addr, "/other").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
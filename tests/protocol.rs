@@ -0,0 +1,81 @@
+//! Integration tests for malformed client frames
+
+mod common;
+
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+
+use common::{spawn_server, TestClient};
+
+async fn expect_invalid(client: &mut TestClient, frame: Message) -> serde_json::Value {
+    client.send_raw(frame).await;
+    let msg = client.recv().await;
+    assert_eq!(msg["type"], "error");
+    assert_eq!(msg["code"], "invalid_message");
+    msg
+}
+
+#[tokio::test]
+async fn test_unknown_type_reports_tag() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    let msg = expect_invalid(&mut client, Message::Text(r#"{"type":"dance"}"#.into())).await;
+    assert_eq!(msg["field"], "type");
+    assert!(msg["message"].as_str().unwrap().contains("dance"));
+}
+
+#[tokio::test]
+async fn test_missing_field_reports_field() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    let msg = expect_invalid(
+        &mut client,
+        Message::Text(r#"{"type":"set_username"}"#.into()),
+    )
+    .await;
+    assert_eq!(msg["field"], "username");
+}
+
+#[tokio::test]
+async fn test_wrong_field_type_reports_field() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    let frame = Message::Text(r#"{"type":"join_room","room_code":123}"#.into());
+    let msg = expect_invalid(&mut client, frame).await;
+    assert_eq!(msg["field"], "room_code");
+}
+
+#[tokio::test]
+async fn test_invalid_json_is_reported() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    let msg = expect_invalid(&mut client, Message::Text("{not json".into())).await;
+    assert!(msg.get("field").is_none());
+}
+
+#[tokio::test]
+async fn test_binary_frame_is_reported() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    expect_invalid(&mut client, Message::Binary(vec![1, 2, 3])).await;
+}
+
+#[tokio::test]
+async fn test_connection_survives_malformed_frame() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    expect_invalid(&mut client, Message::Text("[]".into())).await;
+
+    client
+        .send(json!({ "type": "set_username", "username": "Alice" }))
+        .await;
+    let msg = client.recv().await;
+    assert_eq!(msg["type"], "username_set");
+    assert_eq!(msg["username"], "Alice");
+}
@@ -0,0 +1,134 @@
+//! Integration tests for per-connection and per-session rate limits
+
+mod common;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+
+use chat_server_v1::handler::CLOSE_RATE_LIMITED;
+use chat_server_v1::{RateLimit, RateLimitConfig, ServerConfig};
+use common::{spawn_server_with, TestClient};
+
+/// Server settings allowing two chat messages and the given violations
+fn chat_limit(max_violations: u32) -> ServerConfig {
+    ServerConfig {
+        rate_limit: RateLimitConfig {
+            enabled: true,
+            max_violations,
+            violation_window: Duration::from_secs(60),
+            limits: BTreeMap::from([("chat".to_string(), RateLimit::new(2, 0.1))]),
+        },
+        ..ServerConfig::default()
+    }
+}
+
+async fn chat(client: &mut TestClient, content: &str) {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+}
+
+/// Wait for the server to close the connection and return the close code
+async fn close_code(client: &mut TestClient) -> u16 {
+    loop {
+        match client.next_frame().await {
+            Some(Message::Close(Some(frame))) => return frame.code.into(),
+            Some(Message::Close(None)) | None => panic!("Closed without a close code"),
+            _ => continue,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_excess_messages_are_rate_limited() {
+    let addr = spawn_server_with(chat_limit(10)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+
+    chat(&mut alice, "one").await;
+    chat(&mut alice, "two").await;
+    chat(&mut alice, "three").await;
+
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "rate_limited");
+    assert!(error["retry_after_ms"].as_u64().unwrap() > 0);
+
+    // Only the messages within the limit reach the room
+    assert_eq!(bob.expect("chat").await["content"], "one");
+    assert_eq!(bob.expect("chat").await["content"], "two");
+
+    // Unlimited message types still go through
+    alice.send(json!({ "type": "typing" })).await;
+    bob.expect("partner_typing").await;
+}
+
+#[tokio::test]
+async fn test_session_limit_spans_connections() {
+    let addr = spawn_server_with(chat_limit(10)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice.create_room().await;
+
+    chat(&mut alice, "one").await;
+    chat(&mut alice, "two").await;
+    alice.expect("chat_ack").await;
+    alice.expect("chat_ack").await;
+    let token = alice.resume_token.clone();
+    drop(alice);
+
+    // The new connection has its own tokens, but the session has none left
+    let mut alice = TestClient::connect(addr).await;
+    alice
+        .send(json!({ "type": "resume", "token": token }))
+        .await;
+    alice.expect("resumed").await;
+    chat(&mut alice, "three").await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "rate_limited");
+}
+
+#[tokio::test]
+async fn test_shared_name_does_not_share_limit() {
+    let addr = spawn_server_with(chat_limit(10)).await;
+    let mut first = TestClient::named(addr, "Alice").await;
+    let room_code = first.create_room().await;
+    let mut second = TestClient::named(addr, "Alice").await;
+    second.join_room(&room_code).await;
+
+    chat(&mut first, "one").await;
+    chat(&mut first, "two").await;
+    second.expect("chat").await;
+    second.expect("chat").await;
+
+    // Limits follow sessions, not names
+    chat(&mut second, "three").await;
+    assert_eq!(first.expect("chat").await["content"], "three");
+}
+
+#[tokio::test]
+async fn test_repeated_violations_close_connection() {
+    let addr = spawn_server_with(chat_limit(2)).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice.create_room().await;
+
+    for i in 0..5 {
+        chat(&mut alice, &i.to_string()).await;
+    }
+    assert_eq!(close_code(&mut alice).await, CLOSE_RATE_LIMITED);
+}
+
+#[tokio::test]
+async fn test_malformed_messages_count_as_violations() {
+    let addr = spawn_server_with(chat_limit(2)).await;
+    let mut alice = TestClient::connect(addr).await;
+
+    alice.send_raw(Message::Text("not json".to_string())).await;
+    alice.send_raw(Message::Binary(vec![1, 2, 3])).await;
+    alice.send(json!({ "type": "chat", "content": " " })).await;
+    assert_eq!(close_code(&mut alice).await, CLOSE_RATE_LIMITED);
+}
@@ -0,0 +1,134 @@
+//! Integration tests for emoji reactions
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::reaction::MAX_REACTIONS_PER_MESSAGE;
+use chat_server_v1::{RateLimitConfig, ServerConfig};
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob)
+}
+
+/// Send a chat and return its server-assigned ID
+async fn chat(client: &mut TestClient, content: &str) -> u64 {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+    client.expect("chat_ack").await["message_id"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_reactions_are_counted_and_relayed() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+    let id = chat(&mut alice, "lunch?").await;
+
+    bob.send(json!({ "type": "react", "message_id": id, "emoji": "👍" }))
+        .await;
+    let update = alice.expect("reaction_updated").await;
+    assert_eq!(update["message_id"], id);
+    assert_eq!(update["emoji"], "👍");
+    assert_eq!(update["username"], "Bob");
+    assert_eq!(update["added"], true);
+    assert_eq!(update["count"], 1);
+    assert_eq!(bob.expect("reaction_updated").await["count"], 1);
+
+    alice
+        .send(json!({ "type": "react", "message_id": id, "emoji": "👍" }))
+        .await;
+    assert_eq!(bob.expect("reaction_updated").await["count"], 2);
+    alice.expect("reaction_updated").await;
+
+    bob.send(json!({ "type": "unreact", "message_id": id, "emoji": "👍" }))
+        .await;
+    let update = alice.expect("reaction_updated").await;
+    assert_eq!(update["added"], false);
+    assert_eq!(update["count"], 1);
+}
+
+#[tokio::test]
+async fn test_repeated_reaction_is_not_relayed() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob) = pair(addr).await;
+    let id = chat(&mut alice, "hi").await;
+
+    bob.send(json!({ "type": "react", "message_id": id, "emoji": "🎉" }))
+        .await;
+    alice.expect("reaction_updated").await;
+    bob.send(json!({ "type": "react", "message_id": id, "emoji": "🎉" }))
+        .await;
+    bob.send(json!({ "type": "unreact", "message_id": id, "emoji": "😢" }))
+        .await;
+
+    // The next update Alice sees is for a new emoji
+    bob.send(json!({ "type": "react", "message_id": id, "emoji": "❤️" }))
+        .await;
+    assert_eq!(alice.expect("reaction_updated").await["emoji"], "❤️");
+}
+
+#[tokio::test]
+async fn test_invalid_reactions_are_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _bob) = pair(addr).await;
+    let id = chat(&mut alice, "hi").await;
+
+    alice
+        .send(json!({ "type": "react", "message_id": id, "emoji": "lol" }))
+        .await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "invalid_emoji");
+    assert_eq!(error["field"], "emoji");
+
+    alice
+        .send(json!({ "type": "react", "message_id": 9999, "emoji": "👍" }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+
+    // Deleted messages take no reactions
+    alice
+        .send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    alice.expect("message_deleted").await;
+    alice
+        .send(json!({ "type": "react", "message_id": id, "emoji": "👍" }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "message_not_found");
+}
+
+#[tokio::test]
+async fn test_distinct_reaction_cap() {
+    let addr = spawn_server_with(ServerConfig {
+        rate_limit: RateLimitConfig {
+            enabled: false,
+            ..RateLimitConfig::default()
+        },
+        ..ServerConfig::default()
+    })
+    .await;
+    let (mut alice, _bob) = pair(addr).await;
+    let id = chat(&mut alice, "pick one").await;
+
+    for emoji in ('😀'..).take(MAX_REACTIONS_PER_MESSAGE) {
+        alice
+            .send(json!({ "type": "react", "message_id": id, "emoji": emoji.to_string() }))
+            .await;
+        alice.expect("reaction_updated").await;
+    }
+    alice
+        .send(json!({ "type": "react", "message_id": id, "emoji": "🚀" }))
+        .await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "too_many_reactions");
+    assert_eq!(error["max"], MAX_REACTIONS_PER_MESSAGE);
+}
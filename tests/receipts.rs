@@ -0,0 +1,145 @@
+//! Integration tests for read receipts and unread counts
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::ServerConfig;
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient, String) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob, room_code)
+}
+
+/// Send a chat and return its server-assigned ID
+async fn chat(client: &mut TestClient, content: &str) -> u64 {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+    client.expect("chat_ack").await["message_id"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_mark_read_is_relayed_to_partner() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob, _) = pair(addr).await;
+
+    let first = chat(&mut alice, "one").await;
+    let second = chat(&mut alice, "two").await;
+    bob.expect("chat").await;
+    bob.expect("chat").await;
+
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": first }))
+        .await;
+    let read = alice.expect("partner_read").await;
+    assert_eq!(read["username"], "Bob");
+    assert_eq!(read["up_to_message_id"], first);
+
+    // A later ID than any message resolves to the newest one
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": second + 100 }))
+        .await;
+    assert_eq!(
+        alice.expect("partner_read").await["up_to_message_id"],
+        second
+    );
+}
+
+#[tokio::test]
+async fn test_stale_receipt_is_not_relayed() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob, _) = pair(addr).await;
+
+    let first = chat(&mut alice, "one").await;
+    let second = chat(&mut alice, "two").await;
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": second }))
+        .await;
+    alice.expect("partner_read").await;
+
+    // Going backwards changes nothing; the next receipt Alice sees is Bob's next one
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": first }))
+        .await;
+    let third = chat(&mut alice, "three").await;
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": third }))
+        .await;
+    assert_eq!(
+        alice.expect("partner_read").await["up_to_message_id"],
+        third
+    );
+}
+
+#[tokio::test]
+async fn test_unread_count_on_join() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    chat(&mut alice, "one").await;
+    let second = chat(&mut alice, "two").await;
+
+    // A newcomer has read nothing
+    let mut bob = TestClient::named(addr, "Bob").await;
+    assert_eq!(bob.join_room(&room_code).await["unread"], 2);
+
+    // Bob reads up to "two", leaves, and comes back after "three"
+    bob.send(json!({ "type": "mark_read", "up_to_message_id": second }))
+        .await;
+    alice.expect("partner_read").await;
+    bob.send(json!({ "type": "leave_room" })).await;
+    alice.expect("partner_left").await;
+    chat(&mut alice, "three").await;
+
+    assert_eq!(bob.join_room(&room_code).await["unread"], 1);
+}
+
+#[tokio::test]
+async fn test_unread_count_covers_replay() {
+    let mut config = ServerConfig::default();
+    config.history.replay_limit = 2;
+    let addr = spawn_server_with(config).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    for content in ["one", "two", "three"] {
+        chat(&mut alice, content).await;
+    }
+
+    // Only the replayed messages are unread for a newcomer
+    let mut bob = TestClient::named(addr, "Bob").await;
+    let joined = bob.join_room(&room_code).await;
+    assert_eq!(joined["history"].as_array().unwrap().len(), 2);
+    assert_eq!(joined["unread"], 2);
+}
+
+#[tokio::test]
+async fn test_unread_count_on_resume() {
+    let addr = spawn_server().await;
+    let (mut alice, bob, _) = pair(addr).await;
+
+    let token = bob.resume_token.clone();
+    drop(bob);
+    alice.expect("partner_reconnecting").await;
+    chat(&mut alice, "one").await;
+    chat(&mut alice, "two").await;
+
+    let mut bob = TestClient::connect(addr).await;
+    bob.send(json!({ "type": "resume", "token": token })).await;
+    let resumed = bob.expect("resumed").await;
+    assert_eq!(resumed["unread"], 2);
+}
+
+#[tokio::test]
+async fn test_mark_read_requires_room() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+
+    alice
+        .send(json!({ "type": "mark_read", "up_to_message_id": 1 }))
+        .await;
+    assert_eq!(alice.expect("error").await["code"], "not_in_room");
+}
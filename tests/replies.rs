@@ -0,0 +1,93 @@
+//! Integration tests for replies that quote an earlier message
+
+mod common;
+
+use serde_json::json;
+
+use chat_server_v1::history::REPLY_SNIPPET_CHARS;
+use common::{spawn_server, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient, String) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob, room_code)
+}
+
+/// Send a chat and return its server-assigned ID
+async fn chat(client: &mut TestClient, content: &str) -> u64 {
+    client
+        .send(json!({ "type": "chat", "content": content }))
+        .await;
+    client.expect("chat_ack").await["message_id"]
+        .as_u64()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_reply_carries_quote() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob, room_code) = pair(addr).await;
+
+    let id = chat(&mut alice, "Dinner at 7?").await;
+    bob.send(json!({ "type": "chat", "content": "Sure", "reply_to": id }))
+        .await;
+    bob.expect("chat_ack").await;
+
+    let reply = alice.expect("chat").await;
+    assert_eq!(reply["content"], "Sure");
+    assert_eq!(reply["reply_to"]["message_id"], id);
+    assert_eq!(reply["reply_to"]["from"], "Alice");
+    assert_eq!(reply["reply_to"]["snippet"], "Dinner at 7?");
+
+    // Late joiners see the quote in the replayed history
+    bob.send(json!({ "type": "leave_room" })).await;
+    alice.expect("partner_left").await;
+    let mut carol = TestClient::named(addr, "Carol").await;
+    let joined = carol.join_room(&room_code).await;
+    assert_eq!(joined["history"][1]["reply_to"]["message_id"], id);
+}
+
+#[tokio::test]
+async fn test_long_quote_is_truncated() {
+    let addr = spawn_server().await;
+    let (mut alice, mut bob, _) = pair(addr).await;
+
+    let long = "x".repeat(REPLY_SNIPPET_CHARS * 2);
+    let id = chat(&mut alice, &long).await;
+    bob.send(json!({ "type": "chat", "content": "tl;dr", "reply_to": id }))
+        .await;
+
+    let snippet = alice.expect("chat").await["reply_to"]["snippet"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(snippet, format!("{}…", &long[..REPLY_SNIPPET_CHARS]));
+}
+
+#[tokio::test]
+async fn test_reply_to_unknown_message_is_rejected() {
+    let addr = spawn_server().await;
+    let (mut alice, _bob, _) = pair(addr).await;
+
+    alice
+        .send(json!({ "type": "chat", "content": "what?", "reply_to": 9999 }))
+        .await;
+    let error = alice.expect("error").await;
+    assert_eq!(error["code"], "message_not_found");
+    assert_eq!(error["field"], "reply_to");
+
+    // Deleted messages can't be quoted either
+    let id = chat(&mut alice, "gone soon").await;
+    alice
+        .send(json!({ "type": "delete_message", "message_id": id }))
+        .await;
+    alice.expect("message_deleted").await;
+    alice
+        .send(json!({ "type": "chat", "content": "re", "reply_to": id }))
+        .await;
+    assert_eq!(alice.expect("error").await["field"], "reply_to");
+}
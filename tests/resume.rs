@@ -0,0 +1,150 @@
+//! Integration tests for session resume after a dropped connection
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+
+use chat_server_v1::{DeliveryPolicy, ServerConfig};
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Two named clients sharing a room
+async fn pair(addr: std::net::SocketAddr) -> (TestClient, TestClient, String) {
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+    (alice, bob, room_code)
+}
+
+#[tokio::test]
+async fn test_resume_reclaims_seat_and_buffered_messages() {
+    let addr = spawn_server().await;
+    let (alice, mut bob, room_code) = pair(addr).await;
+    let alice_id = alice.client_id.clone();
+    let token = alice.resume_token.clone();
+
+    // Alice's connection drops; Bob is told she is reconnecting
+    drop(alice);
+    let notice = bob.expect("partner_reconnecting").await;
+    assert_eq!(notice["username"], "Alice");
+
+    // Bob keeps chatting while she is away
+    bob.send(json!({ "type": "chat", "content": "are you there?" }))
+        .await;
+
+    // Alice reconnects and resumes
+    let mut alice = TestClient::connect(addr).await;
+    alice
+        .send(json!({ "type": "resume", "token": token }))
+        .await;
+    let resumed = alice.recv().await;
+    assert_eq!(resumed["type"], "resumed");
+    assert_eq!(resumed["client_id"], alice_id);
+    assert_eq!(resumed["username"], "Alice");
+    assert_eq!(resumed["room_code"], room_code);
+    assert_eq!(resumed["resume_token"], alice.resume_token);
+
+    let buffered = alice.expect("chat").await;
+    assert_eq!(buffered["content"], "are you there?");
+
+    let notice = bob.expect("partner_reconnected").await;
+    assert_eq!(notice["username"], "Alice");
+
+    // The resumed connection acts as Alice in the room
+    alice
+        .send(json!({ "type": "chat", "content": "back!" }))
+        .await;
+    let chat = bob.expect("chat").await;
+    assert_eq!(chat["from"], "Alice");
+    assert_eq!(chat["content"], "back!");
+}
+
+#[tokio::test]
+async fn test_resume_with_unknown_token_fails() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    client
+        .send(json!({ "type": "resume", "token": "not-a-token" }))
+        .await;
+    let err = client.expect("error").await;
+    assert_eq!(err["code"], "resume_failed");
+}
+
+#[tokio::test]
+async fn test_resume_of_live_session_fails() {
+    let addr = spawn_server().await;
+    let alice = TestClient::named(addr, "Alice").await;
+
+    let mut other = TestClient::connect(addr).await;
+    other
+        .send(json!({ "type": "resume", "token": alice.resume_token }))
+        .await;
+    let err = other.expect("error").await;
+    assert_eq!(err["code"], "resume_failed");
+}
+
+#[tokio::test]
+async fn test_seat_released_after_grace_period() {
+    let mut config = ServerConfig::default();
+    config.rooms.resume_grace = Duration::from_millis(200);
+    let addr = spawn_server_with(config).await;
+    let (alice, mut bob, _) = pair(addr).await;
+    let token = alice.resume_token.clone();
+
+    drop(alice);
+    bob.expect("partner_reconnecting").await;
+    bob.expect("partner_left").await;
+
+    let mut alice = TestClient::connect(addr).await;
+    alice
+        .send(json!({ "type": "resume", "token": token }))
+        .await;
+    let err = alice.expect("error").await;
+    assert_eq!(err["code"], "resume_failed");
+}
+
+/// Resume after more messages were buffered than the new outbox holds
+async fn resume_with_full_buffer(policy: DeliveryPolicy) {
+    let mut config = ServerConfig::default();
+    config.connection.outbox_capacity = 8;
+    config.connection.delivery_policy = policy;
+    config.rate_limit.enabled = false;
+    let addr = spawn_server_with(config).await;
+    let (alice, mut bob, _) = pair(addr).await;
+    let token = alice.resume_token.clone();
+
+    drop(alice);
+    bob.expect("partner_reconnecting").await;
+    for i in 0..12 {
+        bob.send(json!({ "type": "chat", "content": i.to_string() }))
+            .await;
+        bob.expect("chat_ack").await;
+    }
+
+    // The prelude comes first, then the newest messages that fit
+    let mut alice = TestClient::connect(addr).await;
+    alice
+        .send(json!({ "type": "resume", "token": token }))
+        .await;
+    assert_eq!(alice.recv().await["type"], "resumed");
+    for i in 5..12 {
+        assert_eq!(alice.expect("chat").await["content"], i.to_string());
+    }
+
+    // The resumed connection is still usable
+    alice
+        .send(json!({ "type": "chat", "content": "back!" }))
+        .await;
+    assert!(alice.expect("chat_ack").await["message_id"].is_u64());
+    assert_eq!(bob.expect("chat").await["content"], "back!");
+}
+
+#[tokio::test]
+async fn test_resume_buffer_larger_than_outbox() {
+    resume_with_full_buffer(DeliveryPolicy::DropOldest).await;
+    resume_with_full_buffer(DeliveryPolicy::DisconnectSlowConsumer).await;
+}
@@ -0,0 +1,92 @@
+//! Integration tests for graceful shutdown
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+
+use chat_server_v1::{ChatServer, ServerCommand, ServerConfig};
+use common::{spawn_controlled_server, TestClient, WAIT};
+
+#[tokio::test]
+async fn test_shutdown_notifies_and_closes_every_client() {
+    let server = spawn_controlled_server(ServerConfig::default(), ChatServer::new).await;
+    let mut alice = TestClient::named(server.addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(server.addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    let mut lobby = TestClient::connect(server.addr).await;
+
+    alice
+        .send(json!({ "type": "chat", "content": "see you" }))
+        .await;
+    bob.expect("chat").await;
+
+    server
+        .cmd_tx
+        .send(ServerCommand::Shutdown {
+            reason: "maintenance".to_string(),
+            reconnect_after: Some(Duration::from_secs(5)),
+        })
+        .await
+        .unwrap();
+
+    // The actor stops once everyone has been told
+    timeout(WAIT, server.actor)
+        .await
+        .expect("actor did not stop")
+        .unwrap();
+
+    for client in [&mut alice, &mut bob, &mut lobby] {
+        let notice = client.expect("server_shutdown").await;
+        assert_eq!(notice["reason"], "maintenance");
+        assert_eq!(notice["reconnect_after"], 5000);
+
+        // Then a proper close frame once the queue is drained
+        match client.next_frame().await {
+            Some(Message::Close(Some(frame))) => assert_eq!(frame.code, CloseCode::Away),
+            other => panic!("Expected close frame, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_queued_messages_are_flushed_before_close() {
+    let server = spawn_controlled_server(ServerConfig::default(), ChatServer::new).await;
+    let mut alice = TestClient::named(server.addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(server.addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+
+    // Bob doesn't read while Alice's messages and the shutdown pile up
+    for i in 0..10 {
+        alice
+            .send(json!({ "type": "chat", "content": format!("message {}", i) }))
+            .await;
+    }
+    // Alice's own round-trip shows her chats reached the actor first
+    alice.send(json!({ "type": "fetch_history" })).await;
+    alice.expect("history").await;
+
+    server
+        .cmd_tx
+        .send(ServerCommand::Shutdown {
+            reason: "restart".to_string(),
+            reconnect_after: None,
+        })
+        .await
+        .unwrap();
+
+    for i in 0..10 {
+        let chat = bob.expect("chat").await;
+        assert_eq!(chat["content"], format!("message {}", i));
+    }
+    let notice = bob.recv().await;
+    assert_eq!(notice["type"], "server_shutdown");
+    assert!(notice.get("reconnect_after").is_none());
+}
@@ -0,0 +1,240 @@
+//! Integration tests for TLS termination, certificate reload and client auth
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::crypto::ring;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use chat_server_v1::{
+    handle_connection, ChatServer, ClientAuth, Peer, RateLimiter, ServerConfig, TlsAcceptor,
+    TlsConfig,
+};
+use common::WAIT;
+
+/// A CA plus a directory to write certificates into
+struct Pki {
+    dir: PathBuf,
+    ca: rcgen::Certificate,
+    ca_key: KeyPair,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("chat-tls-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_key = KeyPair::generate().unwrap();
+        let ca = params.self_signed(&ca_key).unwrap();
+        fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+
+        Self { dir, ca, ca_key }
+    }
+
+    /// Issue a certificate for `name`, returning its DER and key
+    fn issue(&self, name: &str) -> (CertificateDer<'static>, KeyPair) {
+        let params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        (cert.der().clone(), key)
+    }
+
+    /// Issue a server certificate into `cert.pem` / `key.pem`
+    fn write_server_cert(&self) -> CertificateDer<'static> {
+        let params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca, &self.ca_key).unwrap();
+        fs::write(self.dir.join("cert.pem"), cert.pem()).unwrap();
+        fs::write(self.dir.join("key.pem"), key.serialize_pem()).unwrap();
+        cert.der().clone()
+    }
+
+    fn tls_config(&self, client_auth: ClientAuth) -> TlsConfig {
+        TlsConfig {
+            enabled: true,
+            cert: self.dir.join("cert.pem"),
+            key: self.dir.join("key.pem"),
+            client_auth,
+            client_ca: self.dir.join("ca.pem"),
+            ..TlsConfig::default()
+        }
+    }
+
+    /// Client settings trusting this CA, optionally with a client certificate
+    fn client_config(
+        &self,
+        client_cert: Option<(CertificateDer<'static>, KeyPair)>,
+    ) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.ca.der().clone()).unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        match client_cert {
+            Some((cert, key)) => {
+                let key = PrivateKeyDer::try_from(key.serialize_der()).unwrap();
+                builder.with_client_auth_cert(vec![cert], key).unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        }
+    }
+}
+
+impl Drop for Pki {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Start a wss:// server with the given acceptor, returning its address
+async fn spawn_tls_server(acceptor: TlsAcceptor) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Arc::new(ServerConfig::default());
+    let (cmd_tx, cmd_rx) = mpsc::channel(config.command_buffer);
+    let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    tokio::spawn(ChatServer::new(cmd_rx, config.clone()).run());
+
+    tokio::spawn(async move {
+        while let Ok((stream, peer_addr)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            let (cmd_tx, config, limiter) = (cmd_tx.clone(), config.clone(), limiter.clone());
+            tokio::spawn(async move {
+                if let Ok(stream) = acceptor.accept(stream).await {
+                    let peer = Peer::Tcp(peer_addr);
+                    let _ = handle_connection(stream, peer, cmd_tx, config, limiter).await;
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Open a TLS connection and perform the WebSocket handshake
+async fn connect(
+    addr: std::net::SocketAddr,
+    client_config: ClientConfig,
+) -> Result<WebSocketStream<TlsStream<TcpStream>>, Box<dyn std::error::Error>> {
+    let tcp = TcpStream::connect(addr).await?;
+    let connector = TlsConnector::from(Arc::new(client_config));
+    let tls = connector
+        .connect(ServerName::try_from("localhost")?, tcp)
+        .await?;
+    let (ws, _) = tokio_tungstenite::client_async("wss://localhost/", tls).await?;
+    Ok(ws)
+}
+
+/// Receive the next JSON message
+async fn recv(ws: &mut WebSocketStream<TlsStream<TcpStream>>) -> Value {
+    loop {
+        match timeout(WAIT, ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => return serde_json::from_str(&text).unwrap(),
+            Some(Ok(Message::Ping(_))) => continue,
+            other => panic!("Expected text frame, got {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_wss_round_trip() {
+    let pki = Pki::new();
+    pki.write_server_cert();
+    let acceptor = TlsAcceptor::new(&pki.tls_config(ClientAuth::Off)).unwrap();
+    let addr = spawn_tls_server(acceptor).await;
+
+    let mut ws = connect(addr, pki.client_config(None)).await.unwrap();
+    assert_eq!(recv(&mut ws).await["type"], "connected");
+
+    let msg = json!({ "type": "set_username", "username": "Alice" });
+    futures_util::SinkExt::send(&mut ws, Message::Text(msg.to_string()))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await["type"], "username_set");
+}
+
+#[tokio::test]
+async fn test_certificate_reload() {
+    let pki = Pki::new();
+    let first = pki.write_server_cert();
+    let acceptor = TlsAcceptor::new(&pki.tls_config(ClientAuth::Off)).unwrap();
+    let addr = spawn_tls_server(acceptor.clone()).await;
+
+    let ws = connect(addr, pki.client_config(None)).await.unwrap();
+    let served = ws.get_ref().get_ref().1.peer_certificates().unwrap()[0].clone();
+    assert_eq!(served, first);
+    assert!(!acceptor.reload().unwrap(), "nothing changed yet");
+
+    // Replace the files; new handshakes get the new certificate
+    let second = pki.write_server_cert();
+    assert!(acceptor.reload().unwrap());
+
+    let ws = connect(addr, pki.client_config(None)).await.unwrap();
+    let served = ws.get_ref().get_ref().1.peer_certificates().unwrap()[0].clone();
+    assert_eq!(served, second);
+}
+
+#[tokio::test]
+async fn test_broken_reload_keeps_certificate() {
+    let pki = Pki::new();
+    pki.write_server_cert();
+    let acceptor = TlsAcceptor::new(&pki.tls_config(ClientAuth::Off)).unwrap();
+    let addr = spawn_tls_server(acceptor.clone()).await;
+
+    fs::write(pki.dir.join("key.pem"), "not a key").unwrap();
+    assert!(acceptor.reload().is_err());
+
+    let mut ws = connect(addr, pki.client_config(None)).await.unwrap();
+    assert_eq!(recv(&mut ws).await["type"], "connected");
+}
+
+#[tokio::test]
+async fn test_required_client_auth() {
+    let pki = Pki::new();
+    pki.write_server_cert();
+    let acceptor = TlsAcceptor::new(&pki.tls_config(ClientAuth::Required)).unwrap();
+    let addr = spawn_tls_server(acceptor).await;
+
+    // Without a certificate the handshake is refused
+    assert!(connect(addr, pki.client_config(None)).await.is_err());
+
+    // A certificate from the trusted CA gets in
+    let client_cert = pki.issue("client");
+    let mut ws = connect(addr, pki.client_config(Some(client_cert)))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut ws).await["type"], "connected");
+}
+
+#[tokio::test]
+async fn test_optional_client_auth() {
+    let pki = Pki::new();
+    pki.write_server_cert();
+    let acceptor = TlsAcceptor::new(&pki.tls_config(ClientAuth::Optional)).unwrap();
+    let addr = spawn_tls_server(acceptor).await;
+
+    let mut ws = connect(addr, pki.client_config(None)).await.unwrap();
+    assert_eq!(recv(&mut ws).await["type"], "connected");
+}
+
+#[tokio::test]
+async fn test_missing_certificate_is_an_error() {
+    let pki = Pki::new();
+    assert!(TlsAcceptor::new(&pki.tls_config(ClientAuth::Off)).is_err());
+}
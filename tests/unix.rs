@@ -0,0 +1,191 @@
+//! Integration tests for the Unix domain socket listener
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use chat_server_v1::{
+    handle_connection, ChatServer, FileMode, ListenMode, Listener, RateLimiter, ServerConfig,
+    UnixConfig,
+};
+use common::{TestClient, WAIT};
+
+/// A scratch directory removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("chat-unix-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    fn unix_config(&self) -> UnixConfig {
+        UnixConfig {
+            path: self.0.join("chat.sock"),
+            mode: FileMode(0o600),
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Run the accept loop for `listener` against a fresh ChatServer
+fn serve(listener: Listener) {
+    let config = Arc::new(ServerConfig::default());
+    let (cmd_tx, cmd_rx) = mpsc::channel(config.command_buffer);
+    let limiter = Arc::new(RateLimiter::new(config.rate_limit.clone()));
+    tokio::spawn(ChatServer::new(cmd_rx, config.clone()).run());
+
+    tokio::spawn(async move {
+        while let Ok((stream, peer)) = listener.accept().await {
+            let (cmd_tx, config, limiter) = (cmd_tx.clone(), config.clone(), limiter.clone());
+            tokio::spawn(handle_connection(stream, peer, cmd_tx, config, limiter));
+        }
+    });
+}
+
+/// Connect over the socket at `path` and perform the WebSocket handshake
+async fn connect(path: &Path) -> WebSocketStream<UnixStream> {
+    let stream = UnixStream::connect(path).await.unwrap();
+    let (ws, _) = tokio_tungstenite::client_async("ws://localhost/", stream)
+        .await
+        .unwrap();
+    ws
+}
+
+/// Receive messages until one of the given type arrives
+async fn expect(ws: &mut WebSocketStream<UnixStream>, msg_type: &str) -> Value {
+    loop {
+        match timeout(WAIT, ws.next()).await.unwrap() {
+            Some(Ok(Message::Text(text))) => {
+                let msg: Value = serde_json::from_str(&text).unwrap();
+                if msg["type"] == msg_type {
+                    return msg;
+                }
+            }
+            Some(Ok(Message::Ping(_))) => continue,
+            other => panic!("Expected text frame, got {:?}", other),
+        }
+    }
+}
+
+async fn send(ws: &mut WebSocketStream<UnixStream>, value: Value) {
+    ws.send(Message::Text(value.to_string())).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unix_socket_round_trip() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+    let listener = Listener::bind(ListenMode::Unix, "127.0.0.1:0", &unix)
+        .await
+        .unwrap();
+    assert_eq!(
+        listener.addresses(),
+        vec![format!("unix:{}", unix.path.display())]
+    );
+    let mode = fs::metadata(&unix.path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o600);
+    serve(listener);
+
+    let mut ws = connect(&unix.path).await;
+    expect(&mut ws, "connected").await;
+    send(
+        &mut ws,
+        json!({ "type": "set_username", "username": "Alice" }),
+    )
+    .await;
+    expect(&mut ws, "username_set").await;
+}
+
+#[tokio::test]
+async fn test_both_listeners_share_one_server() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+    let listener = Listener::bind(ListenMode::Both, "127.0.0.1:0", &unix)
+        .await
+        .unwrap();
+    let addr = listener.addresses()[0].parse().unwrap();
+    serve(listener);
+
+    // A TCP client and a Unix client meet in the same room
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let code = alice.create_room().await;
+
+    let mut bob = connect(&unix.path).await;
+    expect(&mut bob, "connected").await;
+    send(
+        &mut bob,
+        json!({ "type": "set_username", "username": "Bob" }),
+    )
+    .await;
+    expect(&mut bob, "username_set").await;
+    send(&mut bob, json!({ "type": "join_room", "room_code": code })).await;
+    expect(&mut bob, "room_joined").await;
+
+    alice.send(json!({ "type": "chat", "content": "hi" })).await;
+    assert_eq!(expect(&mut bob, "chat").await["content"], "hi");
+}
+
+#[tokio::test]
+async fn test_stale_socket_is_replaced() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+
+    // A socket file whose listener is gone, as after a crash
+    drop(std::os::unix::net::UnixListener::bind(&unix.path).unwrap());
+    assert!(unix.path.exists());
+
+    let listener = Listener::bind(ListenMode::Unix, "", &unix).await.unwrap();
+    serve(listener);
+    let mut ws = connect(&unix.path).await;
+    expect(&mut ws, "connected").await;
+}
+
+#[tokio::test]
+async fn test_live_socket_is_not_replaced() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+    let _first = Listener::bind(ListenMode::Unix, "", &unix).await.unwrap();
+
+    assert!(Listener::bind(ListenMode::Unix, "", &unix).await.is_err());
+    assert!(unix.path.exists());
+}
+
+#[tokio::test]
+async fn test_other_files_are_not_replaced() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+    fs::write(&unix.path, "important").unwrap();
+
+    assert!(Listener::bind(ListenMode::Unix, "", &unix).await.is_err());
+    assert_eq!(fs::read_to_string(&unix.path).unwrap(), "important");
+}
+
+#[tokio::test]
+async fn test_socket_removed_on_drop() {
+    let dir = TempDir::new();
+    let unix = dir.unix_config();
+    let listener = Listener::bind(ListenMode::Unix, "", &unix).await.unwrap();
+    assert!(unix.path.exists());
+
+    drop(listener);
+    assert!(!unix.path.exists());
+}
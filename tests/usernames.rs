@@ -0,0 +1,126 @@
+//! Integration tests for the username policy, uniqueness and renames
+
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+
+use chat_server_v1::ServerConfig;
+use common::{spawn_server, spawn_server_with, TestClient};
+
+/// Server settings with unique usernames and no held seats
+fn unique_names() -> ServerConfig {
+    let mut config = ServerConfig::default();
+    config.usernames.unique = true;
+    config.usernames.reject_mixed_scripts = false;
+    config.rooms.resume_grace = Duration::ZERO;
+    config
+}
+
+async fn set_username(client: &mut TestClient, username: &str) {
+    client
+        .send(json!({ "type": "set_username", "username": username }))
+        .await;
+}
+
+#[tokio::test]
+async fn test_invalid_username_is_rejected() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    for username in ["", "   ", "Al\u{0}ice", "admin", &"x".repeat(33)] {
+        set_username(&mut client, username).await;
+        let error = client.expect("error").await;
+        assert_eq!(error["code"], "invalid_username", "{:?}", username);
+        assert_eq!(error["field"], "username");
+    }
+
+    // The client is still unnamed
+    client.send(json!({ "type": "create_room" })).await;
+    assert_eq!(client.expect("error").await["code"], "username_required");
+}
+
+#[tokio::test]
+async fn test_username_is_normalized() {
+    let addr = spawn_server().await;
+    let mut client = TestClient::connect(addr).await;
+
+    set_username(&mut client, "  Rene\u{301} ").await;
+    let msg = client.expect("username_set").await;
+    assert_eq!(msg["username"], "Ren\u{e9}");
+}
+
+#[tokio::test]
+async fn test_unique_usernames() {
+    let addr = spawn_server_with(unique_names()).await;
+    let alice = TestClient::named(addr, "Alice").await;
+    let mut other = TestClient::connect(addr).await;
+
+    // Same name, different case, and a look-alike (Cyrillic "А") are all taken
+    for username in ["Alice", "ALICE", "\u{410}lice"] {
+        set_username(&mut other, username).await;
+        let error = other.expect("error").await;
+        assert_eq!(error["code"], "username_taken", "{:?}", username);
+    }
+
+    // The name is free again once its owner is gone
+    drop(alice);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    set_username(&mut other, "Alice").await;
+    other.expect("username_set").await;
+}
+
+#[tokio::test]
+async fn test_rename_keeps_own_name() {
+    let addr = spawn_server_with(unique_names()).await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+
+    // Setting your own name again (in another case) is not a conflict
+    set_username(&mut alice, "alice").await;
+    assert_eq!(alice.expect("username_set").await["username"], "alice");
+}
+
+#[tokio::test]
+async fn test_rename_notifies_partner() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    let room_code = alice.create_room().await;
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("partner_joined").await;
+
+    set_username(&mut bob, "Robert").await;
+    bob.expect("username_set").await;
+
+    let msg = alice.expect("partner_renamed").await;
+    assert_eq!(msg["old_username"], "Bob");
+    assert_eq!(msg["username"], "Robert");
+
+    // Chat carries the new name
+    bob.send(json!({ "type": "chat", "content": "hi" })).await;
+    assert_eq!(alice.expect("chat").await["from"], "Robert");
+}
+
+#[tokio::test]
+async fn test_rename_notifies_group_members() {
+    let addr = spawn_server().await;
+    let mut alice = TestClient::named(addr, "Alice").await;
+    alice
+        .send(json!({ "type": "create_room", "capacity": 3 }))
+        .await;
+    let room_code = alice.expect("room_created").await["room_code"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let mut bob = TestClient::named(addr, "Bob").await;
+    bob.join_room(&room_code).await;
+    alice.expect("member_joined").await;
+
+    set_username(&mut alice, "Alicia").await;
+
+    let msg = bob.expect("member_renamed").await;
+    assert_eq!(msg["old_username"], "Alice");
+    assert_eq!(msg["username"], "Alicia");
+    assert_eq!(msg["members"], json!(["Alicia", "Bob"]));
+}